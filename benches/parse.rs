@@ -0,0 +1,135 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use nb_serde_query::{from_str, query_map::QueryMap};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Small {
+    q: String,
+    page: u32,
+    limit: u32,
+    sort: String,
+    desc: bool,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Repeated {
+    ids: Vec<u64>,
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct Wide {
+    f0: u32,
+    f1: u32,
+    f2: u32,
+    f3: u32,
+    f4: u32,
+    f5: u32,
+    f6: u32,
+    f7: u32,
+    f8: u32,
+    f9: u32,
+    f10: u32,
+    f11: u32,
+    f12: u32,
+    f13: u32,
+    f14: u32,
+    f15: u32,
+    f16: u32,
+    f17: u32,
+    f18: u32,
+    f19: u32,
+    f20: u32,
+    f21: u32,
+    f22: u32,
+    f23: u32,
+    f24: u32,
+    f25: u32,
+    f26: u32,
+    f27: u32,
+    f28: u32,
+    f29: u32,
+}
+
+fn small() -> String {
+    "q=rust%20serde&page=3&limit=20&sort=created_at&desc=true".to_string()
+}
+
+fn medium() -> String {
+    (0..50)
+        .map(|i| format!("key{}=value%20number%20{}", i, i))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn wide() -> String {
+    (0..20)
+        .map(|i| format!("key{}={}", i, i))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn wide_struct() -> String {
+    (0..30)
+        .map(|i| format!("f{}={}", i, i))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn repeated() -> String {
+    (0..500)
+        .map(|i| format!("ids={}", i))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let s = small();
+    c.bench_function("small 5 pairs", |b| {
+        b.iter(|| from_str::<Small>(black_box(&s)).unwrap())
+    });
+    let s = medium();
+    c.bench_function("medium 50 pairs", |b| {
+        b.iter(|| from_str::<HashMap<String, String>>(black_box(&s)).unwrap())
+    });
+    c.bench_function("medium 50 pairs query map", |b| {
+        b.iter(|| QueryMap::parse(black_box(&s)).unwrap())
+    });
+    let s = wide();
+    c.bench_function("wide 20 single values", |b| {
+        b.iter(|| from_str::<HashMap<String, u32>>(black_box(&s)).unwrap())
+    });
+    let s = wide_struct();
+    c.bench_function("struct 30 fields", |b| {
+        b.iter(|| from_str::<Wide>(black_box(&s)).unwrap())
+    });
+    let s = repeated();
+    c.bench_function("repeated 500 values", |b| {
+        b.iter(|| from_str::<Repeated>(black_box(&s)).unwrap())
+    });
+
+    // a new input per call, as a server reads one per request
+    c.bench_function("small fresh query map per input", |b| {
+        b.iter(|| {
+            let s = small();
+            QueryMap::parse(black_box(&s))
+                .unwrap()
+                .deserialize_into::<Small>()
+                .unwrap()
+        })
+    });
+    let mut m = QueryMap::new();
+    c.bench_function("small reused query map per input", |b| {
+        b.iter(|| {
+            let s = small();
+            m.reparse(black_box(&s)).unwrap();
+            m.deserialize_into::<Small>().unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,100 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use nb_serde_query::{options::SerializerOptions, to_string, to_strings, Serializer};
+use serde::Serialize;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// Counts allocations, to compare the batch helper with serializing each value on its own.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+#[derive(Serialize)]
+struct Small {
+    q: &'static str,
+    page: u32,
+    limit: u32,
+    sort: &'static str,
+    desc: bool,
+}
+
+const SMALL: Small = Small {
+    q: "rust serde",
+    page: 3,
+    limit: 20,
+    sort: "created_at",
+    desc: true,
+};
+
+fn bench_serialize(c: &mut Criterion) {
+    c.bench_function("fresh serializer", |b| {
+        b.iter(|| to_string(black_box(&SMALL)).unwrap())
+    });
+    let mut serializer = Serializer::with_capacity(8, SerializerOptions::new());
+    let mut out = String::new();
+    c.bench_function("reused serializer", |b| {
+        b.iter(|| {
+            serializer.reset();
+            black_box(&SMALL).serialize(&mut serializer).unwrap();
+            out.clear();
+            serializer.write_output(&mut out);
+            out.len()
+        })
+    });
+}
+
+fn bench_batch(c: &mut Criterion) {
+    let values: Vec<Small> = (0..1000).map(|_| SMALL).collect();
+    let naive = allocations(|| {
+        values
+            .iter()
+            .map(|v| to_string(v).unwrap())
+            .collect::<Vec<_>>()
+    });
+    let batch = allocations(|| to_strings(&values).unwrap());
+    println!(
+        "allocations for {} values: {} one by one, {} in a batch",
+        values.len(),
+        naive,
+        batch
+    );
+
+    let mut group = c.benchmark_group("1000 values");
+    group.bench_function("one by one", |b| {
+        b.iter(|| {
+            black_box(&values)
+                .iter()
+                .map(|v| to_string(v).unwrap())
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("batch", |b| {
+        b.iter(|| to_strings(black_box(&values)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_serialize, bench_batch);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+//! Reading a query string into a struct and writing it back.
+
+use nb_serde_query::{
+    from_str, from_str_with,
+    options::{ArrayStyle, ParseOptions, SerializerOptions},
+    to_string, to_string_with,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Search {
+    q: String,
+    page: Option<u32>,
+    tags: Vec<String>,
+    exact: bool,
+}
+
+fn main() -> Result<(), nb_serde_query::error::Error> {
+    let search: Search = from_str("q=rust%20serde&tags=web&page=2&tags=cli&exact=false")?;
+    println!("{:?}", search);
+    println!("{}", to_string(&search)?);
+
+    let opts = SerializerOptions::new().array_style(ArrayStyle::Comma);
+    println!("{}", to_string_with(&search, &opts)?);
+
+    let opts = ParseOptions::new()
+        .lenient_bool(true)
+        .separators(&['&', ';']);
+    let search: Search = from_str_with("q=query;tags=a;tags=b&exact=yes", &opts)?;
+    println!("{:?}", search);
+    Ok(())
+}
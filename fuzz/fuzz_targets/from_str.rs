@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nb_serde_query::from_str;
+use nb_serde_query_fuzz::{Extras, SampleStruct};
+use std::collections::HashMap;
+
+fuzz_target!(|data: &[u8]| {
+    let s = String::from_utf8_lossy(data);
+    let _ = from_str::<SampleStruct>(&s);
+    let _ = from_str::<HashMap<String, String>>(&s);
+    let _ = from_str::<Extras>(&s);
+});
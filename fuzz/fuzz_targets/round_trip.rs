@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nb_serde_query::{from_str, to_string};
+use nb_serde_query_fuzz::SampleStruct;
+
+fuzz_target!(|v: SampleStruct| {
+    let s = to_string(&v).unwrap_or_else(|e| panic!("failed to serialize {:?}: {}", v, e));
+    let back: SampleStruct =
+        from_str(&s).unwrap_or_else(|e| panic!("failed to parse {:?}: {}", s, e));
+    assert!(back.same_as(&v), "{:?} round tripped through {:?} as {:?}", v, s, back);
+});
@@ -0,0 +1,53 @@
+//! Types shared by the fuzz targets.
+use arbitrary::Arbitrary;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Arbitrary, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Open,
+    Closed,
+}
+
+/// A struct covering the scalar, optional and sequence types most queries are made of.
+#[derive(Debug, Clone, PartialEq, Arbitrary, Serialize, Deserialize)]
+pub struct SampleStruct {
+    pub q: String,
+    pub c: char,
+    pub exact: bool,
+    pub page: Option<u32>,
+    pub offset: i64,
+    pub ratio: f64,
+    pub status: Option<Status>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub ids: Vec<u16>,
+}
+
+impl SampleStruct {
+    /// Equality where a NaN ratio equals any other NaN.
+    pub fn same_as(&self, other: &Self) -> bool {
+        let ratio = self.ratio == other.ratio || (self.ratio.is_nan() && other.ratio.is_nan());
+        ratio && Self { ratio: 0.0, ..self.clone() } == Self { ratio: 0.0, ..other.clone() }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Marker;
+
+#[derive(Debug, Deserialize)]
+pub struct Point(pub i32, pub i32);
+
+/// The less common shapes: units, tuples and values which are read and ignored.
+#[derive(Debug, Deserialize)]
+pub struct Extras {
+    #[serde(default)]
+    pub unit: (),
+    pub marker: Option<Marker>,
+    pub pair: Option<(u8, String)>,
+    pub point: Option<Point>,
+    #[serde(flatten)]
+    pub rest: HashMap<String, IgnoredAny>,
+}
@@ -1,11 +1,94 @@
-use crate::from_str;
-use actix_web::{error::ErrorBadRequest, Error, FromRequest, HttpRequest};
+//! The [`Query`] extractor for actix-web.
+//!
+//! The extractor reads with the [`QueryConfig`] of the innermost scope registering one with
+//! `app_data`, e.g. a strict one on an admin scope and a lenient one on the public API of the
+//! same app, falling back to one registered as `web::Data` and then to the default.
+//!
+//! Rejected query strings are answered as [`QueryConfig`] describes. Extractors of other
+//! parameters can answer the same way by returning the errors this crate would:
+//!
+//! ```
+//! use actix_web::{dev::Payload, error::ErrorBadRequest, test, web, App, FromRequest, HttpRequest};
+//! use futures::future::{ready, Ready};
+//! use nb_serde_query::error::{Error, ErrorKind};
+//!
+//! struct Cursor(u64);
+//!
+//! impl FromRequest for Cursor {
+//!     type Error = actix_web::Error;
+//!     type Future = Ready<Result<Self, Self::Error>>;
+//!
+//!     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+//!         let res = match req.headers().get("x-cursor").map(|v| v.to_str().unwrap_or("")) {
+//!             None => Err(Error::missing("x-cursor")),
+//!             Some(v) => u64::from_str_radix(v, 16)
+//!                 .map(Cursor)
+//!                 .map_err(|_| Error::invalid_value("x-cursor", "a hex cursor", v)),
+//!         };
+//!         ready(res.map_err(ErrorBadRequest))
+//!     }
+//! }
+//!
+//! # actix_web::rt::System::new().block_on(async {
+//! let app = test::init_service(
+//!     App::new().route("/", web::get().to(|c: Cursor| async move { c.0.to_string() })),
+//! )
+//! .await;
+//! let req = test::TestRequest::with_uri("/").insert_header(("x-cursor", "zz"));
+//! let resp = test::call_service(&app, req.to_request()).await;
+//! assert_eq!(resp.status(), 400);
+//! assert_eq!(
+//!     test::read_body(resp).await,
+//!     "invalid value `zz` for key `x-cursor`, expected a hex cursor"
+//! );
+//!
+//! let err = Error::invalid_value("x-cursor", "a hex cursor", "zz");
+//! assert!(matches!(err.kind(), ErrorKind::InvalidValue { .. }));
+//! assert_eq!(err.key(), Some("x-cursor"));
+//! # });
+//! ```
+pub use crate::extract::QueryConfig;
+use crate::{
+    error::{Error as QueryError, Errors},
+    extract::{errors_body, extract_or_reject, extract_query, Rejection},
+};
+use actix_web::{
+    error::InternalError, http::StatusCode, web, Error, FromRequest, HttpRequest, HttpResponse,
+    ResponseError,
+};
 use futures::future::Ready;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
 pub struct Query<T>(pub T);
 
+impl<T> Query<T>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    /// Deserialize `T` from `s` with the options of the default [`QueryConfig`], as the
+    /// extractor would from a request without registered configuration, e.g. to call a handler
+    /// directly in a test.
+    pub fn extract_from_query_str(s: &str) -> Result<Self, QueryError> {
+        extract_query(Some(s), None).map(Query)
+    }
+}
+
+/// Deserialize `T` from the query string of `req` with the registered [`QueryConfig`], for use
+/// where extractors are not available, e.g. in middleware. A request without a query string
+/// reads as an empty one.
+pub fn parse_query<T>(req: &HttpRequest) -> Result<T, QueryError>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    extract_query(Some(req.query_string()), query_config(req))
+}
+
+fn query_config(req: &HttpRequest) -> Option<&QueryConfig> {
+    req.app_data::<QueryConfig>()
+        .or_else(|| req.app_data::<web::Data<QueryConfig>>().map(|d| d.as_ref()))
+}
+
 impl<T> FromRequest for Query<T>
 where
     for<'de> T: Deserialize<'de>,
@@ -14,14 +97,40 @@ where
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
-        let query = req.query_string();
-        match from_str(query).map_err(ErrorBadRequest) {
+        match extract_or_reject(Some(req.query_string()), query_config(req)) {
             Ok(v) => futures::future::ready(Ok(Query(v))),
-            Err(e) => futures::future::ready(Err(e)),
+            Err((errors, rejection)) => futures::future::ready(Err(reject(errors, rejection))),
         }
     }
 }
 
+fn reject(errors: Errors, rejection: Rejection) -> Error {
+    let status = StatusCode::from_u16(rejection.status).unwrap_or(StatusCode::BAD_REQUEST);
+    let content_type = if rejection.json {
+        "application/json"
+    } else {
+        "text/plain; charset=utf-8"
+    };
+    let response = HttpResponse::build(status)
+        .content_type(content_type)
+        .body(rejection.body);
+    InternalError::from_response(errors, response).into()
+}
+
+/// Answered with `400 Bad Request` and a JSON body listing the errors, e.g.
+/// `{"errors":[{"param":"limit","message":"..."}]}`.
+impl ResponseError for Errors {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .content_type("application/json")
+            .body(errors_body(self))
+    }
+}
+
 impl<T> Serialize for Query<T>
 where
     T: Serialize,
@@ -45,3 +154,205 @@ where
         T::deserialize(deserializer).map(Query)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::ParseOptions;
+    use actix_web::{
+        http::StatusCode,
+        test::{self, TestRequest},
+        App,
+    };
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Paging {
+        page: Option<u32>,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let req = TestRequest::with_uri("/items?page=2&tags=a&tags=b").to_http_request();
+        assert_eq!(
+            parse_query::<Paging>(&req).unwrap(),
+            Paging {
+                page: Some(2),
+                tags: vec!["a".to_string(), "b".to_string()],
+            }
+        );
+        for uri in ["/items", "/items?"] {
+            let req = TestRequest::with_uri(uri).to_http_request();
+            assert_eq!(
+                parse_query::<Paging>(&req).unwrap(),
+                Paging {
+                    page: None,
+                    tags: vec![],
+                }
+            );
+        }
+
+        let req = TestRequest::with_uri("/items?page=1&tags=a&tags=b")
+            .app_data(QueryConfig::default().options(ParseOptions::new().max_pairs(2)))
+            .to_http_request();
+        let err = parse_query::<Paging>(&req).unwrap_err();
+        assert_eq!(err.message, "too many pairs, at most 2 allowed");
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Required {
+        page: u32,
+    }
+
+    #[actix_web::test]
+    async fn test_extractor_without_query() {
+        let app = test::init_service(
+            App::new()
+                .route(
+                    "/items",
+                    web::get().to(|q: Query<Paging>| async move { format!("{:?}", q.0.page) }),
+                )
+                .route(
+                    "/required",
+                    web::get().to(|q: Query<Required>| async move { q.0.page.to_string() }),
+                ),
+        )
+        .await;
+        for uri in ["/items", "/items?", "/items?page=3"] {
+            let resp = test::call_service(&app, TestRequest::with_uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+        }
+        for uri in ["/required", "/required?"] {
+            let resp = test::call_service(&app, TestRequest::with_uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            let body = test::read_body(resp).await;
+            assert_eq!(body, "missing field `page`");
+        }
+    }
+
+    async fn list(q: Query<Paging>) -> String {
+        format!("page {} of {}", q.0.page.unwrap_or(1), q.0.tags.join("+"))
+    }
+
+    #[actix_web::test]
+    async fn test_handler_with_query() {
+        let q = Query::<Paging>::extract_from_query_str("tags=a&page=2&tags=b").unwrap();
+        assert_eq!(list(q).await, "page 2 of a+b");
+        let q = Query::<Paging>::extract_from_query_str("?tags=a").unwrap();
+        assert_eq!(list(q).await, "page 1 of a");
+
+        let err = Query::<Paging>::extract_from_query_str("page=x").unwrap_err();
+        assert_eq!(
+            err.message,
+            "invalid value `x` for key `page`, expected u32"
+        );
+
+        let app = test::init_service(App::new().route("/items", web::get().to(list))).await;
+        let req = TestRequest::with_uri("/items?tags=a&page=2&tags=b").to_request();
+        let body = test::read_body(test::call_service(&app, req).await).await;
+        assert_eq!(body, "page 2 of a+b");
+    }
+
+    #[actix_web::test]
+    async fn test_limit_response() {
+        let config = QueryConfig::default()
+            .options(ParseOptions::new().max_input_len(32).max_values_per_key(2));
+        let app = test::init_service(
+            App::new()
+                .app_data(config)
+                .route("/items", web::get().to(list)),
+        )
+        .await;
+        for (uri, body) in [
+            (
+                "/items?tags=a&tags=b&tags=c",
+                serde_json::json!({
+                    "error": "too many values for key `tags`, at most 2 allowed",
+                    "limit": {"kind": "values_per_key", "key": "tags", "max": 2},
+                }),
+            ),
+            (
+                "/items?tags=aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+                serde_json::json!({
+                    "error": "input too long, at most 32 bytes allowed",
+                    "limit": {"kind": "input_length", "key": null, "max": 32},
+                }),
+            ),
+        ] {
+            let resp = test::call_service(&app, TestRequest::with_uri(uri).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(
+                resp.headers().get("content-type").unwrap(),
+                "application/json"
+            );
+            let resp: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(resp, body);
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Range {
+        limit: i32,
+        from: u64,
+    }
+
+    async fn range(q: Query<Range>) -> String {
+        format!("{} from {}", q.0.limit, q.0.from)
+    }
+
+    #[actix_web::test]
+    async fn test_all_errors_response() {
+        let config = QueryConfig::default().all_errors(true).error_status(422);
+        let app = test::init_service(
+            App::new()
+                .app_data(config)
+                .route("/range", web::get().to(range)),
+        )
+        .await;
+        let req = TestRequest::with_uri("/range?limit=abc&from=yesterday").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            resp.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(
+            body,
+            serde_json::json!({"errors": [
+                {
+                    "param": "limit",
+                    "message": "invalid value `abc` for key `limit`, expected i32: \
+                                invalid digit found in string",
+                },
+                {
+                    "param": "from",
+                    "message": "invalid value `yesterday` for key `from`, expected u64: \
+                                invalid digit found in string",
+                },
+            ]})
+        );
+
+        let req = TestRequest::with_uri("/range?limit=5&from=1").to_request();
+        let body = test::read_body(test::call_service(&app, req).await).await;
+        assert_eq!(body, "5 from 1");
+
+        // without the configuration only the first error is answered, as text
+        let app = test::init_service(App::new().route("/range", web::get().to(range))).await;
+        let req = TestRequest::with_uri("/range?limit=abc&from=yesterday").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            test::read_body(resp).await,
+            "invalid value `abc` for key `limit`, expected i32: invalid digit found in string"
+        );
+
+        // `Errors` answers the same way when returned by a handler
+        let errors =
+            crate::from_str_all_errors::<Range>("from=x", &ParseOptions::new()).unwrap_err();
+        assert_eq!(errors.to_string(), "invalid value `x` for key `from`, expected u64: invalid digit found in string; missing field `limit`");
+        let resp = errors.error_response();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
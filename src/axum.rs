@@ -0,0 +1,41 @@
+//! The [`Query`] extractor for axum; see [`QueryConfig`] for how rejections are answered.
+use crate::extract::extract_or_reject;
+pub use crate::extract::QueryConfig;
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+/// Deserializes `T` from the query string with the [`QueryConfig`] registered with
+/// `Extension`, or the default one.
+#[derive(Debug)]
+pub struct Query<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for Query<T>
+where
+    S: Send + Sync,
+    for<'de> T: Deserialize<'de>,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        extract_or_reject(parts.uri.query(), parts.extensions.get::<QueryConfig>())
+            .map(Query)
+            .map_err(|(_, rejection)| {
+                let status =
+                    StatusCode::from_u16(rejection.status).unwrap_or(StatusCode::BAD_REQUEST);
+                if rejection.json {
+                    (
+                        status,
+                        [(header::CONTENT_TYPE, "application/json")],
+                        rejection.body,
+                    )
+                        .into_response()
+                } else {
+                    (status, rejection.body).into_response()
+                }
+            })
+    }
+}
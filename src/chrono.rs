@@ -0,0 +1,154 @@
+//! Date and time parameters.
+//!
+//! `DateTime<Utc>`, `DateTime<FixedOffset>`, `NaiveDate` and `NaiveDateTime` fields work as they
+//! are, through the string impls of chrono: RFC 3339 (`2024-02-01T12:00:00Z`), `2024-01-01` and
+//! `2024-02-01T12:00:00` respectively. Other formats can be used with `#[serde(with = "...")]`.
+//!
+//! [`UnixTimestamp`] and [`UnixTimestampMillis`] read and write epoch seconds and milliseconds.
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{de::Error as DeError, Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::Deref;
+
+/// A point in time written as seconds since the Unix epoch, e.g. `since=1704067200`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnixTimestamp(pub DateTime<Utc>);
+
+/// A point in time written as milliseconds since the Unix epoch, e.g. `since=1704067200000`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnixTimestampMillis(pub DateTime<Utc>);
+
+impl Deref for UnixTimestamp {
+    type Target = DateTime<Utc>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Deref for UnixTimestampMillis {
+    type Target = DateTime<Utc>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Serialize for UnixTimestamp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i64(self.0.timestamp())
+    }
+}
+
+impl<'de> Deserialize<'de> for UnixTimestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let secs = i64::deserialize(deserializer)?;
+        Utc.timestamp_opt(secs, 0)
+            .single()
+            .map(UnixTimestamp)
+            .ok_or(D::Error::custom(format!(
+                "timestamp `{}` out of range",
+                secs
+            )))
+    }
+}
+
+impl Serialize for UnixTimestampMillis {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i64(self.0.timestamp_millis())
+    }
+}
+
+impl<'de> Deserialize<'de> for UnixTimestampMillis {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let millis = i64::deserialize(deserializer)?;
+        Utc.timestamp_millis_opt(millis)
+            .single()
+            .map(UnixTimestampMillis)
+            .ok_or(D::Error::custom(format!(
+                "timestamp `{}` out of range",
+                millis
+            )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::ErrorKind, from_str, to_string};
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Range {
+        from: NaiveDate,
+        to: DateTime<Utc>,
+        at: Option<NaiveDateTime>,
+        since: Option<UnixTimestamp>,
+        until: Option<UnixTimestampMillis>,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let v: Range = from_str("from=2024-01-01&to=2024-02-01T12:00:00Z").unwrap();
+        assert_eq!(v.from, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        assert_eq!(
+            v.to,
+            Utc.with_ymd_and_hms(2024, 2, 1, 12, 0, 0).single().unwrap()
+        );
+        assert_eq!(v.at, None);
+        assert_eq!(from_str::<Range>(&to_string(&v).unwrap()).unwrap(), v);
+
+        let v: Range = from_str(
+            "from=2024-01-01&to=2024-02-01T12:00:00%2B08:00&at=2024-01-01T08:30:00.5\
+             &since=1704067200&until=1704067200123",
+        )
+        .unwrap();
+        assert_eq!(
+            v.to,
+            Utc.with_ymd_and_hms(2024, 2, 1, 4, 0, 0).single().unwrap()
+        );
+        assert_eq!(
+            v.at.unwrap().to_string(),
+            "2024-01-01 08:30:00.500".to_string()
+        );
+        assert_eq!(v.since.unwrap().timestamp(), 1704067200);
+        assert_eq!(v.until.unwrap().timestamp_millis(), 1704067200123);
+        let s = to_string(&v).unwrap();
+        assert!(s.ends_with("&since=1704067200&until=1704067200123"));
+        assert_eq!(from_str::<Range>(&s).unwrap(), v);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let err = from_str::<Range>("from=2024-13-01&to=2024-02-01T12:00:00Z").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::InvalidValue {
+                key: "from".to_string(),
+                expected: "a formatted date string".to_string(),
+            }
+        );
+        assert_eq!(
+            err.message,
+            "invalid value for key `from`, expected a formatted date string"
+        );
+        let err = from_str::<Range>("from=2024-01-01&to=2024-02-01").unwrap_err();
+        assert!(err
+            .message
+            .starts_with("invalid value for key `to`, expected"));
+        let err =
+            from_str::<Range>("from=2024-01-01&to=2024-02-01T12:00:00Z&since=soon").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidNumber);
+    }
+}
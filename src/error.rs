@@ -1,28 +1,208 @@
-use serde::de::Error as DeError;
+use serde::de::{Error as DeError, Expected, Unexpected};
 use serde::ser::Error as SerError;
 use std::{error::Error as StdError, fmt::Display};
 
+/// What went wrong. New kinds may be added, so matches need a wildcard arm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    Other,
+    /// An error raised by the type being deserialized, through `serde::de::Error::custom`.
+    Custom,
+    /// The type being deserialized does not accept the type of a value, through
+    /// `serde::de::Error::invalid_type`, e.g. a 128-bit integer for a 64-bit one.
+    InvalidType,
+    /// The input exceeded one of the configured limits.
+    LimitExceeded {
+        /// The key whose value or values exceeded the limit, for limits of a single key.
+        key: Option<String>,
+        limit: usize,
+        kind: LimitKind,
+    },
+    /// A value is not a number of the expected type.
+    InvalidNumber,
+    /// A value was rejected by the type parsing it from a string, e.g. a malformed date.
+    InvalidValue {
+        key: String,
+        /// What the type expected, as described by its visitor.
+        expected: String,
+    },
+    /// A number does not fit the expected type, or was rejected by it, e.g. `0` for a
+    /// `NonZeroU32`.
+    OutOfRange,
+    /// A field without a default has no key in the input.
+    MissingField {
+        field: String,
+    },
+    /// A key does not name a field, e.g. with
+    /// [`deny_unknown`](crate::options::ParseOptions::deny_unknown). Of several unknown keys, the
+    /// first in byte order is named.
+    UnknownField {
+        field: String,
+    },
+}
+
+/// The limits of [`ParseOptions`](crate::options::ParseOptions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LimitKind {
+    /// [`max_pairs`](crate::options::ParseOptions::max_pairs)
+    Pairs,
+    /// [`max_value_len`](crate::options::ParseOptions::max_value_len)
+    ValueLength,
+    /// [`max_values_per_key`](crate::options::ParseOptions::max_values_per_key)
+    ValuesPerKey,
+    /// [`max_input_len`](crate::options::ParseOptions::max_input_len)
+    InputLength,
+    /// [`max_depth`](crate::options::ParseOptions::max_depth)
+    Depth,
+}
+
+impl LimitKind {
+    /// The name of the limit in snake case, e.g. `values_per_key`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LimitKind::Pairs => "pairs",
+            LimitKind::ValueLength => "value_length",
+            LimitKind::ValuesPerKey => "values_per_key",
+            LimitKind::InputLength => "input_length",
+            LimitKind::Depth => "depth",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Error {
+    pub kind: ErrorKind,
     pub message: String,
     pub cause: Option<Box<dyn StdError>>,
+    /// Where the failing value is in nested input, e.g. `filter.ranges[2].min`. Values at the
+    /// top level are named by the message alone.
+    pub path: Option<String>,
 }
 
 impl Error {
     pub fn new(message: impl Into<String>, cause: Option<Box<dyn StdError>>) -> Self {
+        Self::with_kind(ErrorKind::Other, message, cause)
+    }
+
+    pub fn with_kind(
+        kind: ErrorKind,
+        message: impl Into<String>,
+        cause: Option<Box<dyn StdError>>,
+    ) -> Self {
         Error {
+            kind,
             message: message.into(),
             cause,
+            path: None,
+        }
+    }
+
+    /// A value of `key` rejected by its type, with the message of the errors this crate returns,
+    /// e.g. for a value a custom extractor failed to parse.
+    pub fn invalid_value(
+        key: impl Into<String>,
+        expected: impl Into<String>,
+        got: impl Display,
+    ) -> Self {
+        let (key, expected) = (key.into(), expected.into());
+        let message = format!(
+            "invalid value `{}` for key `{}`, expected {}",
+            got, key, expected
+        );
+        Self::with_kind(ErrorKind::InvalidValue { key, expected }, message, None)
+    }
+
+    /// An input exceeding the `limit` of `kind`, for `key` when the limit applies to each key.
+    pub fn limit_exceeded(kind: LimitKind, limit: usize, key: Option<String>) -> Self {
+        let k = key.as_deref().unwrap_or_default();
+        let message = match kind {
+            LimitKind::Pairs => format!("too many pairs, at most {} allowed", limit),
+            LimitKind::ValueLength => format!(
+                "value of key `{}` too long, at most {} bytes allowed",
+                k, limit
+            ),
+            LimitKind::ValuesPerKey => {
+                format!("too many values for key `{}`, at most {} allowed", k, limit)
+            }
+            LimitKind::InputLength => format!("input too long, at most {} bytes allowed", limit),
+            LimitKind::Depth => format!("too deeply nested, at most {} levels allowed", limit),
+        };
+        Self::with_kind(ErrorKind::LimitExceeded { key, limit, kind }, message, None)
+    }
+
+    /// A field without a default which has no key in the input.
+    pub fn missing(field: impl Into<String>) -> Self {
+        let field = field.into();
+        let message = format!("missing field `{}`", field);
+        Self::with_kind(ErrorKind::MissingField { field }, message, None)
+    }
+
+    /// A key which does not name a field.
+    pub fn unknown_field(field: impl Into<String>) -> Self {
+        let field = field.into();
+        let message = format!("unknown field `{}`", field);
+        Self::with_kind(ErrorKind::UnknownField { field }, message, None)
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// The key or field the error is about, when its kind names one.
+    pub fn key(&self) -> Option<&str> {
+        match &self.kind {
+            ErrorKind::InvalidValue { key, .. } => Some(key),
+            ErrorKind::MissingField { field } | ErrorKind::UnknownField { field } => Some(field),
+            ErrorKind::LimitExceeded { key, .. } => key.as_deref(),
+            _ => None,
         }
     }
 }
 
 impl DeError for Error {
     fn custom<T: std::fmt::Display>(msg: T) -> Self {
-        Error {
-            message: msg.to_string(),
-            cause: None,
+        Error::with_kind(ErrorKind::Custom, msg.to_string(), None)
+    }
+
+    // worded like serde's own message
+    fn invalid_type(unexp: Unexpected, exp: &dyn Expected) -> Self {
+        Error::with_kind(
+            ErrorKind::InvalidType,
+            format!("invalid type: {}, expected {}", unexp, exp),
+            None,
+        )
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Error::missing(field)
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        let mut e = Error::unknown_field(field);
+        // worded like serde's own message
+        match expected {
+            [] => e.message.push_str(", there are no fields"),
+            [a] => e.message.push_str(&format!(", expected `{}`", a)),
+            [a, b] => e
+                .message
+                .push_str(&format!(", expected `{}` or `{}`", a, b)),
+            _ => {
+                let names: Vec<String> = expected.iter().map(|n| format!("`{}`", n)).collect();
+                e.message
+                    .push_str(&format!(", expected one of {}", names.join(", ")));
+            }
         }
+        e
     }
 }
 
@@ -31,15 +211,15 @@ impl SerError for Error {
     where
         T: Display,
     {
-        Error {
-            message: msg.to_string(),
-            cause: None,
-        }
+        Error::new(msg.to_string(), None)
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "at `{}`: ", path)?;
+        }
         if let Some(cause) = &self.cause {
             return write!(f, "{}: {}", self.message, cause);
         }
@@ -48,3 +228,72 @@ impl Display for Error {
 }
 
 impl StdError for Error {}
+
+/// The errors of all rejected parameters of an input, as returned by
+/// [`from_str_all_errors`](crate::from_str_all_errors), each with the top level key it is about.
+/// Errors not about one key, e.g. an exceeded [`max_pairs`](crate::options::ParseOptions::max_pairs),
+/// have none.
+#[derive(Debug, Default)]
+pub struct Errors {
+    errors: Vec<(Option<String>, Error)>,
+}
+
+impl Errors {
+    pub fn push(&mut self, param: Option<String>, err: Error) {
+        self.errors.push((param, err));
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Option<&str>, &Error)> {
+        self.errors.iter().map(|(p, e)| (p.as_deref(), e))
+    }
+}
+
+impl From<Error> for Errors {
+    fn from(err: Error) -> Self {
+        let param = err.key().map(str::to_string);
+        Errors {
+            errors: vec![(param, err)],
+        }
+    }
+}
+
+impl IntoIterator for Errors {
+    type Item = (Option<String>, Error);
+    type IntoIter = std::vec::IntoIter<(Option<String>, Error)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Errors {
+    type Item = &'a (Option<String>, Error);
+    type IntoIter = std::slice::Iter<'a, (Option<String>, Error)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.iter()
+    }
+}
+
+// the messages joined with `; `
+impl Display for Errors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (_, err)) in self.errors.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for Errors {}
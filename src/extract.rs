@@ -0,0 +1,170 @@
+//! The parts of the `Query` extractors shared by the web framework integrations, so that they
+//! read query strings and report errors the same way.
+use crate::{
+    error::{Error, ErrorKind, Errors},
+    from_str_all_errors, from_str_with,
+    options::ParseOptions,
+};
+use serde::Deserialize;
+
+/// Configuration of the `Query` extractors, registered as shared data of the application, e.g.
+/// with `App::app_data` in actix-web, `Extension` in axum or `EndpointExt::data` in poem, or
+/// as an extension of the request in salvo.
+///
+/// The default configuration limits the query string to 32 KiB, 1024 pairs and 8 KiB per value.
+///
+/// # Rejections
+///
+/// Every extractor answers a rejected query string the same way: with `400 Bad Request` and the
+/// message of the [`Error`] as body, which names the failing parameter. Exceeded limits are
+/// answered with a JSON body naming the limit instead, e.g.
+/// `{"error":"...","limit":{"kind":"values_per_key","key":"id","max":10}}`. With
+/// [`all_errors`](Self::all_errors), every rejected parameter is listed in a JSON body instead,
+/// and [`error_status`](Self::error_status) changes the status.
+#[derive(Debug, Clone)]
+pub struct QueryConfig {
+    options: ParseOptions,
+    all_errors: bool,
+    error_status: u16,
+}
+
+impl Default for QueryConfig {
+    fn default() -> Self {
+        Self {
+            options: ParseOptions::new()
+                .max_input_len(32 * 1024)
+                .max_pairs(1024)
+                .max_value_len(8 * 1024),
+            all_errors: false,
+            error_status: 400,
+        }
+    }
+}
+
+impl QueryConfig {
+    pub fn options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Answer with every rejected parameter rather than the first, as a JSON body like
+    /// `{"errors":[{"param":"limit","message":"..."}]}`. See
+    /// [`from_str_all_errors`](crate::from_str_all_errors).
+    pub fn all_errors(mut self, all_errors: bool) -> Self {
+        self.all_errors = all_errors;
+        self
+    }
+
+    /// The status answering rejected query strings, `400` by default, e.g. `422` for
+    /// `Unprocessable Entity`.
+    ///
+    /// # Panics
+    ///
+    /// If `status` is not a client error status, from `400` to `499`.
+    pub fn error_status(mut self, status: u16) -> Self {
+        assert!(
+            (400..500).contains(&status),
+            "`{}` is not a client error status",
+            status
+        );
+        self.error_status = status;
+        self
+    }
+}
+
+/// The answer to a rejected query string.
+#[derive(Debug)]
+pub(crate) struct Rejection {
+    pub(crate) status: u16,
+    pub(crate) body: String,
+    pub(crate) json: bool,
+}
+
+/// Deserialize `T` from the query string of a request with `config`, or the default
+/// configuration when none is registered, returning the first error to the caller rather than
+/// an answer to it. A request without a query string reads as an empty one.
+#[cfg(feature = "actix-web")]
+pub(crate) fn extract_query<T>(
+    query: Option<&str>,
+    config: Option<&QueryConfig>,
+) -> Result<T, Error>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    match config {
+        Some(config) => from_str_with(query.unwrap_or_default(), &config.options),
+        None => from_str_with(query.unwrap_or_default(), &QueryConfig::default().options),
+    }
+}
+
+/// Deserialize `T` from the query string of a request with `config`, or the default
+/// configuration, with all errors if `config` asks for them, and the answer to the errors.
+pub(crate) fn extract_or_reject<T>(
+    query: Option<&str>,
+    config: Option<&QueryConfig>,
+) -> Result<T, (Errors, Rejection)>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    let default;
+    let config = match config {
+        Some(config) => config,
+        None => {
+            default = QueryConfig::default();
+            &default
+        }
+    };
+    let status = config.error_status;
+    if config.all_errors {
+        return from_str_all_errors(query.unwrap_or_default(), &config.options).map_err(|errors| {
+            let body = errors_body(&errors);
+            let json = true;
+            (errors, Rejection { status, body, json })
+        });
+    }
+    from_str_with(query.unwrap_or_default(), &config.options).map_err(|err| {
+        let rejection = match limit_body(&err) {
+            Some(body) => Rejection {
+                status,
+                body,
+                json: true,
+            },
+            None => Rejection {
+                status,
+                body: err.to_string(),
+                json: false,
+            },
+        };
+        (err.into(), rejection)
+    })
+}
+
+/// The JSON body listing `errors`, e.g. `{"errors":[{"param":"limit","message":"..."}]}`, with
+/// a `null` param for errors not about one key.
+pub(crate) fn errors_body(errors: &Errors) -> String {
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|(param, err)| serde_json::json!({"param": param, "message": err.to_string()}))
+        .collect();
+    serde_json::json!({ "errors": errors }).to_string()
+}
+
+/// The JSON body answering an exceeded limit, naming the limit and, for limits of a single key,
+/// the key, e.g. `{"error":"...","limit":{"kind":"values_per_key","key":"id","max":10}}`.
+/// Other errors are answered with their message as it is.
+pub(crate) fn limit_body(err: &Error) -> Option<String> {
+    match &err.kind {
+        ErrorKind::LimitExceeded { key, limit, kind } => Some(
+            serde_json::json!({
+                "error": err.to_string(),
+                "limit": {
+                    "kind": kind.name(),
+                    "key": key,
+                    "max": limit,
+                },
+            })
+            .to_string(),
+        ),
+        _ => None,
+    }
+}
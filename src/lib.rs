@@ -1,10 +1,45 @@
 #[cfg(feature = "actix-web")]
 pub mod actix_web;
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "chrono")]
+pub mod chrono;
 pub mod error;
+#[cfg(any(
+    feature = "actix-web",
+    feature = "axum",
+    feature = "poem",
+    feature = "salvo"
+))]
+mod extract;
+pub mod options;
+#[cfg(feature = "params")]
+pub mod params;
+#[cfg(feature = "poem")]
+pub mod poem;
+pub mod query_map;
+#[cfg(feature = "salvo")]
+pub mod salvo;
+#[cfg(feature = "schemars")]
+pub mod schemars;
+#[cfg(feature = "time")]
+pub mod time;
 pub mod utils;
+#[cfg(feature = "utoipa")]
+pub mod utoipa;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-use crate::error::Error;
-use base64::prelude::*;
+use crate::error::{Error, ErrorKind, Errors, LimitKind};
+use crate::options::{
+    ArrayStyle, BoolFormat, BytesEncoding, CompatMode, FloatFormat, MapDuplicateKeys, NestingStyle,
+    ParseOptions, SerializerOptions,
+};
+use crate::query_map::{insert_pair, parse_pairs_into, QueryMap, Values};
+use crate::utils::{
+    decode_component, decode_percent, encode_component, encode_form_component, join_canonical,
+};
+use indexmap::IndexMap;
 use serde::{
     de::{DeserializeOwned, MapAccess, SeqAccess},
     ser::{
@@ -13,9 +48,27 @@ use serde::{
     },
     Deserialize, Serialize,
 };
+use smallvec::smallvec;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::error::Error as StdError;
+use std::fmt::Display;
+use std::marker::PhantomData;
 use std::ops::Deref;
+use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+/// A sequence written as JSON in a single value, e.g. `ids=%5B1%2C2%5D` for `ids=[1,2]`.
+///
+/// In an `Option<Array<T>>`, `None` is omitted, `Some(Array(vec![]))` is written as `[]` and
+/// any other value as its JSON, so all three read back as they were. An empty value (`ids=`)
+/// reads as an empty array, or as `None` with [`EmptyValue::AsNone`](options::EmptyValue::AsNone),
+/// which is what [`SerializerOptions::none_as_empty`] writes for `None`.
+///
+/// Other formats see the elements as a plain sequence, so a DTO shared with serde_json holds a
+/// JSON array there, not a string of JSON; such a string is still read. Elements only need to
+/// implement `Deserialize`, so generic structs like `Page<T> { items: Array<T> }` derive it
+/// without extra bounds.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Array<T>(pub Vec<T>);
 
 impl<T> Deref for Array<T> {
@@ -26,1204 +79,5972 @@ impl<T> Deref for Array<T> {
     }
 }
 
-impl<'de, T> Deserialize<'de> for Array<T>
+/// The JSON of the elements, e.g. `[1,2]`.
+impl<T> Display for Array<T>
 where
-    T: DeserializeOwned,
+    T: Serialize,
 {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let s: String = Deserialize::deserialize(deserializer)?;
-        let v = serde_json::from_str::<Vec<T>>(&s).map_err(serde::de::Error::custom)?;
-        Ok(Array(v))
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = serde_json::to_string(&self.0).map_err(|_| std::fmt::Error)?;
+        f.write_str(&s)
     }
 }
 
-pub fn to_string<T>(value: T) -> Result<String, Error>
+impl<T> FromStr for Array<T>
 where
-    T: Serialize,
+    T: DeserializeOwned,
 {
-    let mut serializer = Serializer::new();
-    value.serialize(&mut serializer)?;
-    Ok(serializer.output)
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(Array(Vec::new()));
+        }
+        let v = serde_json::from_str::<Vec<T>>(s)
+            .map_err(|e| Error::new("invalid JSON array", Some(Box::new(e))))?;
+        Ok(Array(v))
+    }
 }
 
-impl<T> Serialize for Array<T>
+// The name `Array` is serialized with, so that the `Serializer` writes its JSON in a single
+// value and the `Deserializer` gives it that value. Other formats, e.g. serde_json, see the
+// sequence inside, so a DTO shared with them holds an array rather than a string of JSON.
+const ARRAY_NAME: &str = "$nb_serde_query::Array";
+
+impl<'de, T> Deserialize<'de> for Array<T>
 where
-    T: Serialize,
+    T: Deserialize<'de>,
 {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
-        S: serde::Serializer,
+        D: serde::Deserializer<'de>,
     {
-        let s = serde_json::to_string(&self.0).map_err(serde::ser::Error::custom)?;
-        serializer.serialize_str(&s)
+        deserializer.deserialize_newtype_struct(ARRAY_NAME, ArrayVisitor(PhantomData))
     }
 }
 
-#[derive(Debug, Default)]
-pub struct Serializer {
-    output: String,
-    curr_key: Option<String>,
-    is_for_key: bool,
-}
+struct ArrayVisitor<T>(PhantomData<T>);
 
-impl Serializer {
-    pub fn new() -> Self {
-        Self {
-            output: String::new(),
-            curr_key: None,
-            is_for_key: false,
-        }
+impl<'de, T> Visitor<'de> for ArrayVisitor<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = Array<T>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a JSON array")
     }
-}
 
-impl SerializeMap for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
+    // the JSON of the elements, as written in a query string or formerly by other formats
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        if v.is_empty() {
+            return Ok(Array(Vec::new()));
+        }
+        // read through a `Value`, as the elements can't borrow from `v`
+        let json: serde_json::Value = serde_json::from_str(v).map_err(E::custom)?;
+        Vec::deserialize(json).map(Array).map_err(E::custom)
+    }
 
-    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
     where
-        T: ?Sized + Serialize,
+        A: SeqAccess<'de>,
     {
-        self.is_for_key = true;
-        key.serialize(&mut **self)
+        let mut v = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(elem) = seq.next_element()? {
+            v.push(elem);
+        }
+        Ok(Array(v))
     }
 
-    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
-        T: ?Sized + Serialize,
+        D: serde::Deserializer<'de>,
     {
-        self.is_for_key = false;
-        value.serialize(&mut **self)
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(self)
+        } else {
+            deserializer.deserialize_seq(self)
+        }
     }
+}
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+/// A sequence written in a single value, its elements separated by `SEP`, e.g. `ids=1,2,3` for
+/// `Separated<u32, ','>`. Elements are written with `Display` and read with `FromStr`.
+///
+/// A `SEP` or `%` inside an element is escaped, so that it stays part of the element. `SEP` is
+/// written as it is unless it means something else in a query string, like `&`, or is not
+/// allowed unencoded, like a space. An empty value reads as no elements. `SEP` cannot be `%`.
+///
+/// ```
+/// use nb_serde_query::{from_str, to_string, CommaSeparated, Separated};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Filter {
+///     ids: CommaSeparated<u32>,
+///     tags: Separated<String, ';'>,
+/// }
+///
+/// let v = Filter {
+///     ids: Separated(vec![1, 2, 3]),
+///     tags: Separated(vec!["a;b".to_string(), "c".to_string()]),
+/// };
+/// let s = to_string(&v).unwrap();
+/// assert_eq!(s, "ids=1,2,3&tags=a%253Bb;c");
+/// assert_eq!(from_str::<Filter>(&s).unwrap(), v);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Separated<T, const SEP: char>(pub Vec<T>);
+
+/// A sequence written in a single value separated by commas, e.g. `ids=1,2,3`.
+pub type CommaSeparated<T> = Separated<T, ','>;
+
+/// A sequence written in a single value separated by pipes, e.g. `ids=1|2|3`.
+pub type PipeSeparated<T> = Separated<T, '|'>;
+
+// The prefix of the names the `Serializer` recognizes a `Separated` by, followed by `SEP`.
+const SEPARATED_NAME: &str = "$nb_serde_query::Separated";
+
+impl<T, const SEP: char> Separated<T, SEP> {
+    const VALID: () = assert!(SEP != '%', "`%` cannot separate values");
+
+    // The name passed to the `Serializer`, for the separators it may write unencoded.
+    fn name() -> Option<&'static str> {
+        macro_rules! names {
+            ($($c:literal)*) => {
+                match SEP {
+                    $($c => Some(concat!("$nb_serde_query::Separated", $c)),)*
+                    _ => None,
+                }
+            };
+        }
+        names!(' ' '!' '"' '#' '$' '&' '\'' '(' ')' '*' '+' ',' '/' ':' ';' '<' '=' '>' '?' '@'
+            '[' '\\' ']' '^' '`' '{' '|' '}')
     }
 }
 
-impl SerializeSeq for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
+impl<T, const SEP: char> Deref for Separated<T, SEP> {
+    type Target = Vec<T>;
 
-    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
-    where
-        T: ?Sized + Serialize,
-    {
-        value.serialize(&mut **self)
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
+}
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
+/// The value as it is written before percent-encoding, e.g. `1,2,3`.
+impl<T, const SEP: char> Display for Separated<T, SEP>
+where
+    T: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let () = Self::VALID;
+        for (i, v) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", SEP)?;
+            }
+            for c in v.to_string().chars() {
+                if c == SEP || c == '%' {
+                    let mut buf = [0; 4];
+                    for b in c.encode_utf8(&mut buf).bytes() {
+                        write!(f, "%{:02X}", b)?;
+                    }
+                } else {
+                    write!(f, "{}", c)?;
+                }
+            }
+        }
         Ok(())
     }
 }
 
-impl SerializeStruct for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
+impl<T, const SEP: char> FromStr for Separated<T, SEP>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    type Err = Error;
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let () = Self::VALID;
+        if s.is_empty() {
+            return Ok(Separated(Vec::new()));
+        }
+        s.split(SEP)
+            .map(|v| {
+                decode_percent(v)?
+                    .parse()
+                    .map_err(|e: T::Err| Error::new(e.to_string(), None))
+            })
+            .collect::<Result<_, _>>()
+            .map(Separated)
+    }
+}
+
+impl<T, const SEP: char> Serialize for Separated<T, SEP>
+where
+    T: Display,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
-        T: Serialize,
+        S: serde::Serializer,
     {
-        self.is_for_key = true;
-        key.serialize(&mut **self)?;
-        value.serialize(&mut **self)
+        let s = self.to_string();
+        match Self::name() {
+            Some(name) => serializer.serialize_newtype_struct(name, &s),
+            None => serializer.serialize_str(&s),
+        }
     }
+}
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+impl<'de, T, const SEP: char> Deserialize<'de> for Separated<T, SEP>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
     }
 }
 
-impl SerializeStructVariant for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
+/// A value which is already percent-encoded, written as it is by the [`Serializer`].
+///
+/// Read with [`ParseOptions::raw_values`] it holds the value exactly as it appeared in the
+/// input, so a query string survives parsing and serializing byte for byte, e.g. `%2f` and `+`
+/// are neither decoded nor re-encoded. Without that option it holds the decoded value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Raw(pub String);
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
-    where
-        T: Serialize,
-    {
-        self.is_for_key = true;
-        key.serialize(&mut **self)?;
-        self.is_for_key = false;
-        value.serialize(&mut **self)
+// The name the `Serializer` recognizes a `Raw` by.
+const RAW_NAME: &str = "$nb_serde_query::Raw";
+
+impl Deref for Raw {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
+}
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+impl Display for Raw {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
     }
 }
 
-impl SerializeTuple for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
+impl FromStr for Raw {
+    type Err = std::convert::Infallible;
 
-    fn serialize_element<T: ?Sized>(&mut self, _value: &T) -> Result<(), Self::Error>
-    where
-        T: Serialize,
-    {
-        Ok(())
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Raw(s.to_string()))
     }
+}
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+impl Serialize for Raw {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_NAME, &self.0)
     }
 }
 
-impl SerializeTupleStruct for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
-
-    fn serialize_field<T: ?Sized>(&mut self, _value: &T) -> Result<(), Self::Error>
+impl<'de> Deserialize<'de> for Raw {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
-        T: Serialize,
+        D: serde::Deserializer<'de>,
     {
-        Ok(())
+        String::deserialize(deserializer).map(Raw)
     }
+}
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
+/// Serialize `value` with the default options.
+///
+/// `value` is borrowed like in `serde_json::to_string`, so unsized values and data borrowed
+/// elsewhere are serialized without taking ownership.
+pub fn to_string<T>(value: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    to_string_with(value, &SerializerOptions::default())
+}
+
+/// Write pairs straight into a query string, the way [`to_string`] writes the fields of a
+/// struct with the same names and values, but without going through serde, e.g. for a beacon
+/// sent on every request. Values are the [`QueryValue`](utils::QueryValue) types: numbers,
+/// bools, chars, strings, `Option`s of them, which are left out when `None`, and sequences,
+/// written as one pair per element. Keys are not checked for duplicates.
+///
+/// ```
+/// use nb_serde_query::query_string;
+///
+/// let s = query_string!("event" => "page view", "ms" => 12.5, "ids" => [1, 2], "ref" => None::<&str>);
+/// assert_eq!(s, "event=page%20view&ms=12.5&ids=1&ids=2");
+/// ```
+///
+/// The only allocation is the returned `String`. To reuse one instead, append to it with
+/// [`QueryValue::push_pairs`](utils::QueryValue::push_pairs).
+#[macro_export]
+macro_rules! query_string {
+    ($($key:expr => $value:expr),* $(,)?) => {{
+        let mut out = ::std::string::String::new();
+        $($crate::utils::QueryValue::push_pairs(&$value, $key, &mut out);)*
+        out
+    }};
+}
+
+/// Serialize `value` with the pairs sorted bytewise by key.
+///
+/// Repeated values of the same key keep their relative order, so the output is a
+/// canonical form suitable for cache keys and request signing.
+pub fn to_string_sorted<T>(value: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    to_string_with(value, &SerializerOptions::new().sort_keys(true))
+}
+
+/// Serialize `value` to the canonical query string of request signing schemes such as AWS
+/// Signature Version 4: keys and values percent-encoded except for `A-Z a-z 0-9 - _ . ~`, pairs
+/// sorted by encoded key then encoded value, and empty values kept as `key=`.
+///
+/// The options are fixed, so the output does not change with the defaults of
+/// [`SerializerOptions`].
+pub fn to_canonical_string<T>(value: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    let opts = SerializerOptions::new()
+        .array_style(ArrayStyle::Repeat)
+        .nesting_style(NestingStyle::Reject)
+        .bool_format(BoolFormat::TrueFalse)
+        .float_format(FloatFormat::Decimal)
+        .bytes_encoding(BytesEncoding::Base64UrlNoPad)
+        .none_as_empty(false)
+        .allow_duplicate_keys(false)
+        .reject_control_chars(false)
+        .variant_tag_key(Some("type"));
+    let mut serializer = Serializer::with_options(opts);
+    value.serialize(&mut serializer)?;
+    Ok(join_canonical(serializer.pairs))
+}
+
+pub fn to_string_with<T>(value: &T, opts: &SerializerOptions) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("to_string", pairs = tracing::field::Empty).entered();
+    let mut serializer = Serializer::with_options(opts.clone());
+    value.serialize(&mut serializer)?;
+    serializer.post_process()?;
+    #[cfg(feature = "tracing")]
+    span.record("pairs", serializer.pairs.len());
+    Ok(serializer.output())
+}
+
+/// Serialize each of `values` to its own query string with the default options, e.g. the
+/// parameters of many webhook calls. One [`Serializer`] is reused, so its buffers are allocated
+/// once rather than for each value.
+pub fn to_strings<T>(values: &[T]) -> Result<Vec<String>, Error>
+where
+    T: Serialize,
+{
+    to_strings_with(values, &SerializerOptions::default())
+}
+
+pub fn to_strings_with<T>(values: &[T], opts: &SerializerOptions) -> Result<Vec<String>, Error>
+where
+    T: Serialize,
+{
+    let mut strings = Vec::with_capacity(values.len());
+    for_each_string(values, opts, |s| {
+        strings.push(s.to_string());
         Ok(())
-    }
+    })?;
+    Ok(strings)
 }
 
-impl SerializeTupleVariant for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
+/// Write the query string of each of `values` to `writer`, one per line, without allocating a
+/// `String` for each of them like [`to_strings_with`] does.
+pub fn write_strings<T, W>(
+    values: &[T],
+    opts: &SerializerOptions,
+    mut writer: W,
+) -> Result<(), Error>
+where
+    T: Serialize,
+    W: std::io::Write,
+{
+    for_each_string(values, opts, |s| {
+        writeln!(writer, "{}", s).map_err(|e| Error::new("failed to write", Some(Box::new(e))))
+    })
+}
+
+fn for_each_string<T>(
+    values: &[T],
+    opts: &SerializerOptions,
+    mut f: impl FnMut(&str) -> Result<(), Error>,
+) -> Result<(), Error>
+where
+    T: Serialize,
+{
+    let mut serializer = Serializer::with_options(opts.clone());
+    let mut out = String::new();
+    for value in values {
+        serializer.reset();
+        value.serialize(&mut serializer)?;
+        serializer.post_process()?;
+        out.clear();
+        serializer.write_output(&mut out);
+        f(&out)?;
+    }
+    Ok(())
+}
 
-    fn serialize_field<T: ?Sized>(&mut self, _value: &T) -> Result<(), Self::Error>
+impl<T> Serialize for Array<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
-        T: Serialize,
+        S: serde::Serializer,
     {
-        Ok(())
+        serializer.serialize_newtype_struct(ARRAY_NAME, &self.0)
     }
+}
 
-    fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
-    }
+#[derive(Debug, Default)]
+pub struct Serializer {
+    pairs: Vec<(String, String)>,
+    curr_key: Option<String>,
+    curr_key_emitted: bool,
+    is_for_key: bool,
+    // set while serializing the value of a `Some`, so that `Some(None)` is written as `key=`
+    in_some: bool,
+    // set while serializing a `Raw`, whose value is written without encoding it
+    in_raw: bool,
+    // set while serializing a `Separated`, to its separator
+    separator: Option<char>,
+    seen_keys: HashSet<String>,
+    map_starts: Vec<usize>,
+    seq_starts: Vec<usize>,
+    // key of every open sequence and the index of its next element
+    seq_keys: Vec<(String, usize)>,
+    // the first pair and the name of every open tuple struct, whose fields are joined into one
+    tuple_starts: Vec<(usize, &'static str)>,
+    // one entry per open struct, the key nested fields are written under
+    key_prefixes: Vec<Option<String>>,
+    depth: usize,
+    options: SerializerOptions,
 }
 
-impl serde::Serializer for &mut Serializer {
-    type Ok = ();
-    type Error = Error;
+impl Serializer {
+    pub fn new() -> Self {
+        Self::with_options(SerializerOptions::default())
+    }
 
-    type SerializeMap = Self;
-    type SerializeSeq = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
+    /// A serializer with room for `pairs` pairs before reallocating.
+    pub fn with_capacity(pairs: usize, options: SerializerOptions) -> Self {
+        let mut serializer = Self::with_options(options);
+        serializer.pairs.reserve(pairs);
+        serializer
+    }
 
-    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
-            return Ok(());
-        }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for bool value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
+    pub fn with_options(options: SerializerOptions) -> Self {
+        Self {
+            pairs: Vec::new(),
+            curr_key: None,
+            curr_key_emitted: false,
+            is_for_key: false,
+            in_some: false,
+            in_raw: false,
+            separator: None,
+            seen_keys: HashSet::new(),
+            map_starts: Vec::new(),
+            seq_starts: Vec::new(),
+            seq_keys: Vec::new(),
+            tuple_starts: Vec::new(),
+            key_prefixes: Vec::new(),
+            depth: 0,
+            options,
         }
-        self.output.push_str(&format!("{}={}", curr_key, v));
-        Ok(())
     }
 
-    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
+    fn enter_struct(&mut self, name: &str) -> Result<(), Error> {
+        self.enter_nested(|| format!("struct `{}`", name))
+    }
+
+    // Start writing a struct or map, under the key it is the value of unless it is the top
+    // level value.
+    fn enter_nested(&mut self, what: impl FnOnce() -> String) -> Result<(), Error> {
+        if self.depth > 0 && self.options.nesting_style != NestingStyle::Reject {
+            self.key_prefixes.push(self.curr_key.clone());
+            self.depth += 1;
             return Ok(());
         }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for i8 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
+        if self.depth > 0 {
+            return Err(Error::new(
+                format!(
+                    "cannot serialize nested {} for key `{}`, use #[serde(flatten)] instead",
+                    what(),
+                    self.curr_key.as_deref().unwrap_or_default()
+                ),
+                None,
+            ));
         }
-        self.output.push_str(&format!("{}={}", curr_key, v));
+        self.key_prefixes.push(None);
+        self.depth += 1;
         Ok(())
     }
 
-    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
+    // Write `tag=Variant` before the fields of an externally tagged variant.
+    fn serialize_variant_tag(&mut self, variant: &str) -> Result<(), Error> {
+        let Some(tag) = self.options.variant_tag_key.clone() else {
             return Ok(());
-        }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for i16 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
-        }
-        self.output.push_str(&format!("{}={}", curr_key, v));
-        Ok(())
+        };
+        self.is_for_key = true;
+        self.serialize_scalar(tag, "str")?;
+        self.serialize_scalar(variant.to_string(), "str")
     }
 
-    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+    fn check_not_key(&self, kind: &str) -> Result<(), Error> {
         if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
-            return Ok(());
-        }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for i32 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
+            return Err(Error::new(
+                format!("map keys must be scalar, got {}", kind),
+                None,
+            ));
         }
-        self.output.push_str(&format!("{}={}", curr_key, v));
         Ok(())
     }
 
-    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
-            return Ok(());
-        }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for i64 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
+    // Whether `k` was written for the value of `key`, e.g. `key`, `key[]`, `key[0]` or
+    // `key[sub]`.
+    fn is_written_for(&self, k: &str, key: &str) -> bool {
+        match k.strip_prefix(key) {
+            Some("") => true,
+            Some(rest) => match self.options.nesting_style {
+                NestingStyle::Separator(sep) => rest.starts_with(['[', sep]),
+                _ => rest.starts_with('['),
+            },
+            None => false,
         }
-        self.output.push_str(&format!("{}={}", curr_key, v));
-        Ok(())
     }
 
-    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(BASE64_STANDARD.encode(v));
-            self.is_for_key = false;
+    // Apply the map duplicate key policy to the entry about to be written for `key`.
+    fn check_map_entry(&mut self, key: &str) -> Result<(), Error> {
+        let policy = self.options.map_duplicate_keys;
+        if policy == MapDuplicateKeys::Allow
+            || !self.seen_keys.iter().any(|k| self.is_written_for(k, key))
+        {
             return Ok(());
         }
-        let curr_key = self.curr_key.as_ref().ok_or(Error::new(
-            format!("empty key for bytes value {:?}", v),
-            None,
-        ))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
+        if policy == MapDuplicateKeys::Error {
+            return Err(Error::new(
+                format!("duplicate key `{}` produced by serialization", key),
+                None,
+            ));
+        }
+        let mut i = 0;
+        while i < self.pairs.len() {
+            if !self.is_written_for(&self.pairs[i].0, key) {
+                i += 1;
+                continue;
+            }
+            self.pairs.remove(i);
+            for start in self.map_starts.iter_mut().chain(&mut self.seq_starts) {
+                if *start > i {
+                    *start -= 1;
+                }
+            }
         }
-        self.output
-            .push_str(&format!("{}={}", curr_key, BASE64_STANDARD.encode(v)));
+        let seen = std::mem::take(&mut self.seen_keys);
+        self.seen_keys = seen
+            .into_iter()
+            .filter(|k| !self.is_written_for(k, key))
+            .collect();
         Ok(())
     }
 
-    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
-            return Ok(());
+    fn encode<'v>(&self, v: &'v str) -> Cow<'v, str> {
+        let encoded = match self.options.compat {
+            CompatMode::Rfc3986 => encode_component(v),
+            CompatMode::SerdeUrlencoded => encode_form_component(v),
+        };
+        // the separators are left as they are only when they are characters kept unencoded
+        let separators = [self.options.pair_separator, self.options.kv_separator];
+        if !encoded.contains(separators) {
+            return encoded;
         }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for char value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
+        let mut out = String::with_capacity(encoded.len() + 6);
+        for c in encoded.chars() {
+            if separators.contains(&c) {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    out.push_str(&format!("%{:02X}", b));
+                }
+            } else {
+                out.push(c);
+            }
         }
-        self.output.push_str(&format!("{}={}", curr_key, v));
-        Ok(())
+        Cow::Owned(out)
     }
 
-    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+    // A delimiter inside a value is written as it is unless it means something else in a query
+    // string or is not allowed unencoded.
+    fn delimiter(&self, c: char) -> String {
+        if c.is_ascii_graphic()
+            && !matches!(c, '&' | '=' | '%' | '+' | '#')
+            && c != self.options.pair_separator
+            && c != self.options.kv_separator
+        {
+            c.to_string()
+        } else {
+            self.encode(&c.to_string()).into_owned()
+        }
+    }
+
+    fn serialize_scalar(&mut self, v: String, type_name: &str) -> Result<(), Error> {
+        if self.options.pair_separator == self.options.kv_separator {
+            return Err(Error::new(
+                format!(
+                    "`{}` cannot separate both pairs and keys from values",
+                    self.options.kv_separator
+                ),
+                None,
+            ));
+        }
+        if self.options.reject_control_chars {
+            if let Some(c) = v.chars().find(|c| c.is_control()) {
+                let place = match (self.is_for_key, &self.curr_key) {
+                    (true, _) | (false, None) => "key".to_string(),
+                    (false, Some(key)) => format!("value of key `{}`", key),
+                };
+                return Err(Error::new(
+                    format!("control character {:?} in {}", c, place),
+                    None,
+                ));
+            }
+        }
         if self.is_for_key {
-            self.curr_key = Some(v.to_string());
+            let key = match &self.options.key_transform {
+                Some(transform) => transform.apply(&v),
+                None => v,
+            };
+            let key = self.encode(&key);
+            self.curr_key = Some(match self.key_prefixes.last() {
+                Some(Some(prefix)) => match self.options.nesting_style {
+                    NestingStyle::Separator(sep) => format!("{}{}{}", prefix, sep, key),
+                    _ => format!("{}[{}]", prefix, key),
+                },
+                _ => key.into_owned(),
+            });
+            self.curr_key_emitted = false;
             self.is_for_key = false;
             return Ok(());
         }
+        if self.options.skip_defaults
+            && self.seq_starts.is_empty()
+            && self.tuple_starts.is_empty()
+            && (v.is_empty() || self.options.skip_values.contains(&v))
+        {
+            return Ok(());
+        }
         let curr_key = self
             .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for f32 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
+            .clone()
+            .ok_or_else(|| Error::new(format!("empty key for {} value {}", type_name, v), None))?;
+        if !self.curr_key_emitted {
+            if !self.seen_keys.insert(curr_key.clone()) && !self.options.allow_duplicate_keys {
+                return Err(Error::new(
+                    format!("duplicate key `{}` produced by serialization", curr_key),
+                    None,
+                ));
+            }
+            self.curr_key_emitted = true;
         }
-        self.output.push_str(&format!("{}={}", curr_key, v));
+        let v = if self.in_raw {
+            // a `#` would end the query string
+            if v.contains('#') {
+                v.replace('#', "%23")
+            } else {
+                v
+            }
+        } else if let Some(sep) = self.separator {
+            let delimiter = self.delimiter(sep);
+            let parts: Vec<_> = v.split(sep).map(|part| self.encode(part)).collect();
+            parts.join(&delimiter)
+        } else {
+            self.encode(&v).into_owned()
+        };
+        self.pairs.push((curr_key, v));
         Ok(())
     }
 
-    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
+    // Run the `post_process` hook of the options on the decoded pairs. The pairs it leaves as
+    // they were keep their encoding, e.g. of a `Raw` value, the others are encoded again.
+    fn post_process(&mut self) -> Result<(), Error> {
+        let Some(hook) = self.options.post_process.clone() else {
             return Ok(());
+        };
+        // a `Raw` value isn't necessarily valid percent-encoding
+        let decode = |s: &str| {
+            decode_component(s)
+                .map(Cow::into_owned)
+                .unwrap_or_else(|_| s.to_string())
+        };
+        let mut encoded = HashMap::new();
+        let mut pairs = Vec::with_capacity(self.pairs.len());
+        for (k, v) in self.pairs.drain(..) {
+            let decoded = (decode(&k), decode(&v));
+            encoded.entry(decoded.clone()).or_insert((k, v));
+            pairs.push(decoded);
         }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for f64 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
+        hook(&mut pairs)?;
+        for pair in pairs {
+            let pair = match encoded.get(&pair) {
+                Some(original) => original.clone(),
+                None => (
+                    self.encode(&pair.0).into_owned(),
+                    self.encode(&pair.1).into_owned(),
+                ),
+            };
+            self.pairs.push(pair);
         }
-        self.output.push_str(&format!("{}={}", curr_key, v));
         Ok(())
     }
 
-    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
-            return Ok(());
+    /// Join the collected pairs into a query string.
+    pub fn output(&self) -> String {
+        let mut out = String::new();
+        self.write_output(&mut out);
+        out
+    }
+
+    /// Append the query string to `out`, e.g. to reuse its allocation.
+    pub fn write_output(&self, out: &mut String) {
+        let mut pairs: Vec<&(String, String)> = self.pairs.iter().collect();
+        if self.options.sort_keys {
+            pairs.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
         }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for f128 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
+        out.reserve(pairs.iter().map(|(k, v)| k.len() + v.len() + 2).sum());
+        for (i, (k, v)) in pairs.iter().enumerate() {
+            if i > 0 {
+                out.push(self.options.pair_separator);
+            }
+            out.push_str(k);
+            out.push(self.options.kv_separator);
+            out.push_str(v);
         }
-        self.output.push_str(&format!("{}={}", curr_key, v));
-        Ok(())
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        Ok(self)
+    pub fn into_inner(self) -> String {
+        self.output()
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
-        self,
-        _name: &'static str,
-        value: &T,
-    ) -> Result<Self::Ok, Self::Error>
+    /// Forget everything serialized so far, keeping the allocated buffers for the next value.
+    pub fn reset(&mut self) {
+        self.pairs.clear();
+        self.curr_key = None;
+        self.curr_key_emitted = false;
+        self.is_for_key = false;
+        self.in_some = false;
+        self.in_raw = false;
+        self.separator = None;
+        self.seen_keys.clear();
+        self.map_starts.clear();
+        self.seq_starts.clear();
+        self.seq_keys.clear();
+        self.tuple_starts.clear();
+        self.key_prefixes.clear();
+        self.depth = 0;
+    }
+}
+
+impl SerializeMap for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        self.is_for_key = true;
+        key.serialize(&mut **self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-        value: &T,
-    ) -> Result<Self::Ok, Self::Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        self.is_for_key = false;
+        if let Some(key) = self.curr_key.clone() {
+            self.check_map_entry(&key)?;
+        }
+        value.serialize(&mut **self)
     }
 
-    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.depth -= 1;
+        self.key_prefixes.pop();
+        let start = self.map_starts.pop().unwrap_or_default();
+        if self.options.sort_map_entries {
+            self.pairs[start..].sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+        }
         Ok(())
     }
+}
 
-    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        Ok(self)
-    }
+impl SerializeSeq for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        if self.options.array_style != ArrayStyle::Indexed {
+            return value.serialize(&mut **self);
+        }
+        let Some((key, index)) = self.seq_keys.last_mut() else {
+            return value.serialize(&mut **self);
+        };
+        let element_key = format!("{}[{}]", key, index);
+        *index += 1;
+        let key = self.curr_key.replace(element_key);
+        self.curr_key_emitted = false;
+        let res = value.serialize(&mut **self);
+        self.curr_key = key;
+        res
     }
 
-    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
-            return Ok(());
-        }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for str value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        let start = self.seq_starts.pop().unwrap_or_default();
+        self.seq_keys.pop();
+        match self.options.array_style {
+            ArrayStyle::Repeat | ArrayStyle::Indexed => {}
+            ArrayStyle::Brackets => {
+                for (k, _) in &mut self.pairs[start..] {
+                    k.push_str("[]");
+                }
+            }
+            ArrayStyle::Comma => {
+                if let Some((k, _)) = self.pairs.get(start).cloned() {
+                    let v = self
+                        .pairs
+                        .drain(start..)
+                        .map(|(_, v)| v)
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    self.pairs.push((k, v));
+                }
+            }
         }
-        self.output.push_str(&format!("{}={}", curr_key, v));
         Ok(())
     }
+}
 
-    fn serialize_struct(
-        self,
-        _name: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeStruct, Self::Error> {
-        Ok(self)
+impl SerializeStruct for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.is_for_key = true;
+        key.serialize(&mut **self)?;
+        value.serialize(&mut **self)
     }
 
-    fn serialize_struct_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        Ok(self)
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.depth -= 1;
+        self.key_prefixes.pop();
+        Ok(())
     }
+}
 
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        Ok(self)
+impl SerializeStructVariant for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.is_for_key = true;
+        key.serialize(&mut **self)?;
+        self.is_for_key = false;
+        value.serialize(&mut **self)
     }
 
-    fn serialize_tuple_struct(
-        self,
-        _name: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        Ok(self)
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.depth -= 1;
+        self.key_prefixes.pop();
+        Ok(())
     }
+}
 
-    fn serialize_tuple_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        Ok(self)
+impl SerializeTuple for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
     }
 
-    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
-            return Ok(());
-        }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for u128 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
-        }
-        self.output.push_str(&format!("{}={}", curr_key, v));
-        Ok(())
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeSeq::end(self)
     }
+}
 
-    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
-            return Ok(());
-        }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for u16 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
-        }
-        self.output.push_str(&format!("{}={}", curr_key, v));
-        Ok(())
+impl SerializeTupleStruct for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut **self)
     }
 
-    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
+    // Join the fields into the value of a single pair, e.g. `center=52.1,13.4`.
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        let Some((start, name)) = self.tuple_starts.pop() else {
             return Ok(());
+        };
+        let Some((k, _)) = self.pairs.get(start).cloned() else {
+            return Ok(());
+        };
+        let delimiter = self.options.tuple_delimiter;
+        let mut fields = Vec::with_capacity(self.pairs.len() - start);
+        for (_, v) in self.pairs.drain(start..) {
+            if decode_component(&v)?.contains(delimiter) {
+                return Err(Error::new(
+                    format!(
+                        "field `{}` of tuple struct `{}` contains the delimiter `{}`",
+                        decode_component(&v)?,
+                        name,
+                        delimiter
+                    ),
+                    None,
+                ));
+            }
+            fields.push(v);
         }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for u32 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
-        }
-        self.output.push_str(&format!("{}={}", curr_key, v));
+        let delimiter = self.delimiter(delimiter);
+        self.pairs.push((k, fields.join(&delimiter)));
         Ok(())
     }
+}
 
-    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
-            return Ok(());
-        }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for u64 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
-        }
-        self.output.push_str(&format!("{}={}", curr_key, v));
+impl SerializeTupleVariant for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, _value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
         Ok(())
     }
 
-    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-        if self.is_for_key {
-            self.curr_key = Some(v.to_string());
-            self.is_for_key = false;
-            return Ok(());
-        }
-        let curr_key = self
-            .curr_key
-            .as_ref()
-            .ok_or(Error::new(format!("empty key for u8 value {}", v), None))?;
-        if !self.output.is_empty() {
-            self.output.push('&');
-        }
-        self.output.push_str(&format!("{}={}", curr_key, v));
+    fn end(self) -> Result<Self::Ok, Self::Error> {
         Ok(())
     }
+}
 
-    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
-    }
+impl serde::Serializer for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
 
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
-        Ok(())
-    }
+    type SerializeMap = Self;
+    type SerializeSeq = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
 
-    fn serialize_unit_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-    ) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        let v = self.options.bool_format.format(v).to_string();
+        self.serialize_scalar(v, "bool")
     }
-}
 
-use serde::de::Visitor;
-use std::collections::HashMap;
-pub struct Deserializer {
-    m: HashMap<String, Vec<String>>,
-    curr_key: Option<String>,
-    curr_val: Option<Vec<String>>,
-    fields: Vec<String>,
-}
-
-impl Deserializer {
-    pub fn try_from_str(s: &str) -> Result<Self, Error> {
-        let m = s.split('&').map(|p| p.split('=')).try_fold(
-            HashMap::new(),
-            |mut m: HashMap<String, Vec<String>>, mut p| {
-                let key = p.next().ok_or(Error::new("invalid key", None))?;
-                let val = p.next().ok_or(Error::new("invalid value", None))?;
-                if p.next().is_some() {
-                    return Err(Error::new("invalid pair", None));
-                }
-                m.entry(key.to_string()).or_default().push(val.to_string());
-                Ok(m)
-            },
-        )?;
-        Ok(Self {
-            m,
-            curr_key: None,
-            curr_val: None,
-            fields: Vec::new(),
-        })
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "i8")
     }
-}
 
-impl<'de> MapAccess<'de> for Deserializer {
-    type Error = Error;
-    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
-    where
-        K: serde::de::DeserializeSeed<'de>,
-    {
-        if let Some(k) = self.fields.pop() {
-            self.curr_key = Some(k);
-            return seed.deserialize(self).map(Some);
-        }
-        Ok(None)
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "i16")
     }
 
-    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::DeserializeSeed<'de>,
-    {
-        let k = self.curr_key.take().ok_or(Error::new("no key", None))?;
-        if let Some(v) = self.m.remove(&k) {
-            self.curr_val = Some(v);
-            return seed.deserialize(self);
-        }
-        seed.deserialize(self)
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "i32")
     }
-}
 
-impl<'de> SeqAccess<'de> for Deserializer {
-    type Error = Error;
-    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
-    where
-        T: serde::de::DeserializeSeed<'de>,
-    {
-        if let Some(vals) = &mut self.curr_val {
-            if vals.is_empty() {
-                return Ok(None);
-            }
-            let val = vals.remove(0);
-            let mut next_deserializer = Deserializer {
-                m: self.m.clone(),
-                curr_key: None,
-                curr_val: Some(vec![val]),
-                fields: vec![],
-            };
-            return seed.deserialize(&mut next_deserializer).map(Some);
-        }
-        Ok(None)
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "i64")
     }
-}
 
-impl<'de> serde::Deserializer<'de> for &mut Deserializer {
-    type Error = Error;
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        let v = self.options.bytes_encoding.encode(v);
+        self.serialize_scalar(v, "bytes")
+    }
 
-    fn deserialize_struct<V>(
-        self,
-        _name: &'static str,
-        fields: &'static [&'static str],
-        visitor: V,
-    ) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        let mut next_deserializer = Deserializer {
-            m: self.m.clone(),
-            curr_key: None,
-            curr_val: None,
-            fields: fields.iter().map(|s| s.to_string()).collect(),
-        };
-        next_deserializer.deserialize_map(visitor)
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "char")
     }
 
-    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_map(self)
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        let v = self.options.float_format.format(v);
+        self.serialize_scalar(v, "f32")
     }
 
-    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_str(&self.curr_key.clone().ok_or(Error::new("no key", None))?)
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        let v = self.options.float_format.format(v);
+        self.serialize_scalar(v, "f64")
     }
 
-    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_bool(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no bool value", None))?
-                .first()
-                .ok_or(Error::new("no bool value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid bool literial", Some(Box::new(e))))?,
-        )
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "i128")
     }
 
-    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_i32(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no i32 value", None))?
-                .first()
-                .ok_or(Error::new("no i32 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid i32 literial", Some(Box::new(e))))?,
-        )
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        self.check_not_key("map")?;
+        self.in_some = false;
+        self.enter_nested(|| "map".to_string())?;
+        self.map_starts.push(self.pairs.len());
+        Ok(self)
     }
 
-    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
     where
-        V: Visitor<'de>,
+        T: ?Sized + Serialize,
     {
-        visitor.visit_string(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no string value", None))?
-                .first()
-                .ok_or(Error::new("no string value", None))?
-                .clone(),
-        )
+        if name == ARRAY_NAME {
+            let json = serde_json::to_string(value)
+                .map_err(|e| Error::new("cannot serialize array as JSON", Some(Box::new(e))))?;
+            return self.serialize_str(&json);
+        }
+        if let Some(sep) = name.strip_prefix(SEPARATED_NAME) {
+            self.separator = sep.chars().next();
+            let res = value.serialize(&mut *self);
+            self.separator = None;
+            return res;
+        }
+        if name != RAW_NAME || self.is_for_key {
+            return value.serialize(self);
+        }
+        self.in_raw = true;
+        let res = value.serialize(&mut *self);
+        self.in_raw = false;
+        res
     }
 
-    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
     where
-        V: Visitor<'de>,
+        T: ?Sized + Serialize,
     {
-        if let Some(val) = self.curr_val.take() {
-            if val.is_empty() {
-                return visitor.visit_none();
-            }
-            let mut next_deserializer = Deserializer {
-                m: self.m.clone(),
-                curr_key: None,
-                curr_val: Some(val),
-                fields: vec![],
-            };
-            return visitor.visit_some(&mut next_deserializer);
+        // only a top level variant wraps a struct whose fields can sit next to the tag
+        if self.depth == 0 && !self.is_for_key {
+            self.serialize_variant_tag(variant)?;
         }
-        visitor.visit_none()
+        value.serialize(self)
     }
 
-    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_seq(self)
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        self.check_not_key("none")?;
+        if self.options.none_as_empty || self.in_some {
+            return self.serialize_scalar(String::new(), "none");
+        }
+        Ok(())
     }
 
-    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        unimplemented!()
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        self.check_not_key("seq")?;
+        self.in_some = false;
+        self.seq_starts.push(self.pairs.len());
+        self.seq_keys
+            .push((self.curr_key.clone().unwrap_or_default(), 0));
+        Ok(self)
     }
 
-    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
-        V: Visitor<'de>,
+        T: ?Sized + Serialize,
     {
-        visitor.visit_i8(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no i8 value", None))?
-                .first()
-                .ok_or(Error::new("no i8 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid i8 literal", Some(Box::new(e))))?,
-        )
+        self.in_some = true;
+        let res = value.serialize(&mut *self);
+        self.in_some = false;
+        res
     }
 
-    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_i16(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no i16 value", None))?
-                .first()
-                .ok_or(Error::new("no i16 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid i16 literal", Some(Box::new(e))))?,
-        )
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "str")
     }
 
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        unimplemented!()
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.check_not_key("struct")?;
+        self.in_some = false;
+        self.enter_struct(name)?;
+        Ok(self)
     }
 
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        unimplemented!()
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        self.check_not_key("struct variant")?;
+        self.in_some = false;
+        self.enter_struct(name)?;
+        self.serialize_variant_tag(variant)?;
+        Ok(self)
     }
 
-    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        unimplemented!()
+    // tuples and fixed size arrays are written like sequences, `rgb=255&rgb=128&rgb=0`
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.check_not_key("tuple")?;
+        self.serialize_seq(Some(len))
     }
 
-    fn deserialize_enum<V>(
+    fn serialize_tuple_struct(
         self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
-        _visitor: V,
-    ) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        unimplemented!()
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.check_not_key("tuple struct")?;
+        self.in_some = false;
+        self.tuple_starts.push((self.pairs.len(), name));
+        Ok(self)
     }
 
-    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_f32(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no f32 value", None))?
-                .first()
-                .ok_or(Error::new("no f32 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid f32 literal", Some(Box::new(e))))?,
-        )
+    // there is no way to write a variant together with its unnamed fields that reads back
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        self.check_not_key("tuple variant")?;
+        let key = match &self.curr_key {
+            Some(key) => format!(" for key `{}`", key),
+            None => String::new(),
+        };
+        Err(Error::new(
+            format!(
+                "cannot serialize tuple variant `{}::{}`{}",
+                name, variant, key
+            ),
+            None,
+        ))
     }
 
-    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_f64(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no f64 value", None))?
-                .first()
-                .ok_or(Error::new("no f64 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid f64 literal", Some(Box::new(e))))?,
-        )
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "u128")
     }
 
-    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_i128(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no i128 value", None))?
-                .first()
-                .ok_or(Error::new("no i128 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid i128 literal", Some(Box::new(e))))?,
-        )
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "u16")
     }
 
-    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        visitor.visit_i64(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no i64 value", None))?
-                .first()
-                .ok_or(Error::new("no i64 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid i64 literal", Some(Box::new(e))))?,
-        )
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "u32")
     }
 
-    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: serde::de::Visitor<'de>,
-    {
-        unimplemented!()
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "u64")
     }
 
-    fn deserialize_newtype_struct<V>(
-        self,
-        _name: &'static str,
-        _visitor: V,
-    ) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        unimplemented!()
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(v.to_string(), "u8")
     }
 
-    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_str(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no string value", None))?
-                .first()
-                .ok_or(Error::new("no string value", None))?,
-        )
+    // a unit is written as no pair at all, but `Some(())` as a key with an empty value, so that
+    // an `Option<()>` tells whether the key is present
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.check_not_key("unit")?;
+        if self.in_some {
+            return self.serialize_scalar(String::new(), "unit");
+        }
+        Ok(())
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        unimplemented!()
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.check_not_key("unit struct")?;
+        if self.in_some {
+            return self.serialize_scalar(String::new(), "unit struct");
+        }
+        Ok(())
     }
 
-    fn deserialize_tuple_struct<V>(
+    fn serialize_unit_variant(
         self,
         _name: &'static str,
-        _len: usize,
-        _visitor: V,
-    ) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        unimplemented!()
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_scalar(variant.to_string(), "unit variant")
     }
+}
 
-    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_u128(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no u128 value", None))?
-                .first()
-                .ok_or(Error::new("no u128 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid u128 literal", Some(Box::new(e))))?,
-        )
+use serde::de::{Expected, IntoDeserializer, Visitor};
+
+// The entries a struct or map visits. Both are read from the parsed multimap the same way, so
+// that the options shaping it apply to both alike: a map visits its keys in input order, a
+// struct its field names in declaration order, and either only those the input still has a
+// value for. The names of a struct, aliases included, are filtered further to one per field.
+struct Entries<'de> {
+    keys: Vec<Cow<'de, str>>,
+    // for a struct, the index of the first name of the field each name belongs to, empty until
+    // known
+    fields: Option<Vec<usize>>,
+    next: usize,
+}
+
+impl<'de> Entries<'de> {
+    fn map(keys: Vec<Cow<'de, str>>) -> Self {
+        Self {
+            keys,
+            fields: None,
+            next: 0,
+        }
     }
 
-    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_u16(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no u16 value", None))?
-                .first()
-                .ok_or(Error::new("no u16 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid u16 literal", Some(Box::new(e))))?,
-        )
+    fn fields(names: &'static [&'static str]) -> Self {
+        Self {
+            keys: names.iter().map(|name| Cow::Borrowed(*name)).collect(),
+            fields: Some(Vec::new()),
+            next: 0,
+        }
     }
 
-    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_u32(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no u32 value", None))?
-                .first()
-                .ok_or(Error::new("no u32 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid u32 literal", Some(Box::new(e))))?,
-        )
+    fn is_struct(&self) -> bool {
+        self.fields.is_some()
     }
 
-    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_u64(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no u64 value", None))?
-                .first()
-                .ok_or(Error::new("no u64 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid u64 literal", Some(Box::new(e))))?,
-        )
+    // The entries visited as `i`: the names of the field `i` is the first name of, or none for
+    // a later name of a field. A key of a map is visited alone.
+    fn group(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        let groups = self.fields.as_deref().unwrap_or_default();
+        let end = match groups.get(i) {
+            None => i + 1,
+            Some(first) if *first == i => self.keys.len(),
+            Some(_) => i,
+        };
+        (i..end).filter(move |j| groups.get(*j).is_none_or(|first| *first == i))
     }
+}
 
-    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
-    where
-        V: Visitor<'de>,
-    {
-        visitor.visit_u8(
-            self.curr_val
-                .take()
-                .ok_or(Error::new("no u8 value", None))?
-                .first()
-                .ok_or(Error::new("no u8 value", None))?
-                .parse()
-                .map_err(|e| Error::new("invalid u8 literal", Some(Box::new(e))))?,
-        )
+// The index of the first name deserializing to the same field identifier as each name. Only a
+// key read with `deserialize_identifier`, the way serde derives field enums, is grouped; any
+// other key, e.g. a `u8` or `bool` of a hand written visitor, leaves every name on its own.
+fn group_names<'de, K>(names: &[Cow<'de, str>]) -> Result<Vec<usize>, Error>
+where
+    K: Deserialize<'de>,
+{
+    let mut identifier = false;
+    let _ = K::deserialize(IdentifierProbe(&mut identifier));
+    if !identifier {
+        return Ok((0..names.len()).collect());
+    }
+    let mut variants = Vec::with_capacity(names.len());
+    let mut groups = Vec::with_capacity(names.len());
+    for name in names {
+        let variant = std::mem::discriminant(&K::deserialize(KeyDeserializer::new(name.clone()))?);
+        groups.push(
+            variants
+                .iter()
+                .position(|v| *v == variant)
+                .unwrap_or(variants.len()),
+        );
+        variants.push(variant);
     }
+    Ok(groups)
+}
+
+// Records whether a type is read as an identifier, failing every read.
+struct IdentifierProbe<'a>(&'a mut bool);
+
+impl<'de> serde::Deserializer<'de> for IdentifierProbe<'_> {
+    type Error = Error;
 
-    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::new("not an identifier", None))
     }
 
-    fn deserialize_unit_struct<V>(
-        self,
-        _name: &'static str,
-        _visitor: V,
-    ) -> Result<V::Value, Self::Error>
+    fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        *self.0 = true;
+        Err(Error::new("not an identifier", None))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct enum ignored_any
     }
 }
 
-pub fn from_str<T>(s: &str) -> Result<T, Error>
-where
-    for<'de> T: Deserialize<'de>,
-{
-    let mut deserializer = Deserializer::try_from_str(s)?;
-    T::deserialize(&mut deserializer)
+/// A query string read as a map from its keys to their values.
+///
+/// The input is self-describing enough to be transcoded to another format, e.g. to JSON with
+/// `serde_transcode`. Keys are visited in input order, a repeated key is given as a sequence,
+/// and values written the way integers, floats or bools are given as such. Nested keys like
+/// `a[b]=1` are not turned into nested maps, so only flat queries transcode faithfully.
+///
+/// ```
+/// use nb_serde_query::Deserializer;
+///
+/// let mut json = Vec::new();
+/// let mut de = Deserializer::try_from_str("q=rust&page=2&tag=a&tag=b").unwrap();
+/// serde_transcode::transcode(&mut de, &mut serde_json::Serializer::new(&mut json)).unwrap();
+/// assert_eq!(json, br#"{"q":"rust","page":2,"tag":["a","b"]}"#);
+/// ```
+pub struct Deserializer<'de> {
+    m: IndexMap<Cow<'de, str>, Values<'de>>,
+    // the keys dropped by the key filter of the options, when recorded, and their number of
+    // values
+    filtered: IndexMap<Cow<'de, str>, usize>,
+    curr_key: Option<Cow<'de, str>>,
+    curr_val: Option<Values<'de>>,
+    // set when an `Option` is given a single empty value, so that a sequence inside it is empty
+    empty_some: bool,
+    // set while reading the fields of a nested struct, the text around their names, e.g.
+    // `filter[` and `]` for `filter[limit]`
+    prefix: Option<(String, &'static str)>,
+    // the field names and element indices leading to the current value, for errors
+    path: Vec<Cow<'de, str>>,
+    // the entries of the struct or map being read
+    entries: Entries<'de>,
+    depth: usize,
+    options: ParseOptions,
+    // the top level key whose value failed first, for `from_str_all_errors`
+    failed_key: Option<String>,
+    // the value read last, which is the one failing when an error is returned
+    #[cfg(feature = "tracing")]
+    last_value: Option<Cow<'de, str>>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde::{Deserialize, Serialize};
+impl<'de> Deserializer<'de> {
+    pub fn try_from_str(s: &'de str) -> Result<Self, Error> {
+        Self::try_from_str_with(s, &ParseOptions::default())
+    }
 
-    #[derive(Debug, Serialize, Deserialize, PartialEq)]
-    struct Pagination {
-        limit: i32,
-        offset: i32,
+    pub fn try_from_str_with(s: &'de str, options: &ParseOptions) -> Result<Self, Error> {
+        let mut m = IndexMap::new();
+        let mut filtered = IndexMap::new();
+        parse_pairs_into(s, options, &mut m, &mut filtered)?;
+        Ok(Self {
+            filtered,
+            ..Self::with_map(m, options)
+        })
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
-    struct MyStruct {
-        name: String,
-        age: i32,
-        ids: Array<String>,
-        #[serde(flatten)]
-        pagination: Option<Pagination>,
+    /// Read from pairs that are already split and decoded, e.g. the entries of a browser's
+    /// `URLSearchParams`. The options apply as they would to the same pairs in a query string.
+    pub fn try_from_pairs<I, K, V>(pairs: I, options: &ParseOptions) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<Cow<'de, str>>,
+        V: Into<Cow<'de, str>>,
+    {
+        let mut m = IndexMap::new();
+        let mut filtered = IndexMap::new();
+        for (i, (key, val)) in pairs.into_iter().enumerate() {
+            if let Some(max) = options.max_pairs.filter(|max| i >= *max) {
+                return Err(Error::limit_exceeded(LimitKind::Pairs, max, None));
+            }
+            let (key, val) = (key.into(), val.into());
+            if let Some(max) = options.max_value_len.filter(|max| val.len() > *max) {
+                let key = Some(key.into_owned());
+                return Err(Error::limit_exceeded(LimitKind::ValueLength, max, key));
+            }
+            insert_pair(key, val, options, &mut m, &mut filtered)?;
+        }
+        Ok(Self {
+            filtered,
+            ..Self::with_map(m, options)
+        })
     }
 
-    #[test]
-    fn test_serde() {
-        let s = r#"{"ids": "[\"1\", \"2\", \"3\", \"4\", \"5\"]"}"#;
-        println!("{:?}", serde_json::from_str::<MyStruct>(s).unwrap());
-        println!(
-            "{:}",
-            serde_json::to_string(&MyStruct {
-                name: "test".to_string(),
-                age: 37,
-                ids: Array(vec![
-                    "1".to_string(),
-                    "2".to_string(),
-                    "3".to_string(),
-                    "4".to_string(),
-                    "5".to_string()
-                ]),
-                pagination: None,
+    fn with_map(m: IndexMap<Cow<'de, str>, Values<'de>>, options: &ParseOptions) -> Self {
+        Self {
+            m,
+            filtered: IndexMap::new(),
+            curr_key: None,
+            curr_val: None,
+            empty_some: false,
+            prefix: None,
+            path: Vec::new(),
+            entries: Entries::map(Vec::new()),
+            depth: 0,
+            options: options.clone(),
+            failed_key: None,
+            #[cfg(feature = "tracing")]
+            last_value: None,
+        }
+    }
+
+    /// Parse a new input with the same options, keeping the allocated buffers.
+    ///
+    /// Values borrow from `s`, so every input must outlive the deserializer. To reuse buffers
+    /// for inputs that do not, e.g. a `String` per request, see [`QueryMap::reparse`].
+    pub fn reset(&mut self, s: &'de str) -> Result<(), Error> {
+        self.curr_key = None;
+        self.curr_val = None;
+        self.empty_some = false;
+        self.prefix = None;
+        self.path.clear();
+        self.failed_key = None;
+        self.entries = Entries::map(Vec::new());
+        self.depth = 0;
+        parse_pairs_into(s, &self.options, &mut self.m, &mut self.filtered)
+    }
+
+    /// The keys the deserialized type did not consume, followed by those dropped by a
+    /// [`KeyFilter`](options::KeyFilter) recording them.
+    pub fn finish(self) -> UnusedKeys {
+        UnusedKeys {
+            keys: self
+                .m
+                .into_iter()
+                .map(|(k, vals)| (k.into_owned(), vals.len()))
+                .chain(self.filtered.into_iter().map(|(k, n)| (k.into_owned(), n)))
+                .collect::<Vec<_>>()
+                .into_iter(),
+        }
+    }
+
+    fn visit_map_with_entries<V>(
+        &mut self,
+        entries: Entries<'de>,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.check_depth()?;
+        let parent_entries = std::mem::replace(&mut self.entries, entries);
+        self.claim_literal_fields();
+        self.depth += 1;
+        let res = visitor.visit_map(&mut *self);
+        self.depth -= 1;
+        self.entries = parent_entries;
+        res
+    }
+
+    // The next entry with a value. Fields without a key are not visited, so that serde defaults
+    // them or reports them missing, nor are keys of a map read by an earlier entry, e.g. by a
+    // struct nested in it. Aliases are visited with the first name of their field.
+    fn take_next_entry(&mut self) -> Option<Cow<'de, str>> {
+        let entries = &self.entries;
+        let found = (entries.next..entries.keys.len()).find(|i| {
+            entries.group(*i).any(|j| match entries.is_struct() {
+                true => self.has_key(&entries.keys[j]),
+                false => self.m.contains_key(&*self.full_key(&entries.keys[j])),
             })
-            .unwrap()
-        );
+        });
+        self.entries.next = found.map_or(self.entries.keys.len(), |i| i + 1);
+        // an entry is visited once, a later name of its field is kept for `next_key`
+        found.map(|i| std::mem::take(&mut self.entries.keys[i]))
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
-    struct NormalVec {
-        ids: Vec<String>,
+    // Whether the input has a value for the field `name`, directly, as a nested struct or map,
+    // or as indexed elements.
+    fn has_key(&self, name: &str) -> bool {
+        let key = self.full_key(name);
+        if self.m.contains_key(&*key) {
+            return true;
+        }
+        let sep = self.options.nesting_separator;
+        self.m.keys().any(|k| {
+            k.strip_prefix(&*key)
+                .and_then(|rest| rest.chars().next())
+                .is_some_and(|c| c == '[' || Some(c) == sep)
+        })
     }
 
-    #[test]
-    fn test_serde_normal_vec() {
-        let v = NormalVec {
-            ids: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+    // Structs, maps and the elements of indexed sequences each add a level. Keys of recursive
+    // types, e.g. `child[child][child]...`, would otherwise nest as deep as the input goes.
+    fn check_depth(&self) -> Result<(), Error> {
+        match self.options.max_depth {
+            Some(max) if self.depth >= max => {
+                Err(Error::limit_exceeded(LimitKind::Depth, max, None))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Parse the current value as a number, naming the key, the input and the type on failure.
+    fn parse_number<T>(&mut self, type_name: &str) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: StdError + 'static,
+    {
+        let key = self.curr_key.as_deref().unwrap_or_default();
+        let val = self
+            .curr_val
+            .take()
+            .and_then(|vals| vals.into_iter().next())
+            .ok_or_else(|| Error::new(format!("no {} value for key `{}`", type_name, key), None))?;
+        let mut text = Cow::Borrowed(val.as_ref());
+        if self.options.lenient_numbers {
+            // an unencoded `+` is decoded to a space
+            if let Some(rest) = val.strip_prefix(['+', ' ']) {
+                if !rest.starts_with(['+', '-']) {
+                    text = Cow::Borrowed(rest);
+                }
+            }
+        }
+        if self.options.number_underscores && text.contains('_') {
+            text = Cow::Owned(text.replace('_', ""));
+        }
+        // errors show the value as it was given
+        parse_value(&text, &val, key, type_name)
+    }
+
+    fn full_key<'k>(&self, name: &'k str) -> Cow<'k, str> {
+        let name = match self.options.nesting_separator {
+            Some(sep) if self.entries.is_struct() => escape_separator(name, sep),
+            _ => Cow::Borrowed(name),
         };
-        println!("{}", to_string(&v).unwrap());
+        match &self.prefix {
+            Some((open, close)) => Cow::Owned(format!("{}{}{}", open, name, close)),
+            None => name,
+        }
     }
 
-    #[test]
-    fn test_serializer() {
-        let s = MyStruct {
-            name: "test".to_string(),
-            age: 37,
-            ids: Array(vec!["1".to_string(), "2".to_string(), "3".to_string()]),
-            pagination: Some(Pagination {
-                limit: 10,
-                offset: 0,
-            }),
+    // A key naming a field of the current struct literally, e.g. `utm.source` for a field
+    // renamed so, is read by that field rather than as `source` nested in a field `utm`,
+    // whichever is declared first. It is escaped so that nested fields no longer see it.
+    fn claim_literal_fields(&mut self) {
+        let Some(sep) = self
+            .options
+            .nesting_separator
+            .filter(|_| self.entries.is_struct())
+        else {
+            return;
         };
-        println!("{}", to_string(&s).unwrap());
+        let names: Vec<String> = (self.entries.keys.iter())
+            .filter(|name| name.contains(sep))
+            .map(|name| name.to_string())
+            .collect();
+        for name in names {
+            let escaped = self.full_key(&name).into_owned();
+            if self.m.contains_key(escaped.as_str()) {
+                continue;
+            }
+            let literal = match &self.prefix {
+                Some((open, close)) => format!("{}{}{}", open, name, close),
+                None => name.to_string(),
+            };
+            if let Some((i, _, vals)) = self.m.shift_remove_full(literal.as_str()) {
+                self.m.shift_insert(i, Cow::Owned(escaped), vals);
+            }
+        }
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
-    struct Empty {
-        a: Option<String>,
-        b: Option<i32>,
-        c: Option<Array<String>>,
+    // The indices of the elements written as `base[0]`, `base[1]`, ..., `base[0][field]`.
+    fn element_indices(&self, base: &str) -> Result<Vec<usize>, Error> {
+        let mut indices: Vec<usize> = self
+            .m
+            .keys()
+            .filter_map(|k| k.strip_prefix(base)?.strip_prefix('['))
+            .filter_map(|rest| rest.split_once(']')?.0.parse().ok())
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        for (expected, i) in indices.iter().enumerate() {
+            if *i != expected {
+                return Err(Error::new(
+                    format!("missing element {} of `{}`", expected, base),
+                    None,
+                ));
+            }
+        }
+        Ok(indices)
     }
 
-    #[test]
-    fn test_serialize_empty() {
-        let s = Empty {
-            a: None,
-            b: None,
-            c: None,
-        };
-        println!("{}", to_string(&s).unwrap());
+    // The text around the field names of the nested struct or sequence which is the current
+    // value, if any of its keys is present. Bracketed keys are always recognized.
+    fn nested_prefix(&self) -> Option<(String, &'static str)> {
+        let base = self.full_key(self.curr_key.as_ref()?);
+        let sep = self.options.nesting_separator;
+        self.m
+            .keys()
+            .filter_map(|k| k.strip_prefix(&*base))
+            .find_map(|rest| match rest.chars().next() {
+                Some('[') => Some((format!("{}[", base), "]")),
+                Some(c) if Some(c) == sep => Some((format!("{}{}", base, c), "")),
+                _ => None,
+            })
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
-    struct De {
-        name: String,
-        age: i32,
-        pagination: Pagination,
-        ids: Vec<i32>,
-        hobbies: Option<Vec<String>>,
-        op: Option<String>,
+    // The key of the current value and its element indices, if it is written as an indexed
+    // sequence.
+    fn indexed_base(&self) -> Result<Option<(String, Vec<usize>)>, Error> {
+        let Some(key) = &self.curr_key else {
+            return Ok(None);
+        };
+        let base = self.full_key(key);
+        let indices = self.element_indices(&base)?;
+        Ok((!indices.is_empty()).then_some((base.into_owned(), indices)))
     }
 
-    #[test]
-    fn test_deserializer() {
-        let mut deserializer = Deserializer::try_from_str(
-            "age=37&name=test&offset=0&limit=10&ids=1&ids=2&op=some&hobbies=moto&hobbies=code",
-        )
-        .unwrap();
-        let de = De::deserialize(&mut deserializer).unwrap();
-        assert!(de.name == "test");
-        assert!(de.age == 37);
-        assert!(
-            de.pagination
-                == Pagination {
-                    limit: 10,
-                    offset: 0
+    // Deserialize the current value with `segment` appended to the path. Errors of nested values
+    // are given the path of the innermost failing one.
+    fn with_path<T>(
+        &mut self,
+        segment: impl Into<Cow<'de, str>>,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        self.path.push(segment.into());
+        let res = f(self).map_err(|mut e| {
+            if self.path.len() == 1 && self.failed_key.is_none() {
+                self.failed_key = Some(self.path[0].to_string());
+            }
+            // a missing field is named by the message, its path is the struct missing it
+            let nested = match e.kind {
+                ErrorKind::MissingField { .. } => !self.path.is_empty(),
+                _ => self.path.len() > 1,
+            };
+            if e.path.is_none() && nested {
+                let mut path = String::new();
+                for segment in &self.path {
+                    if !path.is_empty() && !segment.starts_with('[') {
+                        path.push('.');
+                    }
+                    path.push_str(segment);
                 }
-        );
-        assert!(de.ids == vec![1, 2]);
-        assert!(de.op == Some("some".into()));
-        assert!(de.hobbies == Some(vec!["moto".into(), "code".into()]));
+                e.path = Some(path);
+            }
+            e
+        });
+        self.path.pop();
+        res
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
-    struct Se {
-        name: String,
-        age: i32,
-        pagination: Pagination,
-        ids: Vec<i32>,
-        hobbies: Option<Vec<String>>,
-        op: Option<String>,
+    // Attach the key to errors raised by visitors, e.g. for `0` given to a `NonZeroU32`.
+    fn with_key<T>(&self, res: Result<T, Error>) -> Result<T, Error> {
+        res.map_err(|e| {
+            Error::with_kind(
+                ErrorKind::OutOfRange,
+                format!(
+                    "invalid value for key `{}`",
+                    self.curr_key.as_deref().unwrap_or_default()
+                ),
+                Some(Box::new(e)),
+            )
+        })
     }
 
-    #[test]
-    fn test_to_string() {
-        let se = Se {
-            name: "test".into(),
-            age: 37,
-            pagination: Pagination {
-                limit: 10,
-                offset: 0,
-            },
-            ids: vec![1, 2],
-            hobbies: Some(vec!["moto".into(), "code".into()]),
-            op: Some("some".into()),
+    // A visitor without 128-bit support rejects a value with serde's default type error, which
+    // is reported as a value out of its range.
+    fn with_key_wide<T>(&self, res: Result<T, Error>, v: impl Display) -> Result<T, Error> {
+        match res {
+            Err(e) if e.kind == ErrorKind::InvalidType => Err(Error::with_kind(
+                ErrorKind::OutOfRange,
+                format!(
+                    "invalid value `{}` for key `{}`, expected a 64-bit integer",
+                    v,
+                    self.curr_key.as_deref().unwrap_or_default()
+                ),
+                Some(Box::new(e)),
+            )),
+            res => self.with_key(res),
+        }
+    }
+}
+
+// Parse `text`, the value of `key` as given or cleaned up from `given`, naming the given value,
+// the key and the type on failure. A number written with digits alone is out of the range of its
+// type, a value of a type other than a number is rejected by it.
+pub(crate) fn parse_value<T>(
+    text: &str,
+    given: &str,
+    key: &str,
+    type_name: &str,
+) -> Result<T, Error>
+where
+    T: FromStr,
+    T::Err: StdError + 'static,
+{
+    text.parse().map_err(|e| {
+        let number = matches!(
+            type_name,
+            "i8" | "i16"
+                | "i32"
+                | "i64"
+                | "i128"
+                | "isize"
+                | "u8"
+                | "u16"
+                | "u32"
+                | "u64"
+                | "u128"
+                | "usize"
+                | "f32"
+                | "f64"
+        );
+        let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+        let kind = if !number {
+            ErrorKind::InvalidValue {
+                key: key.to_string(),
+                expected: type_name.to_string(),
+            }
+        } else if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            ErrorKind::OutOfRange
+        } else {
+            ErrorKind::InvalidNumber
         };
-        let s = to_string(&se).unwrap();
-        assert!(
-            s == "name=test&age=37&limit=10&offset=0&ids=1&ids=2&hobbies=moto&hobbies=code&op=some"
+        Error::with_kind(
+            kind,
+            format!(
+                "invalid value `{}` for key `{}`, expected {}",
+                given, key, type_name
+            ),
+            Some(Box::new(e)),
         )
-    }
+    })
+}
 
-    #[test]
-    fn test_serialize_vector() {
-        #[derive(Debug, Serialize)]
-        pub struct Vector {
-            ids: Vec<String>,
+impl<'de> MapAccess<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    // Structs derived by serde pass every name of a field, aliases included, in `fields` and
+    // identify fields by a fieldless enum read as an identifier. All names of one field
+    // deserialize to the same variant, so they are grouped and only one of them is yielded: the
+    // one appearing first in the input, or the first listed one if none is present.
+    fn next_key<K>(&mut self) -> Result<Option<K>, Self::Error>
+    where
+        K: Deserialize<'de>,
+    {
+        match &self.entries.fields {
+            Some(groups) => {
+                if groups.is_empty() && !self.entries.keys.is_empty() {
+                    self.entries.fields = Some(group_names::<K>(&self.entries.keys)?);
+                }
+            }
+            None => return self.next_key_seed(std::marker::PhantomData),
+        }
+        let Some(name) = self.take_next_entry() else {
+            return Ok(None);
+        };
+        let first = self.entries.next - 1;
+        let key = K::deserialize(KeyDeserializer::new(name.clone()))?;
+        let mut chosen = name;
+        let mut chosen_index = self.m.get_index_of(&*self.full_key(&chosen));
+        let mut others = Vec::new();
+        let aliases: Vec<Cow<'de, str>> = (self.entries.group(first).skip(1))
+            .map(|i| self.entries.keys[i].clone())
+            .collect();
+        for alias in aliases {
+            let index = self.m.get_index_of(&*self.full_key(&alias));
+            if index.is_some() && (chosen_index.is_none() || index < chosen_index) {
+                others.push(std::mem::replace(&mut chosen, alias));
+                chosen_index = index;
+            } else {
+                others.push(alias);
+            }
+        }
+        for n in others {
+            let k = self.full_key(&n).into_owned();
+            self.m.shift_remove(k.as_str());
         }
+        self.curr_key = Some(chosen);
+        Ok(Some(key))
+    }
 
-        let v = Vector {
-            ids: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+    {
+        let Some(name) = self.take_next_entry() else {
+            return Ok(None);
         };
-        let s = to_string(&v).unwrap();
-        assert!(s == "ids=1&ids=2&ids=3");
+        let key = match self.options.nesting_separator {
+            Some(sep) if !self.entries.is_struct() => unescape_separator(name.clone(), sep),
+            _ => name.clone(),
+        };
+        let key = seed.deserialize(KeyDeserializer::new(key))?;
+        self.curr_key = Some(name);
+        Ok(Some(key))
+    }
+
+    fn next_entry_seed<K, V>(
+        &mut self,
+        kseed: K,
+        vseed: V,
+    ) -> Result<Option<(K::Value, V::Value)>, Self::Error>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let Some(key) = self.next_key_seed(kseed)? else {
+            return Ok(None);
+        };
+        self.next_value_seed(vseed).map(|v| Some((key, v)))
+    }
+
+    // An `Option` given a single empty value its inner type cannot be read from, e.g. `qty=` for
+    // `Option<u32>` as posted by a blank number input, is `None` rather than an error.
+    fn next_value<V>(&mut self) -> Result<V, Self::Error>
+    where
+        V: Deserialize<'de>,
+    {
+        let blank = match &self.curr_key {
+            Some(k) => matches!(
+                self.m.get(&*self.full_key(k)).map(|vals| vals.as_slice()),
+                Some([v]) if v.is_empty()
+            ),
+            None => false,
+        };
+        match self.next_value_seed(std::marker::PhantomData) {
+            Err(e) if blank => {
+                V::deserialize(serde::de::value::UnitDeserializer::<Error>::new()).map_err(|_| e)
+            }
+            res => res,
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let k = self
+            .curr_key
+            .clone()
+            .ok_or_else(|| Error::new("no key", None))?;
+        self.curr_val = self.m.shift_remove(&*self.full_key(&k));
+        self.trace_value();
+        self.empty_some = false;
+        // errors raised by types themselves, e.g. by untagged enums, don't know the key
+        self.with_path(k.clone(), |de| {
+            seed.deserialize(de).map_err(|mut e| match e.kind {
+                // the path of an element, e.g. `statuses[1]`, is kept
+                ErrorKind::Custom | ErrorKind::InvalidType => {
+                    let path = e.path.take();
+                    let mut e =
+                        Error::new(format!("invalid value for key `{}`", k), Some(Box::new(e)));
+                    e.path = path;
+                    e
+                }
+                _ => e,
+            })
+        })
+    }
+}
+
+// Reads a key of a map or the name of a field. Keys of maps with number, char or bool keys
+// are parsed. Identifiers are given as strings or bytes, and a visitor asking for a number,
+// e.g. one of a hand written field identifier, is given the name when it is not one.
+struct KeyDeserializer<'de> {
+    key: Cow<'de, str>,
+}
+
+impl<'de> KeyDeserializer<'de> {
+    fn new(key: impl Into<Cow<'de, str>>) -> Self {
+        Self { key: key.into() }
+    }
+}
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                if let Ok(v) = self.key.parse() {
+                    return visitor.$visit(v);
+                }
+                let msg = format!("invalid key `{}`, expected {}", self.key, &visitor as &dyn Expected);
+                visitor
+                    .visit_str::<Error>(&self.key)
+                    .map_err(|_| Error::new(msg, None))
+            }
+        )*
+    };
+}
+
+impl<'de> serde::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.key {
+            Cow::Borrowed(key) => visitor.visit_borrowed_str(key),
+            Cow::Owned(key) => visitor.visit_string(key),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.key {
+            Cow::Borrowed(key) => visitor.visit_borrowed_bytes(key.as_bytes()),
+            Cow::Owned(key) => visitor.visit_bytes(key.as_bytes()),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_byte_buf(self.key.into_owned().into_bytes())
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.key
+            .into_owned()
+            .into_deserializer()
+            .deserialize_enum(name, variants, visitor)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+// A separator in a field name, e.g. the `.` of a field renamed `utm.source`, is looked up
+// escaped as `utm\.source`, the way a literal separator is kept in the keys.
+fn escape_separator(name: &str, sep: char) -> Cow<'_, str> {
+    match name.contains(sep) {
+        true => Cow::Owned(name.replace(sep, &format!("\\{}", sep))),
+        false => Cow::Borrowed(name),
+    }
+}
+
+// The key with its escaped separators made literal again, as map keys are given to serde.
+fn unescape_separator(key: Cow<'_, str>, sep: char) -> Cow<'_, str> {
+    match key.contains('\\') {
+        true => Cow::Owned(key.replace(&format!("\\{}", sep), &sep.to_string())),
+        false => key,
+    }
+}
+
+// Split a value at `delimiter`, borrowing the parts if the value is borrowed.
+fn split_value(val: Cow<'_, str>, delimiter: char) -> Values<'_> {
+    match val {
+        Cow::Borrowed(v) => v.split(delimiter).map(Cow::Borrowed).collect(),
+        Cow::Owned(v) => v
+            .split(delimiter)
+            .map(|part| Cow::Owned(part.to_string()))
+            .collect(),
+    }
+}
+
+// Gives the values of a key in input order, then the elements of an indexed sequence in index
+// order. Both orders are part of the documented behavior of `from_str`.
+struct SeqDeserializer<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    vals: smallvec::IntoIter<[Cow<'de, str>; 1]>,
+    // the key of an indexed sequence and the indices of its elements
+    base: String,
+    indices: std::vec::IntoIter<usize>,
+    // the index of the next element
+    index: usize,
+}
+
+impl<'de, 'a> SeqAccess<'de> for SeqDeserializer<'a, 'de> {
+    type Error = Error;
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        let segment = format!("[{}]", self.index);
+        self.index += 1;
+        if let Some(val) = self.vals.next() {
+            self.de.curr_val = Some(smallvec![val]);
+            self.de.trace_value();
+            return self
+                .de
+                .with_path(segment, |de| seed.deserialize(ElementDeserializer { de }))
+                .map(Some);
+        }
+        if let Some(i) = self.indices.next() {
+            // the element is read like a top level value named `base[i]`
+            let key = format!("{}[{}]", self.base, i);
+            self.de.curr_val = self.de.m.shift_remove(key.as_str());
+            self.de.trace_value();
+            self.de.check_depth()?;
+            let parent_key = self.de.curr_key.replace(Cow::Owned(key));
+            let parent_prefix = self.de.prefix.take();
+            // `base[i]` is a whole key, not a field name to escape
+            let parent_entries = std::mem::replace(&mut self.de.entries, Entries::map(Vec::new()));
+            self.de.depth += 1;
+            let res = self
+                .de
+                .with_path(segment, |de| seed.deserialize(ElementDeserializer { de }));
+            self.de.depth -= 1;
+            self.de.curr_key = parent_key;
+            self.de.prefix = parent_prefix;
+            self.de.entries = parent_entries;
+            return res.map(Some);
+        }
+        Ok(None)
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let entries = Entries::fields(fields);
+        // nested structs without prefixed keys read the keys of their fields as they are
+        let prefix = match self.depth {
+            0 => None,
+            _ => self.nested_prefix(),
+        };
+        if prefix.is_none() {
+            return self.visit_map_with_entries(entries, visitor);
+        }
+        let parent = std::mem::replace(&mut self.prefix, prefix);
+        let res = self.visit_map_with_entries(entries, visitor);
+        self.prefix = parent;
+        res
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let prefix = match self.depth {
+            0 => None,
+            _ => self.nested_prefix(),
+        };
+        let Some((open, close)) = prefix else {
+            let entries = Entries::map(self.m.keys().cloned().collect());
+            return self.visit_map_with_entries(entries, visitor);
+        };
+        // a nested map, e.g. `attrs[color]=red`, is given the keys with the prefix stripped
+        let entries = Entries::map(
+            self.m
+                .keys()
+                .filter_map(|k| match k {
+                    Cow::Borrowed(k) => Some(Cow::Borrowed(
+                        k.strip_prefix(open.as_str())?.strip_suffix(close)?,
+                    )),
+                    Cow::Owned(k) => Some(Cow::Owned(
+                        k.strip_prefix(open.as_str())?
+                            .strip_suffix(close)?
+                            .to_string(),
+                    )),
+                })
+                .collect(),
+        );
+        let parent = self.prefix.replace((open, close));
+        let res = self.visit_map_with_entries(entries, visitor);
+        self.prefix = parent;
+        res
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // values naming a variant, e.g. the tag of an internally tagged enum
+        if self.curr_val.is_some() {
+            return self.deserialize_str(visitor);
+        }
+        match self.curr_key.clone() {
+            Some(Cow::Borrowed(key)) => visitor.visit_borrowed_str(key),
+            Some(Cow::Owned(key)) => visitor.visit_string(key),
+            None => Err(Error::new("no key", None)),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let key = self.curr_key.as_deref().unwrap_or_default();
+        let val = self
+            .curr_val
+            .take()
+            .and_then(|vals| vals.into_iter().next())
+            .ok_or_else(|| Error::new(format!("no bool value for key `{}`", key), None))?;
+        if self.options.lenient_bool {
+            match val.to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" | "y" | "on" => return visitor.visit_bool(true),
+                "false" | "0" | "no" | "n" | "off" => return visitor.visit_bool(false),
+                _ => {}
+            }
+        }
+        visitor.visit_bool(parse_value(&val, &val, key, "bool")?)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let v = self.parse_number("i32")?;
+        self.with_key(visitor.visit_i32(v))
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match &self.curr_val {
+            // the inner `Option` of `Option<Option<T>>` given `key=`
+            Some(_) if self.empty_some => {
+                self.curr_val = None;
+                self.empty_some = false;
+                visitor.visit_none()
+            }
+            Some(val) if !val.is_empty() => {
+                self.empty_some = matches!(val.as_slice(), [v] if v.is_empty());
+                visitor.visit_some(self)
+            }
+            None if self.nested_prefix().is_some() => visitor.visit_some(self),
+            _ => {
+                self.curr_val = None;
+                visitor.visit_none()
+            }
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let (base, indices) = match self.curr_val {
+            Some(_) => (String::new(), Vec::new()),
+            None => self.indexed_base()?.unwrap_or_default(),
+        };
+        let mut vals = self.curr_val.take().unwrap_or_default();
+        if std::mem::take(&mut self.empty_some) {
+            vals.clear();
+        }
+        visitor.visit_seq(SeqDeserializer {
+            de: self,
+            vals: vals.into_iter(),
+            base,
+            indices: indices.into_iter(),
+            index: 0,
+        })
+    }
+
+    // Self-describing types, e.g. `#[serde(untagged)]` enums, buffer the value they are given and
+    // try each variant against it, so values which look like integers, finite floats or bools are
+    // given as such. Everything else, including floats which would lose precision like `1.50`,
+    // is given as a string. Untagged enums over structs are not supported.
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let v = match self.curr_val.as_deref() {
+            None if self.depth == 0 || self.nested_prefix().is_some() => {
+                return self.deserialize_map(visitor)
+            }
+            None => {
+                return Err(Error::new(
+                    format!(
+                        "no value for key `{}`",
+                        self.curr_key.as_deref().unwrap_or_default()
+                    ),
+                    None,
+                ))
+            }
+            Some([v]) => v,
+            Some(_) => return self.deserialize_seq(visitor),
+        };
+        // only values written the way numbers are, so `007` or `+1` stay strings
+        if let Ok(n) = v.parse::<u64>() {
+            if n.to_string() == **v {
+                self.curr_val = None;
+                return visitor.visit_u64(n);
+            }
+        }
+        if let Ok(n) = v.parse::<i64>() {
+            if n.to_string() == **v {
+                self.curr_val = None;
+                return visitor.visit_i64(n);
+            }
+        }
+        if let Ok(f) = v.parse::<f64>() {
+            if f.is_finite() && f.to_string() == **v {
+                self.curr_val = None;
+                return visitor.visit_f64(f);
+            }
+        }
+        if let Ok(b) = v.parse::<bool>() {
+            self.curr_val = None;
+            return visitor.visit_bool(b);
+        }
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let v = self.parse_number("i8")?;
+        self.with_key(visitor.visit_i8(v))
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let v = self.parse_number("i16")?;
+        self.with_key(visitor.visit_i16(v))
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let key = self.curr_key.clone().unwrap_or_default();
+        let val = self
+            .curr_val
+            .take()
+            .and_then(|vals| vals.into_iter().next())
+            .ok_or_else(|| Error::new(format!("no bytes value for key `{}`", key), None))?;
+        let encoding = self.options.bytes_encoding;
+        let bytes = encoding.decode(&val).map_err(|e| {
+            let expected = encoding.name().to_string();
+            let message = format!("invalid value for key `{}`, expected {}", key, expected);
+            let key = key.into_owned();
+            Error::with_kind(ErrorKind::InvalidValue { key, expected }, message, Some(e))
+        })?;
+        visitor.visit_byte_buf(bytes)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let key = self.curr_key.as_deref().unwrap_or_default();
+        let val = self
+            .curr_val
+            .take()
+            .and_then(|vals| vals.into_iter().next())
+            .ok_or_else(|| Error::new(format!("no char value for key `{}`", key), None))?;
+        visitor.visit_char(parse_value(&val, &val, key, "char")?)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // only unit variants can be written as a single value, e.g. `order=Asc`
+        let val = self
+            .curr_val
+            .take()
+            .and_then(|vals| vals.into_iter().next())
+            .ok_or_else(|| Error::new("no enum value", None))?;
+        visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(val))
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let v = self.parse_number("f32")?;
+        self.with_key(visitor.visit_f32(v))
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let v = self.parse_number("f64")?;
+        self.with_key(visitor.visit_f64(v))
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let v: i128 = self.parse_number("i128")?;
+        // a visitor is consumed by a failed visit, so values fitting in 64 bits are visited as
+        // such up front, which visitors without 128-bit support accept as well
+        if let Ok(v) = i64::try_from(v) {
+            return self.with_key(visitor.visit_i64(v));
+        }
+        let res = visitor.visit_i128(v);
+        self.with_key_wide(res, v)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let v = self.parse_number("i64")?;
+        self.with_key(visitor.visit_i64(v))
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.curr_val = None;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // an `Array` is the JSON in the value
+        if name == ARRAY_NAME {
+            return self.deserialize_str(visitor);
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // types parsed from strings, e.g. dates, describe their format in `expecting`
+        let expected = (&visitor as &dyn Expected).to_string();
+        // values without escapes are borrowed from the input
+        match self
+            .curr_val
+            .take()
+            .ok_or_else(|| Error::new("no string value", None))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::new("no string value", None))?
+        {
+            Cow::Borrowed(v) => visitor.visit_borrowed_str::<Error>(v),
+            Cow::Owned(v) => visitor.visit_string::<Error>(v),
+        }
+        .map_err(|e| {
+            let key = self.curr_key.as_deref().unwrap_or_default().to_string();
+            let message = format!("invalid value for key `{}`, expected {}", key, expected);
+            Error::with_kind(
+                ErrorKind::InvalidValue { key, expected },
+                message,
+                Some(Box::new(e)),
+            )
+        })
+    }
+
+    // Tuples and fixed size arrays are read like sequences, `rgb=255&rgb=128&rgb=0` or
+    // `rgb[0]=255&...`, or from a single value split at the tuple delimiter, `rgb=255,128,0`.
+    // Unlike sequences they must have exactly `len` elements.
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.empty_some = false;
+        let (base, indices, vals) = match self.curr_val.take() {
+            Some(mut vals) => {
+                if let ([val], true) = (vals.as_mut_slice(), len > 1) {
+                    vals = split_value(std::mem::take(val), self.options.tuple_delimiter);
+                }
+                (String::new(), Vec::new(), vals)
+            }
+            None => {
+                let (base, indices) = self.indexed_base()?.unwrap_or_default();
+                (base, indices, Values::new())
+            }
+        };
+        let count = vals.len() + indices.len();
+        if count != len {
+            return Err(Error::new(
+                format!(
+                    "expected {} values for key `{}`, got {}",
+                    len,
+                    self.curr_key.as_deref().unwrap_or_default(),
+                    count
+                ),
+                None,
+            ));
+        }
+        visitor.visit_seq(SeqDeserializer {
+            de: self,
+            vals: vals.into_iter(),
+            base,
+            indices: indices.into_iter(),
+            index: 0,
+        })
+    }
+
+    // tuple structs are written as one value, `center=52.1,13.4`, split at the tuple delimiter
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.empty_some = false;
+        let mut vals = self.curr_val.take().unwrap_or_default();
+        if let [val] = vals.as_mut_slice() {
+            vals = split_value(std::mem::take(val), self.options.tuple_delimiter);
+        }
+        if vals.len() != len {
+            return Err(Error::new(
+                format!(
+                    "expected {} fields for tuple struct `{}` at key `{}`, got {}",
+                    len,
+                    name,
+                    self.curr_key.as_deref().unwrap_or_default(),
+                    vals.len()
+                ),
+                None,
+            ));
+        }
+        visitor.visit_seq(SeqDeserializer {
+            de: self,
+            vals: vals.into_iter(),
+            base: String::new(),
+            indices: Vec::new().into_iter(),
+            index: 0,
+        })
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let v: u128 = self.parse_number("u128")?;
+        if let Ok(v) = u64::try_from(v) {
+            return self.with_key(visitor.visit_u64(v));
+        }
+        let res = visitor.visit_u128(v);
+        self.with_key_wide(res, v)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let v = self.parse_number("u16")?;
+        self.with_key(visitor.visit_u16(v))
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let v = self.parse_number("u32")?;
+        self.with_key(visitor.visit_u32(v))
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let v = self.parse_number("u64")?;
+        self.with_key(visitor.visit_u64(v))
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let v = self.parse_number("u8")?;
+        self.with_key(visitor.visit_u8(v))
+    }
+
+    // units are written as no pair at all, and read from an empty value too
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let vals = self.curr_val.take().unwrap_or_default();
+        match vals.iter().find(|v| !v.is_empty()) {
+            None => visitor.visit_unit(),
+            Some(v) => Err(Error::invalid_value(
+                self.curr_key.as_deref().unwrap_or_default(),
+                "an empty value",
+                v,
+            )),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+}
+
+// Implement `serde::Deserializer` for an owning type by forwarding to the `&mut Deserializer`
+// it holds.
+macro_rules! forward_to_deserializer {
+    ($($method:ident($($arg:ident: $ty:ty),*)),* $(,)?) => {
+        $(
+            fn $method<V>(mut self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                serde::Deserializer::$method(self.inner(), $($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+macro_rules! forward_all_to_deserializer {
+    () => {
+        forward_to_deserializer! {
+            deserialize_any(),
+            deserialize_bool(),
+            deserialize_i8(),
+            deserialize_i16(),
+            deserialize_i32(),
+            deserialize_i64(),
+            deserialize_i128(),
+            deserialize_u8(),
+            deserialize_u16(),
+            deserialize_u32(),
+            deserialize_u64(),
+            deserialize_u128(),
+            deserialize_f32(),
+            deserialize_f64(),
+            deserialize_char(),
+            deserialize_str(),
+            deserialize_string(),
+            deserialize_bytes(),
+            deserialize_byte_buf(),
+            deserialize_option(),
+            deserialize_unit(),
+            deserialize_unit_struct(name: &'static str),
+            deserialize_newtype_struct(name: &'static str),
+            deserialize_seq(),
+            deserialize_tuple(len: usize),
+            deserialize_tuple_struct(name: &'static str, len: usize),
+            deserialize_map(),
+            deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+            deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+            deserialize_identifier(),
+            deserialize_ignored_any(),
+        }
+    };
+}
+
+// Reads one element of a sequence, where an empty value, e.g. the second of `n=1&n=&n=3`, is
+// an absent element rather than an empty one.
+struct ElementDeserializer<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de> ElementDeserializer<'_, 'de> {
+    fn inner(&mut self) -> &mut Deserializer<'de> {
+        self.de
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for ElementDeserializer<'_, 'de> {
+    type Error = Error;
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if matches!(self.de.curr_val.as_deref(), Some([v]) if v.is_empty()) {
+            self.de.curr_val = None;
+            return visitor.visit_none();
+        }
+        self.de.deserialize_option(visitor)
+    }
+
+    forward_to_deserializer! {
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    }
+}
+
+impl<'de> Deserializer<'de> {
+    fn inner(&mut self) -> &mut Self {
+        self
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    forward_all_to_deserializer!();
+}
+
+/// The parsed pairs, deserializing like the query string they were parsed from.
+impl<'de> IntoDeserializer<'de, Error> for &'de QueryMap {
+    type Deserializer = Deserializer<'de>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        let m = self
+            .entries
+            .iter()
+            .map(|(k, vals)| {
+                let vals = vals.iter().map(|v| Cow::Borrowed(v.as_str())).collect();
+                (Cow::Borrowed(k.as_str()), vals)
+            })
+            .collect();
+        Deserializer::with_map(m, &ParseOptions::default())
+    }
+}
+
+/// The decoded values of a single key, deserializing like a field holding them, e.g. to read
+/// a leftover pair into a typed value. Sequences receive every value, everything else the
+/// first one.
+pub struct ValueDeserializer<'de> {
+    de: Deserializer<'de>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    pub fn new(value: &'de str) -> Self {
+        Self::with_values([value])
+    }
+
+    pub fn with_values<I>(values: I) -> Self
+    where
+        I: IntoIterator<Item = &'de str>,
+    {
+        let mut de = Deserializer::with_map(IndexMap::new(), &ParseOptions::default());
+        de.curr_key = Some(Cow::Borrowed("value"));
+        de.curr_val = Some(values.into_iter().map(Cow::Borrowed).collect());
+        // read like the value of a field, not as a map of the top level
+        de.depth = 1;
+        Self { de }
+    }
+
+    /// Name the values `key` in errors instead of `value`.
+    pub fn key(mut self, key: &str) -> Self {
+        self.de.curr_key = Some(Cow::Owned(key.to_string()));
+        self
+    }
+
+    fn inner(&mut self) -> &mut Deserializer<'de> {
+        &mut self.de
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    forward_all_to_deserializer!();
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Deserialize `T` from `s`.
+///
+/// Repeated values of a key are given to sequences in the order they appear in the input,
+/// however they are interleaved with other keys: `ids=3&page=1&ids=1&ids=2` reads `ids` as
+/// `[3, 1, 2]`. Indexed elements such as `ids[1]=b&ids[0]=a` are ordered by their index.
+///
+/// The input ends at a `#`, so the fragment of a URL tail like `page=2#results` is ignored
+/// rather than read as part of the last value, or rejected with
+/// [`ParseOptions::deny_fragment`]. The [`Serializer`] writes `#` in keys and values as `%23`.
+///
+/// Fields without a key are left to serde: `Option`s are `None`, fields with
+/// `#[serde(default)]` take their default, and any other field is reported as
+/// ``missing field `name` ``. A sequence that may be empty, and so absent, needs a default.
+///
+/// Serde buffers the pairs of `#[serde(flatten)]` fields before their types are known, so they
+/// are typed by how they are written: `2` is a number, `true` a bool, a repeated key a sequence
+/// and anything else a string. A flattened struct with number fields reads as expected, but a
+/// flattened `String` given `7`, or a sequence given a single value, is rejected. Keys are
+/// buffered as strings, so a flattened map needs string keys. A flattened map of
+/// `serde_json::Value` keeps whatever is left.
+pub fn from_str<'de, T>(s: &'de str) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    from_str_with(s, &ParseOptions::default())
+}
+
+pub fn from_str_with<'de, T>(s: &'de str, opts: &ParseOptions) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("from_str", pairs = tracing::field::Empty).entered();
+    let mut deserializer =
+        Deserializer::try_from_str_with(s, opts).inspect_err(Deserializer::trace_parse_error)?;
+    #[cfg(feature = "tracing")]
+    span.record(
+        "pairs",
+        deserializer.m.values().map(|v| v.len()).sum::<usize>(),
+    );
+    let v = T::deserialize(&mut deserializer).inspect_err(|e| deserializer.trace_error(e))?;
+    #[cfg(feature = "tracing")]
+    if !deserializer.m.is_empty() {
+        let keys: Vec<&str> = deserializer.m.keys().map(|k| k.as_ref()).collect();
+        tracing::debug!(keys = ?keys, "unused keys");
+    }
+    if opts.deny_unknown && !deserializer.m.is_empty() {
+        let mut keys: Vec<&str> = deserializer.m.keys().map(|k| k.as_ref()).collect();
+        keys.sort();
+        let mut err = Error::unknown_field(keys[0]);
+        err.message = format!("unknown keys: {}", keys.join(", "));
+        return Err(err);
+    }
+    Ok(v)
+}
+
+/// Deserialize `T` from `s` like [`from_str_with`], but report the errors of all rejected
+/// parameters rather than the first one, e.g. to list every invalid field of a form.
+///
+/// The keys of a failing value are left out and `T` is read again, until it succeeds or fails
+/// without a key to leave out, e.g. for a missing field, whose error is then the last one.
+/// Fields only missing because their value was rejected are not reported again.
+///
+/// ```
+/// use nb_serde_query::{from_str_all_errors, options::ParseOptions};
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Range {
+///     limit: i32,
+///     from: u64,
+///     to: Option<u64>,
+/// }
+///
+/// let errors = from_str_all_errors::<Range>("limit=abc&from=x&to=3", &ParseOptions::new())
+///     .unwrap_err();
+/// let params: Vec<_> = errors.iter().map(|(param, _)| param.unwrap()).collect();
+/// assert_eq!(params, ["limit", "from"]);
+/// ```
+pub fn from_str_all_errors<'de, T>(s: &'de str, opts: &ParseOptions) -> Result<T, Errors>
+where
+    T: Deserialize<'de>,
+{
+    let mut errors = Errors::default();
+    let mut rejected: Vec<String> = Vec::new();
+    loop {
+        let mut deserializer = Deserializer::try_from_str_with(s, opts)?;
+        let sep = opts.nesting_separator;
+        deserializer.m.retain(|k, _| {
+            let root = k
+                .split(|c| c == '[' || Some(c) == sep)
+                .next()
+                .unwrap_or_default();
+            // a field named with a separator, e.g. `utm.source`, is its own root
+            let literal = sep.map_or(Cow::Borrowed(k.as_ref()), |sep| {
+                unescape_separator(Cow::Borrowed(k.as_ref()), sep)
+            });
+            !rejected.iter().any(|r| r == root || *r == literal)
+        });
+        let err = match T::deserialize(&mut deserializer) {
+            Ok(v) => {
+                if opts.deny_unknown {
+                    let mut keys: Vec<&str> = deserializer.m.keys().map(|k| k.as_ref()).collect();
+                    keys.sort();
+                    for key in keys {
+                        errors.push(Some(key.to_string()), Error::unknown_field(key));
+                    }
+                }
+                return if errors.is_empty() {
+                    Ok(v)
+                } else {
+                    Err(errors)
+                };
+            }
+            Err(err) => err,
+        };
+        match deserializer.failed_key.take() {
+            Some(key) if !rejected.contains(&key) => {
+                errors.push(Some(key.clone()), err);
+                rejected.push(key);
+            }
+            _ => {
+                let missing = matches!(&err.kind, ErrorKind::MissingField { field } if rejected.contains(field));
+                if !missing {
+                    let param = err.key().map(str::to_string);
+                    errors.push(param, err);
+                }
+                return Err(errors);
+            }
+        }
+    }
+}
+
+/// Deserialize `T` from the query of `uri`. A URI without a query, or with an empty one, reads
+/// as an empty query string, so `T` is made of its defaults and `None`s.
+#[cfg(feature = "http")]
+pub fn from_uri<'de, T>(uri: &'de http::Uri) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    from_str(uri.query().unwrap_or_default())
+}
+
+/// Deserialize `T` from `s` and return the pairs `T` did not consume.
+///
+/// The leftover pairs are grouped by key in the order the keys first appeared in the input,
+/// repeated values keep their relative order. Values are returned the same way `T` would
+/// have seen them.
+pub fn from_str_partial<'de, T>(s: &'de str) -> Result<(T, Vec<(String, String)>), Error>
+where
+    T: Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::try_from_str(s)?;
+    let v = T::deserialize(&mut deserializer)?;
+    let leftovers = deserializer
+        .m
+        .into_iter()
+        .flat_map(|(k, vals)| {
+            vals.into_iter()
+                .map(move |v| (k.to_string(), v.into_owned()))
+        })
+        .collect();
+    Ok((v, leftovers))
+}
+
+#[cfg(not(feature = "tracing"))]
+impl<'de> Deserializer<'de> {
+    fn trace_value(&mut self) {}
+
+    fn trace_parse_error(_: &Error) {}
+
+    fn trace_error(&self, _: &Error) {}
+}
+
+#[cfg(feature = "tracing")]
+impl<'de> Deserializer<'de> {
+    fn trace_value(&mut self) {
+        self.last_value = self
+            .curr_val
+            .as_ref()
+            .and_then(|vals| vals.first().cloned());
+    }
+
+    fn trace_parse_error(e: &Error) {
+        tracing::warn!(kind = ?e.kind, "failed to parse query string");
+    }
+
+    // Values are cut to their first 16 characters unless the options allow full values, the
+    // message is left out as it may contain the value.
+    fn trace_error(&self, e: &Error) {
+        let key = e.path.as_deref().or(self.curr_key.as_deref());
+        let value = self
+            .last_value
+            .as_deref()
+            .map(|v| match self.options.trace_full_values {
+                true => v,
+                false => v.char_indices().nth(16).map_or(v, |(i, _)| &v[..i]),
+            });
+        tracing::warn!(kind = ?e.kind, key, value, "failed to deserialize query string");
+    }
+}
+
+/// The keys a type did not consume and the number of values of each, in the order the keys
+/// first appeared in the input.
+#[derive(Debug)]
+pub struct UnusedKeys {
+    keys: std::vec::IntoIter<(String, usize)>,
+}
+
+impl Iterator for UnusedKeys {
+    type Item = (String, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.keys.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl ExactSizeIterator for UnusedKeys {}
+
+/// Deserialize `T` from `s` and return the keys `T` did not consume, e.g. to log parameters
+/// clients send but handlers ignore.
+pub fn from_str_traced<'de, T>(s: &'de str) -> Result<(T, UnusedKeys), Error>
+where
+    T: Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::try_from_str(s)?;
+    let v = T::deserialize(&mut deserializer)?;
+    Ok((v, deserializer.finish()))
+}
+
+/// The keys `T` reads: the names and aliases of its fields, as serde lists them, e.g. to reject
+/// requests with unexpected parameters before deserializing them, or to document an endpoint.
+///
+/// The names are those serde gives the deserializer, without deserializing anything, which
+/// limits them:
+///
+/// - a nested struct is named by its field, its own keys, e.g. `filter[limit]`, are not listed;
+/// - fields skipped when deserializing are not listed;
+/// - a struct with a `#[serde(flatten)]`ed field is read as a map, whose keys serde doesn't
+///   name, so like maps, enums and other types it fails.
+///
+/// ```
+/// use nb_serde_query::field_names;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Search {
+///     q: String,
+///     #[serde(alias = "size")]
+///     limit: Option<u32>,
+/// }
+///
+/// assert_eq!(field_names::<Search>().unwrap(), ["q", "limit", "size"]);
+/// ```
+pub fn field_names<T>() -> Result<Vec<String>, Error>
+where
+    T: Deserialize<'static>,
+{
+    let mut names = None;
+    // always fails, once the names are recorded or at the first type that isn't a struct
+    let _ = T::deserialize(FieldNames { names: &mut names });
+    match names {
+        Some(names) => Ok(names.iter().map(|name| name.to_string()).collect()),
+        None => Err(Error::new(
+            format!(
+                "`{}` is not a struct with named fields",
+                std::any::type_name::<T>()
+            ),
+            None,
+        )),
+    }
+}
+
+// Records the fields of the first struct asked for and stops there. Options and newtypes are
+// looked through.
+struct FieldNames<'a> {
+    names: &'a mut Option<&'static [&'static str]>,
+}
+
+impl<'de> serde::Deserializer<'de> for FieldNames<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(Error::new("not a struct", None))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        *self.names = Some(fields);
+        Err(Error::new("field names recorded", None))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{
+        BoolFormat, BytesEncoding, Duplicates, EmptyValue, FloatFormat, KeyFilter, KeyTransform,
+        NestingStyle,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::collections::{BTreeSet, HashMap};
+    use std::sync::Arc;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Pagination {
+        limit: i32,
+        offset: i32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct MyStruct {
+        name: String,
+        age: i32,
+        ids: Array<String>,
+        #[serde(flatten)]
+        pagination: Option<Pagination>,
+    }
+
+    #[test]
+    fn test_serde() {
+        let s = r#"{"name": "test", "age": 37, "ids": "[\"1\", \"2\", \"3\", \"4\", \"5\"]"}"#;
+        println!("{:?}", serde_json::from_str::<MyStruct>(s).unwrap());
+        println!(
+            "{:}",
+            serde_json::to_string(&MyStruct {
+                name: "test".to_string(),
+                age: 37,
+                ids: Array(vec![
+                    "1".to_string(),
+                    "2".to_string(),
+                    "3".to_string(),
+                    "4".to_string(),
+                    "5".to_string()
+                ]),
+                pagination: None,
+            })
+            .unwrap()
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct NormalVec {
+        ids: Vec<String>,
+    }
+
+    #[test]
+    fn test_serde_normal_vec() {
+        let v = NormalVec {
+            ids: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+        };
+        println!("{}", to_string(&v).unwrap());
+    }
+
+    #[test]
+    fn test_serializer() {
+        let s = MyStruct {
+            name: "test".to_string(),
+            age: 37,
+            ids: Array(vec!["1".to_string(), "2".to_string(), "3".to_string()]),
+            pagination: Some(Pagination {
+                limit: 10,
+                offset: 0,
+            }),
+        };
+        println!("{}", to_string(&s).unwrap());
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Empty {
+        a: Option<String>,
+        b: Option<i32>,
+        c: Option<Array<String>>,
+    }
+
+    #[test]
+    fn test_serialize_empty() {
+        let s = Empty {
+            a: None,
+            b: None,
+            c: None,
+        };
+        println!("{}", to_string(&s).unwrap());
+    }
+
+    #[test]
+    fn test_optional_array() {
+        let states = [
+            None,
+            Some(Array(vec![])),
+            Some(Array(vec!["a".to_string()])),
+        ];
+        let written = ["", "c=%5B%5D", "c=%5B%22a%22%5D"];
+        for (c, s) in states.iter().zip(written) {
+            let v = Empty {
+                a: None,
+                b: None,
+                c: c.clone(),
+            };
+            assert_eq!(to_string(&v).unwrap(), s);
+            assert_eq!(from_str::<Empty>(s).unwrap().c, *c);
+        }
+        assert_eq!(from_str::<Empty>("c=").unwrap().c, Some(Array(vec![])));
+
+        // `None` written as `c=` only reads back as `None` if empty values are dropped
+        let opts = SerializerOptions::new().none_as_empty(true);
+        let lenient = ParseOptions::new().empty_value(EmptyValue::AsNone);
+        for c in states {
+            let v = Empty {
+                a: None,
+                b: None,
+                c,
+            };
+            let s = to_string_with(&v, &opts).unwrap();
+            assert_eq!(
+                from_str_with::<Empty>(&s, &lenient).unwrap().c,
+                v.c,
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Lists {
+        ids: CommaSeparated<i64>,
+        names: PipeSeparated<String>,
+        tags: Separated<String, ';'>,
+        words: Separated<String, ' '>,
+    }
+
+    #[test]
+    fn test_separated() {
+        let v = Lists {
+            ids: Separated(vec![1, -2, 3]),
+            names: Separated(vec!["a|b".to_string(), "100%".to_string(), String::new()]),
+            tags: Separated(vec!["x;y".to_string(), "a=b&c".to_string()]),
+            words: Separated(vec!["hello".to_string(), "a+b".to_string()]),
+        };
+        let s = to_string(&v).unwrap();
+        assert_eq!(
+            s,
+            "ids=1,-2,3&names=a%257Cb|100%2525|&tags=x%253By;a%3Db%26c&words=hello%20a%2Bb"
+        );
+        assert_eq!(from_str::<Lists>(&s).unwrap(), v);
+
+        // the separator is encoded where it would separate pairs
+        let opts = SerializerOptions::new().pair_separator(';');
+        let s = to_string_with(&v, &opts).unwrap();
+        assert!(s.contains("tags=x%253By%3Ba%3Db%26c;"), "{}", s);
+        let parse_opts = ParseOptions::new().pair_separator(';');
+        assert_eq!(from_str_with::<Lists>(&s, &parse_opts).unwrap(), v);
+
+        let opts = SerializerOptions::new().compat(CompatMode::SerdeUrlencoded);
+        let s = to_string_with(&v, &opts).unwrap();
+        assert!(s.ends_with("words=hello+a%2Bb"), "{}", s);
+        assert_eq!(from_str::<Lists>(&s).unwrap(), v);
+
+        let v: Lists = from_str("ids=&names=&tags=&words=").unwrap();
+        assert!(v.ids.is_empty() && v.names.is_empty());
+        let err = from_str::<Lists>("ids=1,x&names=&tags=&words=").unwrap_err();
+        assert!(err.to_string().contains("invalid digit"), "{}", err);
+
+        // other formats see the joined string
+        let json = serde_json::to_string(&v.ids).unwrap();
+        assert_eq!(json, r#""""#);
+        let ids: CommaSeparated<u8> = serde_json::from_str(r#""4,5""#).unwrap();
+        assert_eq!(*ids, [4, 5]);
+    }
+
+    #[derive(Serialize)]
+    struct SearchLink<'a> {
+        q: &'a str,
+        ids: &'a CommaSeparated<u32>,
+        filter: &'a Array<String>,
+        cursor: &'a Raw,
+    }
+
+    #[test]
+    fn test_display_from_str() {
+        let ids: CommaSeparated<u32> = "1,2,3".parse().unwrap();
+        let filter: Array<String> = r#"["a b","c"]"#.parse().unwrap();
+        let cursor: Raw = "abc%2Fd".parse().unwrap();
+        let link = SearchLink {
+            q: "rust & serde",
+            ids: &ids,
+            filter: &filter,
+            cursor: &cursor,
+        };
+        let s = to_string(&link).unwrap();
+
+        // a value is displayed as it is written before percent-encoding
+        let map = QueryMap::from_struct(&link).unwrap();
+        assert_eq!(map.get("ids"), Some(ids.to_string().as_str()));
+        assert_eq!(map.get("filter"), Some(filter.to_string().as_str()));
+        assert_eq!(format!("{}", cursor), "abc%2Fd");
+        assert_eq!(s.parse::<QueryMap>().unwrap(), map);
+        // the map remembers which delimiters were left unencoded
+        assert_eq!(map.to_string(), s);
+
+        let paging = Paging {
+            limit: 10,
+            offset: 20,
+        };
+        let map = QueryMap::from_struct(&paging).unwrap();
+        assert_eq!(format!("{}", map), to_string(&paging).unwrap());
+        // keys written unencoded too
+        #[derive(Serialize)]
+        struct Indexed {
+            tags: Vec<&'static str>,
+        }
+        let tags = Indexed {
+            tags: vec!["a b", "c,d"],
+        };
+        let opts = SerializerOptions::new().array_style(ArrayStyle::Indexed);
+        let map = QueryMap::from_struct_with(&tags, &opts).unwrap();
+        assert_eq!(map.to_string(), "tags[0]=a%20b&tags[1]=c%2Cd");
+        assert_eq!(map.to_string(), to_string_with(&tags, &opts).unwrap());
+
+        // pairs written with other separators are split at them
+        let opts = SerializerOptions::new()
+            .pair_separator(';')
+            .kv_separator(':');
+        assert_eq!(
+            to_string_with(&paging, &opts).unwrap(),
+            "limit:10;offset:20"
+        );
+        let map = QueryMap::from_struct_with(&paging, &opts).unwrap();
+        assert_eq!(map.get("limit"), Some("10"));
+        assert_eq!(map.get("offset"), Some("20"));
+        let map = QueryMap::from_struct_with(&tags, &opts).unwrap();
+        assert_eq!(map.get_all("tags"), ["a b", "c,d"]);
+
+        let tags: Separated<String, ';'> = Separated(vec!["a;b".to_string(), "50%".to_string()]);
+        assert_eq!(tags.to_string(), "a%3Bb;50%25");
+        assert_eq!(
+            tags.to_string().parse::<Separated<String, ';'>>().unwrap(),
+            tags
+        );
+        assert!("1,x".parse::<CommaSeparated<u32>>().is_err());
+        assert!("[1".parse::<Array<u32>>().is_err());
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct De {
+        name: String,
+        age: i32,
+        #[serde(flatten)]
+        pagination: Pagination,
+        ids: Vec<i32>,
+        hobbies: Option<Vec<String>>,
+        op: Option<String>,
+    }
+
+    #[test]
+    fn test_deserializer() {
+        let mut deserializer = Deserializer::try_from_str(
+            "age=37&name=test&offset=0&limit=10&ids=1&ids=2&op=some&hobbies=moto&hobbies=code",
+        )
+        .unwrap();
+        let de = De::deserialize(&mut deserializer).unwrap();
+        assert!(de.name == "test");
+        assert!(de.age == 37);
+        assert!(
+            de.pagination
+                == Pagination {
+                    limit: 10,
+                    offset: 0
+                }
+        );
+        assert!(de.ids == vec![1, 2]);
+        assert!(de.op == Some("some".into()));
+        assert!(de.hobbies == Some(vec!["moto".into(), "code".into()]));
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Se {
+        name: String,
+        age: i32,
+        #[serde(flatten)]
+        pagination: Pagination,
+        ids: Vec<i32>,
+        hobbies: Option<Vec<String>>,
+        op: Option<String>,
+    }
+
+    #[test]
+    fn test_to_string() {
+        let se = Se {
+            name: "test".into(),
+            age: 37,
+            pagination: Pagination {
+                limit: 10,
+                offset: 0,
+            },
+            ids: vec![1, 2],
+            hobbies: Some(vec!["moto".into(), "code".into()]),
+            op: Some("some".into()),
+        };
+        let s = to_string(&se).unwrap();
+        assert!(
+            s == "name=test&age=37&limit=10&offset=0&ids=1&ids=2&hobbies=moto&hobbies=code&op=some"
+        )
+    }
+
+    #[test]
+    fn test_serialize_vector() {
+        #[derive(Debug, Serialize)]
+        pub struct Vector {
+            ids: Vec<String>,
+        }
+
+        let v = Vector {
+            ids: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+        };
+        let s = to_string(&v).unwrap();
+        assert!(s == "ids=1&ids=2&ids=3");
+    }
+
+    #[test]
+    fn test_to_string_sorted() {
+        let se = Se {
+            name: "test".into(),
+            age: 37,
+            pagination: Pagination {
+                limit: 10,
+                offset: 0,
+            },
+            ids: vec![2, 1],
+            hobbies: Some(vec!["moto".into(), "code".into()]),
+            op: Some("some".into()),
+        };
+        let s = to_string_sorted(&se).unwrap();
+        assert_eq!(
+            s,
+            "age=37&hobbies=moto&hobbies=code&ids=2&ids=1&limit=10&name=test&offset=0&op=some"
+        );
+    }
+
+    #[test]
+    fn test_to_string_sorted_hash_map() {
+        #[derive(Debug, Serialize)]
+        struct WithMap {
+            z: i32,
+            #[serde(flatten)]
+            extra: HashMap<String, String>,
+        }
+
+        let v = WithMap {
+            z: 1,
+            extra: (0..10)
+                .map(|i| (format!("k{}", i), i.to_string()))
+                .collect(),
+        };
+        let s = to_string_sorted(&v).unwrap();
+        assert_eq!(s, "k0=0&k1=1&k2=2&k3=3&k4=4&k5=5&k6=6&k7=7&k8=8&k9=9&z=1");
+    }
+
+    #[derive(Debug, Serialize)]
+    struct Scores {
+        name: String,
+        scores: HashMap<String, Vec<i32>>,
+    }
+
+    #[test]
+    fn test_serialize_hash_map_of_vec() {
+        let v = Scores {
+            name: "a".into(),
+            scores: HashMap::from([
+                ("math".to_string(), vec![1, 2]),
+                ("art".to_string(), vec![3]),
+            ]),
+        };
+        let mut serializer = Serializer::with_options(
+            SerializerOptions::new()
+                .sort_map_entries(true)
+                .nesting_style(NestingStyle::Brackets),
+        );
+        v.serialize(&mut serializer).unwrap();
+        assert_eq!(
+            serializer.output(),
+            "name=a&scores[art]=3&scores[math]=1&scores[math]=2"
+        );
+
+        let m: HashMap<String, Vec<i32>> = from_str(&to_string(&v.scores).unwrap()).unwrap();
+        assert_eq!(m, v.scores);
+    }
+
+    #[test]
+    fn test_btree_map_round_trip() {
+        #[derive(Debug, Serialize)]
+        struct Labels {
+            #[serde(flatten)]
+            labels: std::collections::BTreeMap<String, String>,
+        }
+
+        let v = Labels {
+            labels: [("env", "prod"), ("app", "api"), ("tier", "web")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        let s = to_string(&v).unwrap();
+        assert_eq!(s, "app=api&env=prod&tier=web");
+        let m: HashMap<String, String> = from_str(&s).unwrap();
+        assert_eq!(m.len(), 3);
+        assert_eq!(m["env"], "prod");
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Overrides {
+        page: u32,
+        m: HashMap<String, String>,
+    }
+
+    #[test]
+    fn test_serialize_map_field() {
+        let v = Overrides {
+            page: 1,
+            m: HashMap::from([("page".to_string(), "9".to_string())]),
+        };
+        // a map is nested under its field like a struct
+        let opts = SerializerOptions::new().nesting_style(NestingStyle::Brackets);
+        let s = to_string_with(&v, &opts).unwrap();
+        assert_eq!(s, "page=1&m[page]=9");
+        assert_eq!(from_str::<Overrides>(&s).unwrap(), v);
+        let opts = SerializerOptions::new().nesting_style(NestingStyle::Separator('.'));
+        assert_eq!(to_string_with(&v, &opts).unwrap(), "page=1&m.page=9");
+
+        let err = to_string(&v).unwrap_err();
+        assert_eq!(
+            err.message,
+            "cannot serialize nested map for key `m`, use #[serde(flatten)] instead"
+        );
+    }
+
+    #[test]
+    fn test_serialize_map_non_scalar_key() {
+        let m = std::collections::BTreeMap::from([((1, 2), "a".to_string())]);
+        let err = to_string(&m).unwrap_err();
+        assert_eq!(err.message, "map keys must be scalar, got tuple");
+        let m = std::collections::BTreeMap::from([(None::<u32>, 1)]);
+        let err = to_string(&m).unwrap_err();
+        assert_eq!(err.message, "map keys must be scalar, got none");
+        let m = std::collections::BTreeMap::from([(vec![1], 1), (vec![2], 2)]);
+        let err = to_string(&m).unwrap_err();
+        assert_eq!(err.message, "map keys must be scalar, got seq");
+    }
+
+    #[test]
+    fn test_map_scalar_keys() {
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+        enum Env {
+            Prod,
+            Dev,
+        }
+
+        let m = std::collections::BTreeMap::from([(10u32, "foo"), (11, "bar")]);
+        assert_eq!(to_string(&m).unwrap(), "10=foo&11=bar");
+        let m = std::collections::BTreeMap::from([(-1i64, true), (2, false)]);
+        assert_eq!(to_string(&m).unwrap(), "-1=true&2=false");
+        let m = std::collections::BTreeMap::from([(Env::Prod, 1), (Env::Dev, 2)]);
+        let s = to_string(&m).unwrap();
+        assert_eq!(s, "Prod=1&Dev=2");
+        let back: std::collections::BTreeMap<Env, u8> = from_str(&s).unwrap();
+        assert_eq!(back[&Env::Dev], 2);
+
+        let err = from_str::<HashMap<u32, String>>("10=foo&x=bar").unwrap_err();
+        assert_eq!(err.message, "invalid key `x`, expected u32");
+        let err = from_str::<HashMap<char, String>>("ab=1").unwrap_err();
+        assert_eq!(err.message, "invalid key `ab`, expected a character");
+    }
+
+    #[test]
+    fn test_serialize_nested_struct() {
+        #[derive(Debug, Serialize)]
+        struct Outer {
+            limit: i32,
+            filter: Pagination,
+        }
+
+        let v = Outer {
+            limit: 25,
+            filter: Pagination {
+                limit: 10,
+                offset: 0,
+            },
+        };
+        let err = to_string(&v).unwrap_err();
+        assert_eq!(
+            err.message,
+            "cannot serialize nested struct `Pagination` for key `filter`, use #[serde(flatten)] instead"
+        );
+    }
+
+    #[derive(Debug, Serialize)]
+    struct Page {
+        limit: i32,
+    }
+
+    #[derive(Debug, Serialize)]
+    struct Colliding {
+        #[serde(flatten)]
+        pagination: Pagination,
+        #[serde(flatten)]
+        page: Page,
+    }
+
+    #[test]
+    fn test_serialize_duplicate_keys() {
+        let v = Colliding {
+            pagination: Pagination {
+                limit: 10,
+                offset: 0,
+            },
+            page: Page { limit: 25 },
+        };
+        let err = to_string(&v).unwrap_err();
+        assert_eq!(
+            err.message,
+            "duplicate key `limit` produced by serialization"
+        );
+
+        let mut serializer =
+            Serializer::with_options(SerializerOptions::new().allow_duplicate_keys(true));
+        v.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.output(), "limit=10&offset=0&limit=25");
+    }
+
+    // A map keeping repeated keys, e.g. a multimap of overrides.
+    struct Entries<V>(Vec<(&'static str, V)>);
+
+    impl<V: Serialize> Serialize for Entries<V> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+        }
+    }
+
+    #[test]
+    fn test_map_duplicate_keys() {
+        let v = Entries(vec![
+            ("sort", vec!["name", "age"]),
+            ("q", vec!["a"]),
+            ("sort", vec!["date"]),
+        ]);
+        let with = |policy, allow| {
+            let opts = SerializerOptions::new()
+                .map_duplicate_keys(policy)
+                .allow_duplicate_keys(allow);
+            to_string_with(&v, &opts)
+        };
+        assert!(with(MapDuplicateKeys::Allow, false).is_err());
+        assert_eq!(
+            with(MapDuplicateKeys::Allow, true).unwrap(),
+            "sort=name&sort=age&q=a&sort=date"
+        );
+        assert_eq!(
+            with(MapDuplicateKeys::LastWins, false).unwrap(),
+            "q=a&sort=date"
+        );
+        assert_eq!(
+            with(MapDuplicateKeys::Error, true).unwrap_err().message,
+            "duplicate key `sort` produced by serialization"
+        );
+
+        let opts = SerializerOptions::new()
+            .map_duplicate_keys(MapDuplicateKeys::LastWins)
+            .array_style(ArrayStyle::Indexed)
+            .sort_map_entries(true);
+        let v = Entries(vec![("b", vec![1, 2]), ("a", vec![3]), ("b", vec![4])]);
+        assert_eq!(to_string_with(&v, &opts).unwrap(), "a[0]=3&b[0]=4");
+
+        let v = Colliding {
+            pagination: Pagination {
+                limit: 10,
+                offset: 0,
+            },
+            page: Page { limit: 25 },
+        };
+        let opts = SerializerOptions::new().map_duplicate_keys(MapDuplicateKeys::LastWins);
+        assert_eq!(to_string_with(&v, &opts).unwrap(), "offset=0&limit=25");
+        let m = QueryMap::from_struct_with(&v, &opts).unwrap();
+        assert_eq!(m.get_all("limit"), ["25"]);
+    }
+
+    #[test]
+    fn test_serialize_vec_is_not_duplicate() {
+        #[derive(Debug, Serialize)]
+        struct Tagged {
+            #[serde(flatten)]
+            page: Page,
+            tags: Vec<String>,
+        }
+
+        let v = Tagged {
+            page: Page { limit: 5 },
+            tags: vec!["a".into(), "b".into(), "c".into()],
+        };
+        assert_eq!(to_string(&v).unwrap(), "limit=5&tags=a&tags=b&tags=c");
+    }
+
+    #[test]
+    fn test_to_string_with_options() {
+        let v = Empty {
+            a: Some("x".into()),
+            b: None,
+            c: None,
+        };
+        let opts = SerializerOptions::new().none_as_empty(true).sort_keys(true);
+        assert_eq!(to_string_with(&v, &opts).unwrap(), "a=x&b=&c=");
+
+        let v = Vector {
+            name: "n".into(),
+            ids: vec![1, 2, 3],
+        };
+        let opts = SerializerOptions::new().array_style(ArrayStyle::Repeat);
+        assert_eq!(
+            to_string_with(&v, &opts).unwrap(),
+            "ids=1&ids=2&ids=3&name=n"
+        );
+        let opts = opts.array_style(ArrayStyle::Brackets);
+        assert_eq!(
+            to_string_with(&v, &opts).unwrap(),
+            "ids[]=1&ids[]=2&ids[]=3&name=n"
+        );
+        let opts = opts.array_style(ArrayStyle::Comma).sort_keys(true);
+        assert_eq!(to_string_with(&v, &opts).unwrap(), "ids=1,2,3&name=n");
+    }
+
+    #[derive(Debug, Serialize)]
+    struct Vector {
+        ids: Vec<i32>,
+        name: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Toggles {
+        active: bool,
+        archived: Option<bool>,
+        flags: Vec<bool>,
+    }
+
+    #[test]
+    fn test_bool_format() {
+        let v = Toggles {
+            active: true,
+            archived: Some(false),
+            flags: vec![false, true],
+        };
+        let lenient = ParseOptions::new().lenient_bool(true);
+        for (format, s) in [
+            (
+                BoolFormat::TrueFalse,
+                "active=true&archived=false&flags=false&flags=true",
+            ),
+            (BoolFormat::OneZero, "active=1&archived=0&flags=0&flags=1"),
+            (
+                BoolFormat::YesNo,
+                "active=yes&archived=no&flags=no&flags=yes",
+            ),
+            (
+                BoolFormat::Custom {
+                    true_str: "Y".to_string(),
+                    false_str: "N".to_string(),
+                },
+                "active=Y&archived=N&flags=N&flags=Y",
+            ),
+        ] {
+            let opts = SerializerOptions::new().bool_format(format);
+            assert_eq!(to_string_with(&v, &opts).unwrap(), s);
+            assert_eq!(from_str_with::<Toggles>(s, &lenient).unwrap(), v);
+        }
+
+        let opts = SerializerOptions::new().bool_format(BoolFormat::Custom {
+            true_str: "on duty".to_string(),
+            false_str: "off".to_string(),
+        });
+        assert_eq!(
+            to_string_with(&v, &opts).unwrap(),
+            "active=on%20duty&archived=off&flags=off&flags=on%20duty"
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Amounts {
+        count: Option<i32>,
+        total: Option<u64>,
+        price: Option<f64>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    #[allow(dead_code)]
+    struct Gateway {
+        query: Option<String>,
+        #[serde(alias = "size", alias = "limit")]
+        page_size: Option<u32>,
+        #[serde(rename = "sort-by")]
+        sort_by: Vec<String>,
+        #[serde(skip_deserializing)]
+        computed: u32,
+        filter: Option<Pagination>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct GatewayQuery(Gateway);
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct GatewayPage {
+        q: String,
+        #[serde(flatten)]
+        page: Pagination,
+    }
+
+    #[test]
+    fn test_field_names() {
+        // aliases in the order serde lists them
+        let expected = ["query", "limit", "pageSize", "size", "sort-by", "filter"];
+        assert_eq!(field_names::<Gateway>().unwrap(), expected);
+        assert_eq!(field_names::<Option<Gateway>>().unwrap(), expected);
+        assert_eq!(field_names::<GatewayQuery>().unwrap(), expected);
+        assert_eq!(field_names::<Pagination>().unwrap(), ["limit", "offset"]);
+
+        let err = field_names::<GatewayPage>().unwrap_err();
+        assert!(err
+            .message
+            .ends_with("GatewayPage` is not a struct with named fields"));
+        assert!(field_names::<HashMap<String, String>>().is_err());
+        assert!(field_names::<u32>().is_err());
+    }
+
+    #[test]
+    fn test_lenient_numbers() {
+        let strict = ParseOptions::new();
+        let plus = ParseOptions::new().lenient_numbers(true);
+        let underscores = ParseOptions::new().number_underscores(true);
+        let both = plus.clone().number_underscores(true);
+        // the value read under each of the options above, `None` for an error
+        for (value, expected) in [
+            ("5", [Some(5.0); 4]),
+            ("%2B5", [Some(5.0); 4]),
+            ("+5", [None, Some(5.0), None, Some(5.0)]),
+            ("1_000", [None, None, Some(1000.0), Some(1000.0)]),
+            ("+1_000", [None, None, None, Some(1000.0)]),
+            ("%2B1_0", [None, None, Some(10.0), Some(10.0)]),
+            ("-1_5", [None, None, Some(-15.0), Some(-15.0)]),
+            ("+-5", [None; 4]),
+            ("%2B%2B5", [None; 4]),
+            ("+", [None; 4]),
+            ("_", [None; 4]),
+            ("+_", [None; 4]),
+        ] {
+            for (opts, expected) in [&strict, &plus, &underscores, &both]
+                .into_iter()
+                .zip(expected)
+            {
+                let count = from_str_with::<Amounts>(&format!("count={}", value), opts);
+                let total = from_str_with::<Amounts>(&format!("total={}", value), opts);
+                let price = from_str_with::<Amounts>(&format!("price={}", value), opts);
+                assert_eq!(
+                    count.ok().and_then(|v| v.count),
+                    expected.map(|n| n as i32),
+                    "{} {:?}",
+                    value,
+                    opts
+                );
+                assert_eq!(
+                    total.ok().and_then(|v| v.total),
+                    expected.filter(|n| *n >= 0.0).map(|n| n as u64),
+                    "{} {:?}",
+                    value,
+                    opts
+                );
+                assert_eq!(
+                    price.ok().and_then(|v| v.price),
+                    expected,
+                    "{} {:?}",
+                    value,
+                    opts
+                );
+            }
+        }
+
+        // errors show the value as it was given
+        let err = from_str_with::<Amounts>("count=%2B1_x", &both).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidNumber);
+        assert_eq!(
+            err.message,
+            "invalid value `+1_x` for key `count`, expected i32"
+        );
+        let err = from_str_with::<Amounts>("price=_", &both).unwrap_err();
+        assert_eq!(
+            err.message,
+            "invalid value `_` for key `price`, expected f64"
+        );
+        let err =
+            from_str_with::<Amounts>("total=9_999_999_999_999_999_999_999", &both).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutOfRange);
+    }
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Coords {
+        lat: f64,
+        scale: f32,
+    }
+
+    #[test]
+    fn test_float_format() {
+        let cases: &[(f64, f32, FloatFormat, &str)] = &[
+            (3.0, 0.5, FloatFormat::Decimal, "lat=3&scale=0.5"),
+            (-0.0, 0.1 + 0.2, FloatFormat::Decimal, "lat=-0&scale=0.3"),
+            (
+                1e-7,
+                1e10,
+                FloatFormat::Decimal,
+                "lat=0.0000001&scale=10000000000",
+            ),
+            (3.0, 0.5, FloatFormat::Fixed(2), "lat=3.00&scale=0.50"),
+            (-0.0, 2.0, FloatFormat::Fixed(0), "lat=-0&scale=2"),
+            (0.125, 0.375, FloatFormat::Fixed(2), "lat=0.12&scale=0.38"),
+            (
+                2.675,
+                1e10,
+                FloatFormat::Fixed(1),
+                "lat=2.7&scale=10000000000.0",
+            ),
+        ];
+        for (lat, scale, format, s) in cases {
+            let v = Coords {
+                lat: *lat,
+                scale: *scale,
+            };
+            let opts = SerializerOptions::new().float_format(*format);
+            assert_eq!(to_string_with(&v, &opts).unwrap(), *s);
+            if *format == FloatFormat::Decimal {
+                assert_eq!(from_str::<Coords>(s).unwrap(), v);
+            }
+        }
+
+        let v = Coords {
+            lat: 1e300,
+            scale: -1.5,
+        };
+        let opts = SerializerOptions::new().float_format(FloatFormat::Fixed(1));
+        let s = to_string_with(&v, &opts).unwrap();
+        assert_eq!(from_str::<Coords>(&s).unwrap(), v);
+        assert_eq!(s.len(), "lat=&scale=-1.5".len() + 301 + 2);
+    }
+
+    #[cfg(feature = "ryu")]
+    #[test]
+    fn test_float_format_ryu() {
+        let opts = SerializerOptions::new().float_format(FloatFormat::Ryu);
+        for (lat, scale, s) in [
+            (3.0, 0.5, "lat=3.0&scale=0.5"),
+            (-0.0, 2.0, "lat=-0.0&scale=2.0"),
+            (1e16, 1e15, "lat=1e16&scale=1e15"),
+            (1e-6, 1e-5, "lat=1e-6&scale=0.00001"),
+            (0.1 + 0.2, 0.1 + 0.2, "lat=0.30000000000000004&scale=0.3"),
+        ] {
+            let v = Coords { lat, scale };
+            assert_eq!(to_string_with(&v, &opts).unwrap(), s);
+            assert_eq!(from_str::<Coords>(s).unwrap(), v);
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Upload {
+        data: serde_bytes::ByteBuf,
+    }
+
+    #[test]
+    fn test_bytes_encoding() {
+        let v = Upload {
+            data: serde_bytes::ByteBuf::from(vec![0xfb, 0xff, 0x3e]),
+        };
+        for (encoding, s) in [
+            (BytesEncoding::Base64UrlNoPad, "data=-_8-"),
+            (BytesEncoding::Base64Standard, "data=%2B%2F8%2B"),
+            (BytesEncoding::Hex, "data=fbff3e"),
+        ] {
+            let opts = SerializerOptions::new().bytes_encoding(encoding);
+            assert_eq!(to_string_with(&v, &opts).unwrap(), s);
+            let opts = ParseOptions::new().bytes_encoding(encoding);
+            assert_eq!(from_str_with::<Upload>(s, &opts).unwrap(), v);
+        }
+        // base64 is read in either alphabet, with or without padding
+        let v: Upload = from_str("data=%2B%2F8%3D").unwrap();
+        assert_eq!(v.data.as_slice(), [0xfb, 0xff]);
+        let v: Upload = from_str("data=-_8").unwrap();
+        assert_eq!(v.data.as_slice(), [0xfb, 0xff]);
+
+        let err = from_str::<Upload>("data=a*b").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::InvalidValue {
+                key: "data".to_string(),
+                expected: "base64 bytes".to_string(),
+            }
+        );
+        let opts = ParseOptions::new().bytes_encoding(BytesEncoding::Hex);
+        let err = from_str_with::<Upload>("data=abc", &opts).unwrap_err();
+        assert_eq!(
+            err.message,
+            "invalid value for key `data`, expected hex bytes"
+        );
+    }
+
+    #[derive(Debug, Serialize)]
+    struct Separator {
+        sep: char,
+    }
+
+    #[test]
+    fn test_serialize_char() {
+        for (c, s) in [
+            ('a', "sep=a"),
+            ('-', "sep=-"),
+            ('&', "sep=%26"),
+            ('=', "sep=%3D"),
+            ('+', "sep=%2B"),
+            ('%', "sep=%25"),
+            ('#', "sep=%23"),
+            (' ', "sep=%20"),
+            ('ß', "sep=%C3%9F"),
+            ('🎉', "sep=%F0%9F%8E%89"),
+        ] {
+            assert_eq!(to_string(&Separator { sep: c }).unwrap(), s);
+        }
+    }
+
+    #[test]
+    fn test_control_chars() {
+        for (value, encoded) in [
+            ("a\nb", "a%0Ab"),
+            ("a\r\nb", "a%0D%0Ab"),
+            ("\0", "%00"),
+            ("\u{7f}", "%7F"),
+        ] {
+            let v = Separator {
+                sep: value.chars().find(|c| c.is_control()).unwrap(),
+            };
+            let m = HashMap::from([(value, value)]);
+            assert_eq!(to_string(&m).unwrap(), format!("{}={}", encoded, encoded));
+
+            let opts = SerializerOptions::new().reject_control_chars(true);
+            let err = to_string_with(&HashMap::from([("k", value)]), &opts).unwrap_err();
+            assert!(err.message.ends_with("in value of key `k`"), "{}", err);
+            let err = to_string_with(&HashMap::from([(value, "v")]), &opts).unwrap_err();
+            assert!(err.message.ends_with("in key"), "{}", err);
+            assert!(to_string_with(&v, &opts).is_err());
+        }
+        let opts = SerializerOptions::new().reject_control_chars(true);
+        let err = to_string_with(&HashMap::from([("k", "a\r\nb")]), &opts).unwrap_err();
+        assert_eq!(err.message, "control character '\\r' in value of key `k`");
+        assert_eq!(
+            to_string_with(&HashMap::from([("k", "tab-free ü")]), &opts).unwrap(),
+            "k=tab-free%20%C3%BC"
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Node {
+        value: Option<u32>,
+        child: Option<Box<Node>>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Tree {
+        value: Option<u32>,
+        #[serde(default)]
+        children: Vec<Tree>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Matrix {
+        rows: Vec<Vec<u32>>,
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let nested = |levels: usize| {
+            let mut key = String::from("child");
+            for _ in 1..levels {
+                key.push_str("[child]");
+            }
+            format!("{}[value]=1", key)
+        };
+        // the top level struct and 31 children
+        let v: Node = from_str(&nested(31)).unwrap();
+        let mut node = &v;
+        let mut levels = 0;
+        while let Some(child) = &node.child {
+            node = child;
+            levels += 1;
+        }
+        assert_eq!((levels, node.value), (31, Some(1)));
+
+        for s in [nested(32), nested(10_000)] {
+            let err = from_str::<Node>(&s).unwrap_err();
+            assert_eq!(
+                err.kind,
+                ErrorKind::LimitExceeded {
+                    key: None,
+                    limit: 32,
+                    kind: LimitKind::Depth,
+                }
+            );
+            assert_eq!(err.message, "too deeply nested, at most 32 levels allowed");
+        }
+        let opts = ParseOptions::new().max_depth(3);
+        assert!(from_str_with::<Node>(&nested(2), &opts).is_ok());
+        assert!(from_str_with::<Node>(&nested(3), &opts).is_err());
+
+        // indexed sequences add a level per element
+        let deep_tree = |levels: usize| {
+            let mut key = String::from("children[0]");
+            for _ in 1..levels {
+                key.push_str("[children][0]");
+            }
+            format!("{}[value]=1", key)
+        };
+        let v: Tree = from_str(&deep_tree(15)).unwrap();
+        assert_eq!(v.children.len(), 1);
+        let err = from_str::<Tree>(&deep_tree(10_000)).unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::LimitExceeded {
+                kind: LimitKind::Depth,
+                ..
+            }
+        ));
+
+        // sequences of sequences are bounded by the type
+        let s = (0..10_000)
+            .map(|i| format!("rows={}", i))
+            .collect::<Vec<_>>()
+            .join("&");
+        let v: Matrix = from_str(&s).unwrap();
+        assert_eq!(v.rows.len(), 10_000);
+        assert_eq!(v.rows[9_999], vec![9_999]);
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Flags {
+        active: bool,
+        name: Option<String>,
+        ids: Vec<i32>,
+    }
+
+    #[test]
+    fn test_from_str_with_options() {
+        let opts = ParseOptions::new()
+            .lenient_bool(true)
+            .trim_values(true)
+            .empty_value(EmptyValue::AsNone)
+            .separators(&['&', ';']);
+        let v: Flags = from_str_with("active= yes ;name=&ids=1;ids=2", &opts).unwrap();
+        assert_eq!(
+            v,
+            Flags {
+                active: true,
+                name: None,
+                ids: vec![1, 2],
+            }
+        );
+
+        let opts = ParseOptions::new()
+            .duplicates(Duplicates::Last)
+            .deny_unknown(true);
+        let v: Flags = from_str_with("active=true&ids=1&ids=2", &opts).unwrap();
+        assert_eq!(v.ids, vec![2]);
+        let err = from_str_with::<Flags>("active=true&ids=1&page=1&debug=1", &opts).unwrap_err();
+        assert_eq!(err.message, "unknown keys: debug, page");
+        assert_eq!(err.key(), Some("debug"));
+
+        let opts = ParseOptions::new()
+            .duplicates(Duplicates::Error)
+            .max_pairs(2);
+        let err = from_str_with::<Flags>("active=true&active=false", &opts).unwrap_err();
+        assert_eq!(err.message, "duplicate key `active`");
+        let err = from_str_with::<Flags>("active=true&ids=1&ids=2", &opts).unwrap_err();
+        assert_eq!(err.message, "too many pairs, at most 2 allowed");
+    }
+
+    #[test]
+    fn test_from_str_limits() {
+        let opts = ParseOptions::new()
+            .max_input_len(23)
+            .max_pairs(3)
+            .max_value_len(4);
+        let v: Flags = from_str_with("active=true&ids=1&ids=2", &opts).unwrap();
+        assert_eq!(v.ids, vec![1, 2]);
+
+        let err = from_str_with::<Flags>("active=true&ids=1&ids=22", &opts).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::LimitExceeded {
+                key: None,
+                limit: 23,
+                kind: LimitKind::InputLength,
+            }
+        );
+        assert_eq!(err.message, "input too long, at most 23 bytes allowed");
+
+        let err = from_str_with::<Flags>("ids=1&ids=2&ids=3&ids=4", &opts).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::LimitExceeded {
+                key: None,
+                limit: 3,
+                kind: LimitKind::Pairs,
+            }
+        );
+        assert_eq!(err.message, "too many pairs, at most 3 allowed");
+
+        let err = from_str_with::<Flags>("active=false&ids=1", &opts).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::LimitExceeded {
+                key: Some("active".to_string()),
+                limit: 4,
+                kind: LimitKind::ValueLength,
+            }
+        );
+        assert_eq!(
+            err.message,
+            "value of key `active` too long, at most 4 bytes allowed"
+        );
+
+        let opts = ParseOptions::new().max_values_per_key(2);
+        let v: Flags = from_str_with("ids=1&active=true&ids=2", &opts).unwrap();
+        assert_eq!(v.ids, vec![1, 2]);
+        let err = from_str_with::<Flags>("ids=1&active=true&ids=2&ids=3", &opts).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::LimitExceeded {
+                key: Some("ids".to_string()),
+                limit: 2,
+                kind: LimitKind::ValuesPerKey,
+            }
+        );
+        assert_eq!(err.key(), Some("ids"));
+        assert_eq!(
+            err.message,
+            "too many values for key `ids`, at most 2 allowed"
+        );
+        let opts = opts.duplicates(Duplicates::Last);
+        let v: Flags = from_str_with("active=true&ids=1&ids=2&ids=3", &opts).unwrap();
+        assert_eq!(v.ids, vec![3]);
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Listing {
+        page_size: i32,
+        sort_by: String,
+    }
+
+    #[test]
+    fn test_key_transform_round_trip() {
+        let v = Listing {
+            page_size: 10,
+            sort_by: "name".into(),
+        };
+        let camel = SerializerOptions::new().key_transform(KeyTransform::CamelCase);
+        let s = to_string_with(&v, &camel).unwrap();
+        assert_eq!(s, "pageSize=10&sortBy=name");
+        let snake = ParseOptions::new().key_transform(KeyTransform::SnakeCase);
+        assert_eq!(from_str_with::<Listing>(&s, &snake).unwrap(), v);
+        assert_eq!(
+            from_str_with::<Listing>("page-size=10&sort-by=name", &snake).unwrap(),
+            v
+        );
+
+        let upper = SerializerOptions::new()
+            .key_transform(KeyTransform::Custom(Arc::new(|k| k.to_uppercase())));
+        let s = to_string_with(&v, &upper).unwrap();
+        assert_eq!(s, "PAGE_SIZE=10&SORT_BY=name");
+        let lower = ParseOptions::new().key_transform(KeyTransform::Lowercase);
+        assert_eq!(from_str_with::<Listing>(&s, &lower).unwrap(), v);
+    }
+
+    #[test]
+    fn test_value_decoder() {
+        let opts = ParseOptions::new()
+            .key_transform(KeyTransform::SnakeCase)
+            .value_decoder("sort_by", Box::new(|v| Ok(v.to_lowercase())))
+            .value_decoder(
+                "cursor",
+                Box::new(|v| {
+                    if v.len() > 8 {
+                        return Err(Error::with_kind(
+                            ErrorKind::Custom,
+                            "longer than 8 bytes",
+                            None,
+                        ));
+                    }
+                    Ok(v.to_string())
+                }),
+            );
+        let v: Listing = from_str_with("pageSize=10&sortBy=Name%20DESC", &opts).unwrap();
+        assert_eq!(v.sort_by, "name desc");
+        assert_eq!(v.page_size, 10);
+
+        let m: HashMap<String, String> = from_str_with("cursor=abc&other=ABC", &opts).unwrap();
+        assert_eq!(m["cursor"], "abc");
+        assert_eq!(m["other"], "ABC");
+        let err = from_str_with::<HashMap<String, String>>("cursor=abcdefghi", &opts).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Custom);
+        assert_eq!(
+            err.message,
+            "invalid value for key `cursor`: longer than 8 bytes"
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Search {
+        #[serde(alias = "q", alias = "search")]
+        query: String,
+        page: Option<i32>,
+    }
+
+    #[test]
+    fn test_deserialize_alias() {
+        let expected = Search {
+            query: "foo".into(),
+            page: None,
+        };
+        assert_eq!(from_str::<Search>("query=foo").unwrap(), expected);
+        assert_eq!(from_str::<Search>("q=foo").unwrap(), expected);
+        assert_eq!(from_str::<Search>("search=foo").unwrap(), expected);
+
+        // the name appearing first in the input wins, the others are consumed
+        let opts = ParseOptions::new().deny_unknown(true);
+        let v: Search = from_str_with("q=foo&page=2&query=bar", &opts).unwrap();
+        assert_eq!(v.query, "foo");
+        assert_eq!(v.page, Some(2));
+        let v: Search = from_str_with("query=bar&q=foo", &opts).unwrap();
+        assert_eq!(v.query, "bar");
+    }
+
+    /// Records map entries in the order they are visited.
+    #[derive(Debug, PartialEq)]
+    struct Visited(Vec<(String, Vec<String>)>);
+
+    impl<'de> Deserialize<'de> for Visited {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct VisitedVisitor;
+
+            impl<'de> Visitor<'de> for VisitedVisitor {
+                type Value = Visited;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    write!(f, "a map")
+                }
+
+                fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+                where
+                    A: MapAccess<'de>,
+                {
+                    let mut entries = Vec::new();
+                    while let Some(entry) = map.next_entry()? {
+                        entries.push(entry);
+                    }
+                    Ok(Visited(entries))
+                }
+            }
+
+            deserializer.deserialize_map(VisitedVisitor)
+        }
+    }
+
+    #[test]
+    fn test_deserialize_map_keeps_input_order() {
+        let v: Visited = from_str("z=1&b=2&z=3&a=4&m=5&b=6").unwrap();
+        assert_eq!(
+            v,
+            Visited(vec![
+                ("z".into(), vec!["1".into(), "3".into()]),
+                ("b".into(), vec!["2".into(), "6".into()]),
+                ("a".into(), vec!["4".into()]),
+                ("m".into(), vec!["5".into()]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_from_str_partial() {
+        let (p, rest) =
+            from_str_partial::<Pagination>("trace=1&limit=10&q=rust&offset=20&tag=a&tag=b")
+                .unwrap();
+        assert_eq!(
+            p,
+            Pagination {
+                limit: 10,
+                offset: 20
+            }
+        );
+        assert_eq!(
+            rest,
+            vec![
+                ("trace".to_string(), "1".to_string()),
+                ("q".to_string(), "rust".to_string()),
+                ("tag".to_string(), "a".to_string()),
+                ("tag".to_string(), "b".to_string()),
+            ]
+        );
+    }
+
+    // Records the keys in the order they are visited.
+    struct KeyOrder {
+        fields: &'static [&'static str],
+        entries: bool,
+    }
+
+    impl<'de> Visitor<'de> for KeyOrder {
+        type Value = Vec<String>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a map")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut keys = Vec::new();
+            if self.entries {
+                while let Some((k, _)) = map.next_entry::<String, Option<String>>()? {
+                    keys.push(k);
+                }
+            } else {
+                while let Some(k) = map.next_key::<String>()? {
+                    map.next_value::<Option<String>>()?;
+                    keys.push(k);
+                }
+            }
+            Ok(keys)
+        }
+    }
+
+    impl<'de> serde::de::DeserializeSeed<'de> for KeyOrder {
+        type Value = Vec<String>;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            match self.fields {
+                [] => deserializer.deserialize_map(self),
+                fields => deserializer.deserialize_struct("KeyOrder", fields, self),
+            }
+        }
+    }
+
+    #[test]
+    fn test_key_order() {
+        use serde::de::DeserializeSeed;
+
+        for entries in [false, true] {
+            let mut de = Deserializer::try_from_str("b=1&z=2&a=3").unwrap();
+            let fields = &["z", "missing", "a", "b"];
+            let keys = KeyOrder { fields, entries }.deserialize(&mut de).unwrap();
+            assert_eq!(keys, ["z", "a", "b"]);
+
+            let mut de = Deserializer::try_from_str("b=1&z=2&a=3&b=4").unwrap();
+            let keys = KeyOrder {
+                fields: &[],
+                entries,
+            }
+            .deserialize(&mut de)
+            .unwrap();
+            assert_eq!(keys, ["b", "z", "a"]);
+
+            let mut de = Deserializer::try_from_str("f[y]=1&x=0&f[x]=2").unwrap();
+            de.curr_key = Some(Cow::Borrowed("f"));
+            de.depth = 1;
+            let keys = KeyOrder {
+                fields: &[],
+                entries,
+            }
+            .deserialize(&mut de)
+            .unwrap();
+            assert_eq!(keys, ["y", "x"]);
+        }
+    }
+
+    #[test]
+    fn test_borrowed_keys() {
+        let m: HashMap<&str, &str> = from_str("a=1&b=x").unwrap();
+        assert_eq!(m, HashMap::from([("a", "1"), ("b", "x")]));
+        // a decoded key has nothing to borrow from
+        assert!(from_str::<HashMap<&str, &str>>("a%20b=1").is_err());
+    }
+
+    #[test]
+    fn test_from_pairs() {
+        let pairs = [
+            ("active", "true"),
+            ("ids", "1"),
+            ("name", "a&b=c"),
+            ("ids", "2"),
+        ];
+        let v = Flags::deserialize(
+            &mut Deserializer::try_from_pairs(pairs, &ParseOptions::default()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            v,
+            Flags {
+                active: true,
+                name: Some("a&b=c".to_string()),
+                ids: vec![1, 2],
+            }
+        );
+
+        let pairs = vec![("ids".to_string(), "1".to_string()); 3];
+        let opts = ParseOptions::new().duplicates(Duplicates::Last);
+        let de = Deserializer::try_from_pairs(pairs.clone(), &opts).unwrap();
+        assert_eq!(de.m["ids"].len(), 1);
+        let err = Deserializer::try_from_pairs(pairs, &opts.max_pairs(2))
+            .err()
+            .unwrap();
+        assert_eq!(err.message, "too many pairs, at most 2 allowed");
+    }
+
+    #[test]
+    fn test_value_deserializer() {
+        assert_eq!(
+            i64::deserialize(ValueDeserializer::new("-42")).unwrap(),
+            -42
+        );
+        let tags = Vec::<String>::deserialize(ValueDeserializer::with_values(["a", "b c"]));
+        assert_eq!(tags.unwrap(), vec!["a".to_string(), "b c".to_string()]);
+        assert_eq!(
+            Order::deserialize(ValueDeserializer::new("Desc")).unwrap(),
+            Order::Desc
+        );
+        assert_eq!(
+            Option::<u32>::deserialize(ValueDeserializer::new("7")).unwrap(),
+            Some(7)
+        );
+
+        let err = i64::deserialize(ValueDeserializer::new("x").key("limit")).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidNumber);
+        assert_eq!(
+            err.message,
+            "invalid value `x` for key `limit`, expected i64"
+        );
+
+        // a leftover pair read into a typed value
+        let (_, rest) = from_str_partial::<Pagination>("limit=1&offset=2&trace=1").unwrap();
+        let (key, value) = &rest[0];
+        let trace = bool::deserialize(ValueDeserializer::new(value).key(key));
+        assert!(trace.is_err());
+        let trace = u8::deserialize(ValueDeserializer::new(value).into_deserializer());
+        assert_eq!(trace.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_query_map_into_deserializer() {
+        let m = QueryMap::parse("limit=10&offset=20&tag=a").unwrap();
+        let p = Pagination::deserialize(m.into_deserializer()).unwrap();
+        assert_eq!(
+            p,
+            Pagination {
+                limit: 10,
+                offset: 20
+            }
+        );
+        let all = HashMap::<String, String>::deserialize(m.into_deserializer()).unwrap();
+        assert_eq!(all.len(), 3);
+    }
+
+    #[test]
+    fn test_from_str_traced() {
+        let (p, unused) =
+            from_str_traced::<Pagination>("trace=1&limit=10&tag=a&q=&offset=20&tag=b").unwrap();
+        assert_eq!(p.limit, 10);
+        assert_eq!(unused.len(), 3);
+        assert_eq!(
+            unused.collect::<Vec<_>>(),
+            [("trace", 1), ("tag", 2), ("q", 1)].map(|(k, n)| (k.to_string(), n))
+        );
+
+        let (_, unused) = from_str_traced::<Pagination>("limit=1&offset=2").unwrap();
+        assert_eq!(unused.count(), 0);
+
+        let mut deserializer = Deserializer::try_from_str("limit=1&offset=2&debug=1").unwrap();
+        let _ = HashMap::<String, String>::deserialize(&mut deserializer).unwrap();
+        assert_eq!(deserializer.finish().count(), 0);
+    }
+
+    #[derive(Debug, Deserialize, Default, PartialEq)]
+    #[serde(default)]
+    struct Forwarded {
+        q: String,
+        page: u32,
+        debug: bool,
+        internal_user: Option<String>,
+        sort_by: Option<String>,
+        sort_internal: Option<String>,
+    }
+
+    #[test]
+    fn test_key_filter() {
+        let s = format!(
+            "q=rust&debug=true&internal_user=root&page=2&sort_by=id&sort_internal=x\
+             &DEBUG=1&internal_user=admin&{}=1&sort_by{}=1",
+            "a".repeat(65),
+            "_".repeat(10)
+        );
+        let filter = KeyFilter::new()
+            .allow(&["q", "page", "debug", "internal_*", "sort*"])
+            .deny(&["debug", "internal_*", "sort_internal*"])
+            .max_key_len(16);
+        let opts = ParseOptions::new()
+            .key_transform(KeyTransform::Lowercase)
+            .key_filter(filter.clone());
+        let expected = Forwarded {
+            q: "rust".to_string(),
+            page: 2,
+            sort_by: Some("id".to_string()),
+            ..Default::default()
+        };
+        // dropped keys aren't unknown
+        let v: Forwarded = from_str_with(&s, &opts.clone().deny_unknown(true)).unwrap();
+        assert_eq!(v, expected);
+        let mut de = Deserializer::try_from_str_with(&s, &opts).unwrap();
+        assert_eq!(Forwarded::deserialize(&mut de).unwrap(), expected);
+        assert_eq!(de.finish().count(), 0);
+
+        let opts = opts.key_filter(filter.record(true));
+        let mut de = Deserializer::try_from_str_with(&s, &opts).unwrap();
+        assert_eq!(Forwarded::deserialize(&mut de).unwrap(), expected);
+        let filtered: Vec<_> = de.finish().collect();
+        assert_eq!(
+            filtered,
+            [
+                ("debug".to_string(), 2),
+                ("internal_user".to_string(), 2),
+                ("sort_internal".to_string(), 1),
+                ("a".repeat(65), 1),
+                (format!("sort_by{}", "_".repeat(10)), 1),
+            ]
+        );
+        // maps don't see them either
+        let m: HashMap<String, String> = from_str_with(&s, &opts).unwrap();
+        assert_eq!(m.len(), 3);
+        let m = QueryMap::parse_with(&s, &opts).unwrap();
+        assert_eq!(m.len(), 3);
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Profile {
+        hobbies: Option<Vec<String>>,
+    }
+
+    #[test]
+    fn test_deserialize_option_vec() {
+        let v: Profile = from_str("").unwrap();
+        assert_eq!(v.hobbies, None);
+        let v: Profile = from_str("hobbies=a&hobbies=b").unwrap();
+        assert_eq!(v.hobbies, Some(vec!["a".to_string(), "b".to_string()]));
+        let v: Profile = from_str("hobbies=").unwrap();
+        assert_eq!(v.hobbies, Some(vec![]));
+        let opts = ParseOptions::new().empty_value(EmptyValue::AsNone);
+        let v: Profile = from_str_with("hobbies=", &opts).unwrap();
+        assert_eq!(v.hobbies, None);
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Patch {
+        name: Option<Option<String>>,
+    }
+
+    #[test]
+    fn test_nested_option() {
+        for (v, s) in [
+            (None, ""),
+            (Some(None), "name="),
+            (Some(Some("n".to_string())), "name=n"),
+        ] {
+            let p = Patch { name: v };
+            assert_eq!(to_string(&p).unwrap(), s);
+            assert_eq!(from_str::<Patch>(s).unwrap(), p);
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Borrowed<'a> {
+        #[serde(borrow)]
+        name: Cow<'a, str>,
+        title: &'a str,
+    }
+
+    #[test]
+    fn test_deserialize_borrowed() {
+        let v: Borrowed = from_str("name=jack&title=dr").unwrap();
+        assert!(matches!(v.name, Cow::Borrowed("jack")));
+        assert_eq!(v.title, "dr");
+        let v: Borrowed = from_str("name=jack%20smith&title=dr").unwrap();
+        assert!(matches!(v.name, Cow::Owned(ref name) if name == "jack smith"));
+        assert!(from_str::<Borrowed>("name=jack&title=d%20r").is_err());
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Numbers {
+        page: std::num::NonZeroU32,
+        offset: Option<u64>,
+        level: Option<i16>,
+    }
+
+    #[test]
+    fn test_number_errors() {
+        let v: Numbers = from_str("page=2&offset=10&level=-3").unwrap();
+        assert_eq!(v.page.get(), 2);
+        assert_eq!(v.offset, Some(10));
+        assert_eq!(v.level, Some(-3));
+
+        let err = from_str::<Numbers>("page=0").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutOfRange);
+        assert_eq!(
+            err.to_string(),
+            "invalid value for key `page`: invalid value: integer `0`, expected a nonzero u32"
+        );
+
+        let err = from_str::<Numbers>("page=1&offset=-1").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutOfRange);
+        assert_eq!(
+            err.message,
+            "invalid value `-1` for key `offset`, expected u64"
+        );
+
+        let err = from_str::<Numbers>("page=1&level=40000").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutOfRange);
+        assert_eq!(
+            err.message,
+            "invalid value `40000` for key `level`, expected i16"
+        );
+
+        let err = from_str::<Numbers>("page=one").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidNumber);
+        assert_eq!(
+            err.message,
+            "invalid value `one` for key `page`, expected u32"
+        );
+    }
+
+    // Asks for 128 bits but, like visitors written before serde supported them, reads only
+    // 64-bit integers.
+    #[derive(Debug, PartialEq)]
+    struct Legacy(i64);
+
+    impl<'de> Deserialize<'de> for Legacy {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct LegacyVisitor;
+
+            impl<'de> Visitor<'de> for LegacyVisitor {
+                type Value = Legacy;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("an integer")
+                }
+
+                fn visit_i64<E>(self, v: i64) -> Result<Legacy, E> {
+                    Ok(Legacy(v))
+                }
+
+                fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Legacy, E> {
+                    i64::try_from(v)
+                        .map(Legacy)
+                        .map_err(|_| E::custom("too large"))
+                }
+            }
+
+            deserializer.deserialize_i128(LegacyVisitor)
+        }
+    }
+
+    // Reads 128-bit integers, but rejects the largest ones in words like serde's type error.
+    #[derive(Debug, PartialEq)]
+    struct Reserved;
+
+    impl<'de> Deserialize<'de> for Reserved {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct ReservedVisitor;
+
+            impl<'de> Visitor<'de> for ReservedVisitor {
+                type Value = Reserved;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("an integer")
+                }
+
+                fn visit_i128<E: serde::de::Error>(self, v: i128) -> Result<Reserved, E> {
+                    Err(E::custom(format!("integer `{}` as i128 is reserved", v)))
+                }
+            }
+
+            deserializer.deserialize_i128(ReservedVisitor)
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Wide {
+        reserved: Option<Reserved>,
+        legacy: Option<Legacy>,
+        signed: Option<i128>,
+        unsigned: Option<u128>,
+        id: Option<u64>,
+    }
+
+    #[test]
+    fn test_wide_numbers() {
+        let v: Wide = from_str(&format!("legacy={}", i64::MIN)).unwrap();
+        assert_eq!((v.legacy, v.reserved), (Some(Legacy(i64::MIN)), None));
+        let v: Wide = from_str(&format!("legacy={}", i64::MAX)).unwrap();
+        assert_eq!(v.legacy, Some(Legacy(i64::MAX)));
+
+        let err = from_str::<Wide>(&format!("legacy={}", i64::MAX as i128 + 1)).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutOfRange);
+        assert_eq!(
+            err.message,
+            "invalid value `9223372036854775808` for key `legacy`, expected a 64-bit integer"
+        );
+        // only serde's type error means the visitor lacks 128-bit support
+        let err = from_str::<Wide>(&format!("reserved={}", i128::MAX)).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutOfRange);
+        assert_eq!(err.message, "invalid value for key `reserved`");
+
+        let s = format!(
+            "signed={}&unsigned={}&id={}",
+            i128::MIN,
+            u128::MAX,
+            u64::MAX
+        );
+        let v: Wide = from_str(&s).unwrap();
+        assert_eq!(
+            (v.signed, v.unsigned, v.id),
+            (Some(i128::MIN), Some(u128::MAX), Some(u64::MAX))
+        );
+        let v: Wide = from_str("signed=-1&unsigned=0").unwrap();
+        assert_eq!((v.signed, v.unsigned), (Some(-1), Some(0)));
+
+        let err = from_str::<Wide>(&format!("unsigned={}0", u128::MAX)).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutOfRange);
+        let err = from_str::<Wide>(&format!("id={}", u64::MAX as u128 + 1)).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::OutOfRange);
+        assert_eq!(
+            err.message,
+            "invalid value `18446744073709551616` for key `id`, expected u64"
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Item {
+        sku: String,
+        qty: u32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Batch {
+        items: Vec<Item>,
+        #[serde(default)]
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_indexed_struct_seq() {
+        let opts = SerializerOptions::new()
+            .array_style(ArrayStyle::Indexed)
+            .nesting_style(NestingStyle::Brackets);
+        let v = Batch {
+            items: vec![
+                Item {
+                    sku: "a".to_string(),
+                    qty: 2,
+                },
+                Item {
+                    sku: "b".to_string(),
+                    qty: 1,
+                },
+            ],
+            tags: vec!["x".to_string(), "y".to_string()],
+        };
+        let s = to_string_with(&v, &opts).unwrap();
+        assert_eq!(
+            s,
+            "items[0][sku]=a&items[0][qty]=2&items[1][sku]=b&items[1][qty]=1&tags[0]=x&tags[1]=y"
+        );
+        assert_eq!(from_str::<Batch>(&s).unwrap(), v);
+
+        // elements are ordered by index, not by position in the input
+        let v: Batch =
+            from_str("items[1][sku]=b&items[1][qty]=1&items[0][qty]=2&items[0][sku]=a").unwrap();
+        assert_eq!(v.items[0].sku, "a");
+        assert_eq!(v.items[1].qty, 1);
+
+        let err = from_str::<Batch>("items[0][sku]=a&items[0][qty]=2&items[2][sku]=b").unwrap_err();
+        assert_eq!(err.message, "missing element 1 of `items`");
+        let err = from_str::<Batch>("items[0][sku]=a&items[0][qty]=2&items[1][sku]=b").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::MissingField {
+                field: "qty".to_string()
+            }
+        );
+        assert_eq!(err.path.as_deref(), Some("items[1]"));
+        assert_eq!(err.to_string(), "at `items[1]`: missing field `qty`");
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Filter {
+        name: Option<String>,
+        limit: u32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Paging {
+        limit: u32,
+        offset: u32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct SearchPage {
+        filter: Filter,
+        page: Paging,
+        extra: Option<Paging>,
+    }
+
+    #[test]
+    fn test_prefixed_nested_structs() {
+        let v = SearchPage {
+            filter: Filter {
+                name: Some("a".to_string()),
+                limit: 5,
+            },
+            page: Paging {
+                limit: 20,
+                offset: 40,
+            },
+            extra: None,
+        };
+        for (sep, s) in [
+            (
+                '.',
+                "filter.name=a&filter.limit=5&page.limit=20&page.offset=40",
+            ),
+            (
+                '_',
+                "filter_name=a&filter_limit=5&page_limit=20&page_offset=40",
+            ),
+        ] {
+            let opts = SerializerOptions::new().nesting_style(NestingStyle::Separator(sep));
+            assert_eq!(to_string_with(&v, &opts).unwrap(), s);
+            let opts = ParseOptions::new().nesting_separator(sep);
+            assert_eq!(from_str_with::<SearchPage>(s, &opts).unwrap(), v);
+        }
+
+        let opts = SerializerOptions::new().nesting_style(NestingStyle::Brackets);
+        let s = to_string_with(&v, &opts).unwrap();
+        assert_eq!(
+            s,
+            "filter[name]=a&filter[limit]=5&page[limit]=20&page[offset]=40"
+        );
+        assert_eq!(from_str::<SearchPage>(&s).unwrap(), v);
+
+        let v: SearchPage =
+            from_str("filter[limit]=1&page[limit]=2&page[offset]=3&extra[limit]=4&extra[offset]=5")
+                .unwrap();
+        assert_eq!(v.filter.limit, 1);
+        assert_eq!(v.page.limit, 2);
+        assert_eq!(
+            v.extra,
+            Some(Paging {
+                limit: 4,
+                offset: 5
+            })
+        );
+
+        let err =
+            from_str::<SearchPage>("filter[name]=a&page[limit]=2&page[offset]=3").unwrap_err();
+        assert_eq!(err.to_string(), "at `filter`: missing field `limit`");
+        let err = from_str::<SearchPage>("filter[limit]=1&page[limit]=2").unwrap_err();
+        assert_eq!(err.to_string(), "at `page`: missing field `offset`");
+        let err = from_str::<SearchPage>("filter[limit]=1").unwrap_err();
+        assert_eq!(err.to_string(), "missing field `page`");
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Range {
+        min: i32,
+        max: i32,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct RangeFilter {
+        ranges: Vec<Range>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Report {
+        filter: RangeFilter,
+        ids: Option<Vec<u32>>,
+    }
+
+    #[test]
+    fn test_error_path() {
+        let ranges = (0..3)
+            .map(|i| {
+                format!(
+                    "filter[ranges][{}][min]={}&filter[ranges][{}][max]=9",
+                    i, i, i
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        let v: Report = from_str(&ranges).unwrap();
+        assert_eq!(v.filter.ranges[2], Range { min: 2, max: 9 });
+
+        let s = ranges.replace("[2][min]=2", "[2][min]=x");
+        let err = from_str::<Report>(&s).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidNumber);
+        assert_eq!(err.path.as_deref(), Some("filter.ranges[2].min"));
+        assert_eq!(
+            err.to_string(),
+            "at `filter.ranges[2].min`: invalid value `x` for key `min`, expected i32: \
+             invalid digit found in string"
+        );
+
+        let s = "filter.ranges[0].min=1&filter.ranges[0].max=x";
+        let opts = ParseOptions::new().nesting_separator('.');
+        let err = from_str_with::<Report>(s, &opts).unwrap_err();
+        assert_eq!(err.path.as_deref(), Some("filter.ranges[0].max"));
+
+        let err = from_str::<Report>(&format!("{}&ids=1&ids=-2", ranges)).unwrap_err();
+        assert_eq!(err.path.as_deref(), Some("ids[1]"));
+
+        // values at the top level are named by the message
+        let err = from_str::<Numbers>("page=0").unwrap_err();
+        assert_eq!(err.path, None);
+    }
+
+    #[test]
+    fn test_all_errors() {
+        let opts = ParseOptions::new();
+        let s = "filter[ranges][0][min]=x&filter[ranges][0][max]=9&ids=1&ids=-2";
+        let errors = from_str_all_errors::<Report>(s, &opts).unwrap_err();
+        let found: Vec<_> = errors
+            .iter()
+            .map(|(param, err)| (param.unwrap(), err.path()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("filter", Some("filter.ranges[0].min")),
+                ("ids", Some("ids[1]"))
+            ]
+        );
+
+        // a missing field ends the errors, unless its value was rejected
+        let errors = from_str_all_errors::<Report>("ids=x", &opts).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors.to_string(),
+            "at `ids[0]`: invalid value `x` for key `ids`, expected u32: \
+             invalid digit found in string; missing field `filter`"
+        );
+        let s = "filter[ranges][0][min]=1&filter[ranges][0][max]=2&ids=3";
+        assert!(from_str_all_errors::<Report>(s, &opts).is_ok());
+
+        let opts = ParseOptions::new().deny_unknown(true).max_pairs(4);
+        let errors = from_str_all_errors::<Numbers>("page=1&offset=x&b=1&a=2", &opts).unwrap_err();
+        let params: Vec<_> = errors.into_iter().map(|(param, _)| param).collect();
+        assert_eq!(
+            params,
+            [
+                Some("offset".to_string()),
+                Some("a".to_string()),
+                Some("b".to_string())
+            ]
+        );
+        let errors = from_str_all_errors::<Numbers>("a&b&c&d&e", &opts).unwrap_err();
+        assert_eq!(errors.iter().next().unwrap().0, None);
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum IdOrSlug {
+        Id(i64),
+        Slug(String),
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum Threshold {
+        Ratio(f64),
+        Enabled(bool),
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Lookup {
+        target: IdOrSlug,
+        threshold: Option<Threshold>,
+    }
+
+    #[test]
+    fn test_untagged_enum() {
+        let v: Lookup = from_str("target=42").unwrap();
+        assert_eq!(v.target, IdOrSlug::Id(42));
+        assert_eq!(v.threshold, None);
+        let v: Lookup = from_str("target=-7&threshold=0.5").unwrap();
+        assert_eq!(v.target, IdOrSlug::Id(-7));
+        assert_eq!(v.threshold, Some(Threshold::Ratio(0.5)));
+        let v: Lookup = from_str("target=rust-2024&threshold=true").unwrap();
+        assert_eq!(v.target, IdOrSlug::Slug("rust-2024".to_string()));
+        assert_eq!(v.threshold, Some(Threshold::Enabled(true)));
+        assert_eq!(to_string(&v).unwrap(), "target=rust-2024&threshold=true");
+
+        let err = from_str::<Lookup>("target=1&threshold=high").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value for key `threshold`: \
+             data did not match any variant of untagged enum Threshold"
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(tag = "kind")]
+    enum UserFilter {
+        ByUser { user_id: i64 },
+        ByDate { from: String, to: Option<String> },
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(tag = "kind", content = "by")]
+    enum Sort {
+        Field { name: String, desc: bool },
+        Random,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Sorted {
+        sort: Sort,
+        limit: u32,
+    }
+
+    #[test]
+    fn test_tagged_enum() {
+        for (v, s) in [
+            (UserFilter::ByUser { user_id: 42 }, "kind=ByUser&user_id=42"),
+            (
+                UserFilter::ByDate {
+                    from: "2024-01-01".to_string(),
+                    to: None,
+                },
+                "kind=ByDate&from=2024-01-01",
+            ),
+        ] {
+            assert_eq!(to_string(&v).unwrap(), s);
+            assert_eq!(from_str::<UserFilter>(s).unwrap(), v);
+        }
+        let err = from_str::<UserFilter>("kind=ByGroup&group_id=1").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value for key `kind`, expected variant identifier: \
+             unknown variant `ByGroup`, expected `ByUser` or `ByDate`"
+        );
+
+        let opts = SerializerOptions::new().nesting_style(NestingStyle::Brackets);
+        let v = Sorted {
+            sort: Sort::Field {
+                name: "age".to_string(),
+                desc: true,
+            },
+            limit: 10,
+        };
+        let s = to_string_with(&v, &opts).unwrap();
+        assert_eq!(
+            s,
+            "sort[kind]=Field&sort[by][name]=age&sort[by][desc]=true&limit=10"
+        );
+        assert_eq!(from_str::<Sorted>(&s).unwrap(), v);
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum SortField {
+        CreatedAt,
+        Name,
+        #[serde(other)]
+        Unknown,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Order {
+        Asc,
+        Desc,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct SortBy {
+        field: SortField,
+        order: Option<Order>,
+    }
+
+    #[test]
+    fn test_enum_other_variant() {
+        let v: SortBy = from_str("field=created_at&order=Desc").unwrap();
+        assert_eq!(v.field, SortField::CreatedAt);
+        assert_eq!(v.order, Some(Order::Desc));
+        let v: SortBy = from_str("field=popularity").unwrap();
+        assert_eq!(v.field, SortField::Unknown);
+        let err = from_str::<SortBy>("field=name&order=random").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value for key `order`: unknown variant `random`, expected `Asc` or `Desc`"
+        );
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Status {
+        Open,
+        Closed,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct UserId(u64);
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct StatusFilter {
+        #[serde(default)]
+        statuses: Vec<Status>,
+        #[serde(default)]
+        scores: Vec<Option<i32>>,
+        #[serde(default)]
+        users: Vec<UserId>,
+    }
+
+    #[test]
+    fn test_seq_elements() {
+        let v: StatusFilter =
+            from_str("statuses=open&statuses=closed&scores=1&scores=&scores=3&users=7&users=9")
+                .unwrap();
+        assert_eq!(v.statuses, [Status::Open, Status::Closed]);
+        assert_eq!(v.scores, [Some(1), None, Some(3)]);
+        assert_eq!(v.users, [UserId(7), UserId(9)]);
+
+        let v: StatusFilter =
+            from_str("statuses[1]=open&statuses[0]=closed&scores[0]=&scores[1]=2").unwrap();
+        assert_eq!(v.statuses, [Status::Closed, Status::Open]);
+        assert_eq!(v.scores, [None, Some(2)]);
+
+        let err = from_str::<StatusFilter>("statuses=open&statuses=pending").unwrap_err();
+        assert_eq!(err.path.as_deref(), Some("statuses[1]"));
+        assert_eq!(
+            err.to_string(),
+            "at `statuses[1]`: invalid value for key `statuses`: \
+             unknown variant `pending`, expected `open` or `closed`"
+        );
+        let err = from_str::<StatusFilter>("users=7&users=x").unwrap_err();
+        assert_eq!(err.path.as_deref(), Some("users[1]"));
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Tagged {
+        q: String,
+        tags: Vec<String>,
+        page: u32,
+    }
+
+    #[test]
+    fn test_seq_elements_decoded() {
+        // each value is decoded on its own, so separators in one element stay in it
+        let v: Tagged = from_str("q=x%3Dy&tags=a%26b%3Dc&tags=%2C&tags=d&page=2").unwrap();
+        assert_eq!(v.q, "x=y");
+        assert_eq!(v.tags, ["a&b=c", ",", "d"]);
+        assert_eq!(v.page, 2);
+        assert_eq!(
+            to_string(&v).unwrap(),
+            "q=x%3Dy&tags=a%26b%3Dc&tags=%2C&tags=d&page=2"
+        );
+
+        let v: Tagged = from_str("tags[1]=d&tags[0]=a%26page%3D3&page=2&q=").unwrap();
+        assert_eq!(v.tags, ["a&page=3", "d"]);
+        assert_eq!(v.page, 2);
+    }
+
+    #[derive(Debug, Serialize)]
+    enum Query {
+        ByUser { user_id: i64 },
+        ByPage(Paging),
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(tag = "type")]
+    enum TaggedQuery {
+        ByUser { user_id: i64 },
+        ByPage(Paging),
+    }
+
+    #[test]
+    fn test_serialize_variant_tag() {
+        let s = to_string(&Query::ByUser { user_id: 42 }).unwrap();
+        assert_eq!(s, "type=ByUser&user_id=42");
+        assert_eq!(
+            from_str::<TaggedQuery>(&s).unwrap(),
+            TaggedQuery::ByUser { user_id: 42 }
+        );
+
+        let s = to_string(&Query::ByPage(Paging {
+            limit: 10,
+            offset: 20,
+        }))
+        .unwrap();
+        assert_eq!(s, "type=ByPage&limit=10&offset=20");
+        assert_eq!(
+            from_str::<TaggedQuery>(&s).unwrap(),
+            TaggedQuery::ByPage(Paging {
+                limit: 10,
+                offset: 20
+            })
+        );
+
+        let opts = SerializerOptions::new().variant_tag_key(Some("kind"));
+        let s = to_string_with(&Query::ByUser { user_id: 1 }, &opts).unwrap();
+        assert_eq!(s, "kind=ByUser&user_id=1");
+        let opts = SerializerOptions::new().variant_tag_key(None);
+        let s = to_string_with(&Query::ByUser { user_id: 1 }, &opts).unwrap();
+        assert_eq!(s, "user_id=1");
+    }
+
+    #[derive(Debug, Serialize)]
+    enum Span {
+        Range(u32, u32),
+    }
+
+    #[derive(Debug, Serialize)]
+    struct WithSpan {
+        span: Span,
+    }
+
+    #[test]
+    fn test_serialize_tuple_variant() {
+        // rejected rather than written as nothing
+        let err = to_string(&WithSpan {
+            span: Span::Range(1, 2),
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.message,
+            "cannot serialize tuple variant `Span::Range` for key `span`"
+        );
+        let err = to_string(&Span::Range(1, 2)).unwrap_err();
+        assert_eq!(err.message, "cannot serialize tuple variant `Span::Range`");
+    }
+
+    #[test]
+    fn test_reuse() {
+        let mut serializer = Serializer::with_capacity(4, SerializerOptions::new());
+        let mut out = String::new();
+        for (limit, offset) in [(10, 0), (20, 40)] {
+            serializer.reset();
+            Paging { limit, offset }.serialize(&mut serializer).unwrap();
+            out.clear();
+            serializer.write_output(&mut out);
+            assert_eq!(out, format!("limit={}&offset={}", limit, offset));
+        }
+        assert_eq!(serializer.into_inner(), "limit=20&offset=40");
+
+        let inputs = [
+            "limit=1&offset=2".to_string(),
+            "offset=4&limit=3".to_string(),
+        ];
+        let mut deserializer = Deserializer::try_from_str(&inputs[0]).unwrap();
+        let v = Paging::deserialize(&mut deserializer).unwrap();
+        assert_eq!(
+            v,
+            Paging {
+                limit: 1,
+                offset: 2
+            }
+        );
+        deserializer.reset(&inputs[1]).unwrap();
+        let v = Paging::deserialize(&mut deserializer).unwrap();
+        assert_eq!(
+            v,
+            Paging {
+                limit: 3,
+                offset: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_to_strings() {
+        let values: Vec<_> = (0..5)
+            .map(|i| Paging {
+                limit: 10 * i,
+                offset: i,
+            })
+            .collect();
+        let naive: Vec<_> = values.iter().map(|v| to_string(v).unwrap()).collect();
+        assert_eq!(to_strings(&values).unwrap(), naive);
+        assert!(to_strings::<Paging>(&[]).unwrap().is_empty());
+
+        let opts = SerializerOptions::new().sort_keys(true);
+        let mut out = Vec::new();
+        write_strings(&values, &opts, &mut out).unwrap();
+        let lines: Vec<_> = values
+            .iter()
+            .map(|v| to_string_with(v, &opts).unwrap())
+            .collect();
+        assert_eq!(String::from_utf8(out).unwrap(), lines.join("\n") + "\n");
+
+        // a value failing stops the batch
+        assert!(to_strings(&[1, 2]).is_err());
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct GeoPoint(f64, f64);
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Rgb(u8, u8, u8);
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Style {
+        center: GeoPoint,
+        color: Option<Rgb>,
+    }
+
+    #[test]
+    fn test_tuple_struct() {
+        let v = Style {
+            center: GeoPoint(52.1, 13.4),
+            color: Some(Rgb(255, 128, 0)),
+        };
+        let s = to_string(&v).unwrap();
+        assert_eq!(s, "center=52.1,13.4&color=255,128,0");
+        assert_eq!(from_str::<Style>(&s).unwrap(), v);
+        assert_eq!(
+            from_str::<Style>("center=52.1&center=13.4").unwrap(),
+            Style {
+                center: GeoPoint(52.1, 13.4),
+                color: None
+            }
+        );
+
+        let opts = SerializerOptions::new().tuple_delimiter(';');
+        let s = to_string_with(&v, &opts).unwrap();
+        assert_eq!(s, "center=52.1;13.4&color=255;128;0");
+        let parse_opts = ParseOptions::new().tuple_delimiter(';');
+        assert_eq!(from_str_with::<Style>(&s, &parse_opts).unwrap(), v);
+
+        let err = from_str::<Style>("center=52.1,13.4&color=255,128").unwrap_err();
+        assert_eq!(
+            err.message,
+            "expected 3 fields for tuple struct `Rgb` at key `color`, got 2"
+        );
+        let err = from_str::<Style>("center=1,2,3").unwrap_err();
+        assert_eq!(
+            err.message,
+            "expected 2 fields for tuple struct `GeoPoint` at key `center`, got 3"
+        );
+        let err = from_str::<Style>("center=1,x").unwrap_err();
+        assert_eq!(err.path.as_deref(), Some("center[1]"));
+
+        #[derive(Serialize)]
+        struct Name(String, String);
+        #[derive(Serialize)]
+        struct Person {
+            name: Name,
+        }
+        let err = to_string(&Person {
+            name: Name("a,b".to_string(), "c".to_string()),
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.message,
+            "field `a,b` of tuple struct `Name` contains the delimiter `,`"
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Signed {
+        path: Raw,
+        q: Raw,
+        tags: Vec<Raw>,
+        sig: String,
+    }
+
+    #[test]
+    fn test_raw() {
+        let s = "path=%2Fa%2fb&q=a+b%c3%BC&tags=%5B1%5d&tags=x%20y&sig=a+b%3d";
+        let opts = ParseOptions::new().raw_values(true);
+        let v: Signed = from_str_with(s, &opts).unwrap();
+        assert_eq!(&*v.path, "%2Fa%2fb");
+        assert_eq!(v.tags, [Raw("%5B1%5d".into()), Raw("x%20y".into())]);
+        // values which are not `Raw` are encoded again
+        assert_eq!(v.sig, "a+b%3d");
+        assert_eq!(
+            to_string(&v).unwrap(),
+            "path=%2Fa%2fb&q=a+b%c3%BC&tags=%5B1%5d&tags=x%20y&sig=a%2Bb%253d"
+        );
+
+        let v: Signed = from_str(s).unwrap();
+        assert_eq!(&*v.path, "/a/b");
+        assert_eq!(&*v.q, "a bü");
+    }
+
+    #[test]
+    fn test_fragment() {
+        let v: Paging = from_str("limit=1&offset=2#section").unwrap();
+        assert_eq!(v.offset, 2);
+        let v: HashMap<String, String> = from_str("#a=1&b=2").unwrap();
+        assert!(v.is_empty());
+        let v: HashMap<String, String> = from_str("?#").unwrap();
+        assert!(v.is_empty());
+        assert_eq!(QueryMap::parse("a=1#b=2").unwrap().to_string(), "a=1");
+
+        let strict = ParseOptions::new().deny_fragment(true);
+        let err = from_str_with::<Paging>("limit=1&offset=2#section", &strict).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unexpected fragment `#section`, `#` in values is written `%23`"
+        );
+        assert!(from_str_with::<Paging>("limit=1&offset=2", &strict).is_ok());
+
+        // a `#` in a key or value is encoded, and so survives a round trip
+        let v = HashMap::from([("a#b".to_string(), "#1%23".to_string())]);
+        let s = to_string(&v).unwrap();
+        assert_eq!(s, "a%23b=%231%2523");
+        assert_eq!(
+            from_str_with::<HashMap<String, String>>(&s, &strict).unwrap(),
+            v
+        );
+        let v = Signed {
+            path: Raw("a#b".into()),
+            q: Raw("%23".into()),
+            tags: vec![],
+            sig: String::new(),
+        };
+        assert_eq!(to_string(&v).unwrap(), "path=a%23b&q=%23&sig=");
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Palette {
+        rgb: [u8; 3],
+        pair: Option<(char, bool)>,
+    }
+
+    #[test]
+    fn test_fixed_size_arrays() {
+        let v = Palette {
+            rgb: [255, 128, 0],
+            pair: Some(('x', true)),
+        };
+        let s = to_string(&v).unwrap();
+        assert_eq!(s, "rgb=255&rgb=128&rgb=0&pair=x&pair=true");
+        assert_eq!(from_str::<Palette>(&s).unwrap(), v);
+        for s in [
+            "rgb=255,128,0&pair=x,true",
+            "rgb[2]=0&rgb[0]=255&rgb[1]=128&pair=x,true",
+        ] {
+            let back: Palette = from_str(s).unwrap();
+            assert_eq!((back.rgb, back.pair), (v.rgb, v.pair), "{}", s);
+        }
+
+        let opts = SerializerOptions::new().array_style(ArrayStyle::Comma);
+        let s = to_string_with(&v, &opts).unwrap();
+        assert_eq!(s, "rgb=255,128,0&pair=x,true");
+        assert_eq!(from_str::<Palette>(&s).unwrap(), v);
+        let opts = SerializerOptions::new().array_style(ArrayStyle::Indexed);
+        let s = to_string_with(&v, &opts).unwrap();
+        assert_eq!(from_str::<Palette>(&s).unwrap(), v);
+
+        for (s, got) in [
+            ("rgb=1&rgb=2", 2),
+            ("rgb=1,2", 2),
+            ("rgb=1&rgb=2&rgb=3&rgb=4", 4),
+            ("rgb=1,2,3,4", 4),
+            ("rgb[0]=1", 1),
+        ] {
+            let err = from_str::<Palette>(s).unwrap_err();
+            assert_eq!(
+                err.message,
+                format!("expected 3 values for key `rgb`, got {}", got),
+                "{}",
+                s
+            );
+        }
+
+        let keys: std::collections::BTreeMap<char, [u8; 2]> =
+            [('a', [1, 2]), ('&', [3, 4])].into_iter().collect();
+        let s = to_string(&keys).unwrap();
+        assert_eq!(s, "%26=3&%26=4&a=1&a=2");
+        assert_eq!(
+            from_str::<std::collections::BTreeMap<char, [u8; 2]>>(&s).unwrap(),
+            keys
+        );
+        assert!(from_str::<HashMap<char, u8>>("ab=1").is_err());
+    }
+
+    #[test]
+    fn test_empty_input() {
+        for s in ["", "?", "&", "?&&"] {
+            assert_eq!(
+                from_str::<Empty>(s).unwrap(),
+                Empty {
+                    a: None,
+                    b: None,
+                    c: None
+                },
+                "{:?}",
+                s
+            );
+            let err = from_str::<Pagination>(s).unwrap_err();
+            assert_eq!(
+                err.kind,
+                ErrorKind::MissingField {
+                    field: "limit".to_string()
+                }
+            );
+            assert!(QueryMap::parse(s).unwrap().is_empty());
+        }
+        assert_eq!(
+            from_str::<Pagination>("?limit=1&offset=2").unwrap(),
+            Pagination {
+                limit: 1,
+                offset: 2
+            }
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Link {
+        page: u32,
+        filter: String,
+        active: bool,
+        offset: i64,
+        ids: Vec<u8>,
+    }
+
+    #[test]
+    fn test_skip_defaults() {
+        let v = Link {
+            page: 1,
+            filter: String::new(),
+            active: false,
+            offset: 0,
+            ids: vec![0, 1, 0],
+        };
+        assert_eq!(
+            to_string(&v).unwrap(),
+            "page=1&filter=&active=false&offset=0&ids=0&ids=1&ids=0"
+        );
+        let opts = SerializerOptions::new().skip_defaults(true);
+        assert_eq!(
+            to_string_with(&v, &opts).unwrap(),
+            "page=1&active=false&offset=0&ids=0&ids=1&ids=0"
+        );
+        let opts = opts.skip_values(&["0", "false"]);
+        assert_eq!(
+            to_string_with(&v, &opts).unwrap(),
+            "page=1&ids=0&ids=1&ids=0"
+        );
+        let opts = opts.array_style(ArrayStyle::Comma);
+        assert_eq!(to_string_with(&v, &opts).unwrap(), "page=1&ids=0,1,0");
+
+        // values are compared as they are written
+        let opts = SerializerOptions::new()
+            .bool_format(BoolFormat::OneZero)
+            .skip_defaults(true)
+            .skip_values(&["0"]);
+        let v = Link { ids: vec![], ..v };
+        assert_eq!(to_string_with(&v, &opts).unwrap(), "page=1");
+    }
+
+    #[test]
+    fn test_separators() {
+        let v = Link {
+            page: 2,
+            filter: "a;b:c=d&e".to_string(),
+            active: true,
+            offset: -1,
+            ids: vec![1, 2],
+        };
+        let s = to_string(&v).unwrap();
+        assert_eq!(
+            s,
+            "page=2&filter=a%3Bb%3Ac%3Dd%26e&active=true&offset=-1&ids=1&ids=2"
+        );
+        assert_eq!(from_str::<Link>(&s).unwrap(), v);
+
+        let opts = SerializerOptions::new()
+            .pair_separator(';')
+            .kv_separator(':');
+        let s = to_string_with(&v, &opts).unwrap();
+        assert_eq!(
+            s,
+            "page:2;filter:a%3Bb%3Ac%3Dd%26e;active:true;offset:-1;ids:1;ids:2"
+        );
+        let parse = ParseOptions::new().pair_separator(';').kv_separator(':');
+        assert_eq!(from_str_with::<Link>(&s, &parse).unwrap(), v);
+
+        // separators kept unencoded otherwise are escaped
+        let opts = SerializerOptions::new()
+            .pair_separator('.')
+            .kv_separator('~');
+        let s = to_string_with(&v, &opts).unwrap();
+        assert!(s.starts_with("page~2.filter~a%3Bb%3Ac%3Dd%26e.active~true."));
+        let parse = ParseOptions::new().pair_separator('.').kv_separator('~');
+        assert_eq!(from_str_with::<Link>(&s, &parse).unwrap(), v);
+
+        let opts = SerializerOptions::new()
+            .pair_separator(';')
+            .kv_separator(';');
+        let err = to_string_with(&v, &opts).unwrap_err();
+        assert_eq!(
+            err.message,
+            "`;` cannot separate both pairs and keys from values"
+        );
+        let parse = ParseOptions::new()
+            .separators(&['&', ';'])
+            .kv_separator(';');
+        let err = from_str_with::<Link>("page;2", &parse).unwrap_err();
+        assert_eq!(
+            err.message,
+            "`;` cannot separate both pairs and keys from values"
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Features {
+        #[serde(default)]
+        features: HashSet<String>,
+        #[serde(default)]
+        ids: BTreeSet<i32>,
+        debug: Option<()>,
+    }
+
+    #[test]
+    fn test_sets_and_presence() {
+        let v: Features = from_str("features=beta&ids=3&features=dark_mode&ids=1&ids=3").unwrap();
+        assert_eq!(
+            v.features,
+            HashSet::from(["beta".to_string(), "dark_mode".to_string()])
+        );
+        assert_eq!(v.ids, BTreeSet::from([1, 3]));
+        assert_eq!(v.debug, None);
+
+        // a repeated value is dropped by a set, or rejected when asked to
+        let opts = ParseOptions::new().deny_duplicate_values(true);
+        let err = from_str_with::<Features>("ids=3&ids=1&ids=3", &opts).unwrap_err();
+        assert_eq!(err.message, "duplicate value `3` for key `ids`");
+        let v: Features = from_str_with("ids=3&ids=1&features=3", &opts).unwrap();
+        assert_eq!(v.ids, BTreeSet::from([1, 3]));
+
+        // a key with an empty value, or repeated, is present
+        for s in ["debug=", "ids=1&debug=&debug="] {
+            assert_eq!(from_str::<Features>(s).unwrap().debug, Some(()), "{}", s);
+        }
+        // without one, only with bare keys allowed
+        assert_eq!(
+            from_str::<Features>("ids=1&debug").unwrap_err().message,
+            "pair `debug` has no `=` separating its key from its value"
+        );
+        let opts = ParseOptions::new().bare_keys(true);
+        for s in ["debug", "ids=1&debug&debug="] {
+            let v: Features = from_str_with(s, &opts).unwrap();
+            assert_eq!(v.debug, Some(()), "{}", s);
+        }
+        let err = from_str::<Features>("debug=1").unwrap_err();
+        assert_eq!(
+            err.message,
+            "invalid value `1` for key `debug`, expected an empty value"
+        );
+
+        let v = Features {
+            features: HashSet::new(),
+            ids: BTreeSet::from([2, 1]),
+            debug: Some(()),
+        };
+        let s = to_string(&v).unwrap();
+        assert_eq!(s, "ids=1&ids=2&debug=");
+        assert_eq!(from_str::<Features>(&s).unwrap(), v);
+        let v = Features { debug: None, ..v };
+        assert_eq!(to_string(&v).unwrap(), "ids=1&ids=2");
+    }
+
+    #[derive(Serialize)]
+    struct Unsized<T: ?Sized> {
+        page: u32,
+        ids: T,
+    }
+
+    #[derive(Serialize)]
+    struct BorrowedFields<'a> {
+        name: &'a str,
+        nick: Option<&'a str>,
+        tags: &'a [&'a str],
+        note: Cow<'a, str>,
+        page: Option<&'a u32>,
+    }
+
+    #[test]
+    fn test_serialize_borrowed() {
+        let name = String::from("a b");
+        let tags = vec!["x", "y"];
+        let page = 3;
+        let v = BorrowedFields {
+            name: &name,
+            nick: Some("n"),
+            tags: &tags,
+            note: Cow::Borrowed("c&d"),
+            page: Some(&page),
+        };
+        assert_eq!(
+            to_string(&v).unwrap(),
+            "name=a%20b&nick=n&tags=x&tags=y&note=c%26d&page=3"
+        );
+        let v = BorrowedFields {
+            nick: None,
+            tags: &[],
+            note: Cow::Owned(String::new()),
+            page: None,
+            ..v
+        };
+        assert_eq!(to_string(&v).unwrap(), "name=a%20b&note=");
+
+        // unsized values are serialized in place
+        let v: &Unsized<[u8]> = &Unsized {
+            page: 1,
+            ids: [4, 2],
+        };
+        assert_eq!(to_string(v).unwrap(), "page=1&ids=4&ids=2");
+    }
+
+    #[derive(Debug, Serialize)]
+    struct Callback {
+        event: String,
+        note: String,
+        path: Raw,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        retry: Option<u32>,
+    }
+
+    // FNV-1a, standing in for an HMAC
+    fn signature(pairs: &[(String, String)]) -> String {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for (k, v) in pairs {
+            for b in k.bytes().chain([b'=']).chain(v.bytes()).chain([b'&']) {
+                hash = (hash ^ b as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+        format!("{:016x}", hash)
+    }
+
+    #[test]
+    fn test_post_process() {
+        let v = Callback {
+            event: "paid".to_string(),
+            note: String::new(),
+            path: Raw("%2Fa%2fb".into()),
+            retry: None,
+        };
+        // the hook sees decoded pairs, and those it keeps are written as before
+        let opts = SerializerOptions::new().post_process(Box::new(|pairs| {
+            pairs.retain(|(_, v)| !v.is_empty());
+            Ok(())
+        }));
+        assert_eq!(
+            to_string_with(&v, &opts).unwrap(),
+            "event=paid&path=%2Fa%2fb"
+        );
+
+        let opts = SerializerOptions::new()
+            .sort_keys(true)
+            .post_process(Box::new(|pairs| {
+                assert_eq!(pairs[2], ("path".to_string(), "/a/b".to_string()));
+                let sig = signature(pairs);
+                pairs.push(("sig".to_string(), sig));
+                pairs.push(("signed by".to_string(), "a&b".to_string()));
+                Ok(())
+            }));
+        let expected = signature(&[
+            ("event".to_string(), "paid".to_string()),
+            ("note".to_string(), String::new()),
+            ("path".to_string(), "/a/b".to_string()),
+        ]);
+        // added pairs are encoded, then sorted with the others
+        assert_eq!(
+            to_string_with(&v, &opts).unwrap(),
+            format!(
+                "event=paid&note=&path=%2Fa%2fb&sig={}&signed%20by=a%26b",
+                expected
+            )
+        );
+        let v = Callback {
+            retry: Some(2),
+            ..v
+        };
+        assert_eq!(to_strings_with(&[v], &opts).unwrap().len(), 1);
+
+        let opts = SerializerOptions::new().post_process(Box::new(|pairs| {
+            if pairs.iter().any(|(k, _)| k == "retry") {
+                return Err(Error::new("retries are not signed", None));
+            }
+            Ok(())
+        }));
+        let v = Callback {
+            event: "paid".to_string(),
+            note: String::new(),
+            path: Raw(String::new()),
+            retry: Some(1),
+        };
+        assert_eq!(
+            to_string_with(&v, &opts).unwrap_err().message,
+            "retries are not signed"
+        );
     }
 }
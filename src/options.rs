@@ -0,0 +1,889 @@
+use base64::prelude::*;
+use std::{error::Error as StdError, fmt, sync::Arc};
+
+/// How sequence values are written to the query string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrayStyle {
+    /// `ids=1&ids=2`
+    #[default]
+    Repeat,
+    /// `ids[]=1&ids[]=2`
+    Brackets,
+    /// `ids=1,2`
+    Comma,
+    /// `ids[0]=1&ids[1]=2`
+    Indexed,
+}
+
+/// How structs and maps nested in a struct are written to the query string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NestingStyle {
+    /// Nested structs and maps are rejected, they have to be `#[serde(flatten)]`ed.
+    #[default]
+    Reject,
+    /// `filter[limit]=10`, combined with [`ArrayStyle::Indexed`] `items[0][sku]=a`.
+    Brackets,
+    /// `filter.limit=10` with `Separator('.')`.
+    Separator(char),
+}
+
+/// How bools are written to the query string. Every format but `Custom` reads back with
+/// [`ParseOptions::lenient_bool`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum BoolFormat {
+    /// `active=true`
+    #[default]
+    TrueFalse,
+    /// `active=1`
+    OneZero,
+    /// `active=yes`
+    YesNo,
+    /// `active=Y` with `Custom { true_str: "Y".into(), false_str: "N".into() }`.
+    Custom { true_str: String, false_str: String },
+}
+
+impl BoolFormat {
+    pub(crate) fn format(&self, v: bool) -> &str {
+        match (self, v) {
+            (BoolFormat::TrueFalse, true) => "true",
+            (BoolFormat::TrueFalse, false) => "false",
+            (BoolFormat::OneZero, true) => "1",
+            (BoolFormat::OneZero, false) => "0",
+            (BoolFormat::YesNo, true) => "yes",
+            (BoolFormat::YesNo, false) => "no",
+            (BoolFormat::Custom { true_str, .. }, true) => true_str,
+            (BoolFormat::Custom { false_str, .. }, false) => false_str,
+        }
+    }
+}
+
+/// How floats are written to the query string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatFormat {
+    /// The shortest digits reading back as the same value, never in scientific notation:
+    /// `3`, `0.30000000000000004`, `-0`, and `1e300` written out in full.
+    #[default]
+    Decimal,
+    /// A fixed number of digits after the point, the exact value rounded half to even:
+    /// `3.00`, `-0.00`, and `0.12` for `0.125` with `Fixed(2)`.
+    Fixed(usize),
+    /// The shortest digits as formatted by `ryu`, which is faster. Integral values keep their
+    /// `.0`, and large or tiny magnitudes are written in scientific notation, e.g. `1e16` and
+    /// `1e-6` for an `f64`.
+    #[cfg(feature = "ryu")]
+    Ryu,
+}
+
+#[cfg(feature = "ryu")]
+pub(crate) trait Float: fmt::Display + ryu::Float {}
+#[cfg(feature = "ryu")]
+impl<F: fmt::Display + ryu::Float> Float for F {}
+#[cfg(not(feature = "ryu"))]
+pub(crate) trait Float: fmt::Display {}
+#[cfg(not(feature = "ryu"))]
+impl<F: fmt::Display> Float for F {}
+
+impl FloatFormat {
+    pub(crate) fn format<F: Float>(&self, v: F) -> String {
+        match self {
+            FloatFormat::Decimal => v.to_string(),
+            FloatFormat::Fixed(precision) => format!("{:.*}", precision, v),
+            #[cfg(feature = "ryu")]
+            FloatFormat::Ryu => ryu::Buffer::new().format(v).to_string(),
+        }
+    }
+}
+
+/// Which implementation the [`Serializer`](crate::Serializer) matches where they differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompatMode {
+    /// Percent-encode everything but the unreserved characters of RFC 3986, spaces as `%20`.
+    #[default]
+    Rfc3986,
+    /// Match `serde_urlencoded`: spaces as `+`, `*` kept and `~` encoded. With the `ryu`
+    /// feature, floats are also written by `ryu` as it does, e.g. `1.0` for `1`.
+    SerdeUrlencoded,
+}
+
+/// How bytes, e.g. `serde_bytes::ByteBuf`, are written to the query string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BytesEncoding {
+    /// `data=-_8`, base64 with the URL safe alphabet and without padding.
+    #[default]
+    Base64UrlNoPad,
+    /// `data=%2B%2F8%3D`, base64 with the standard alphabet and padding, percent-encoded.
+    Base64Standard,
+    /// `data=fbff`
+    Hex,
+}
+
+impl BytesEncoding {
+    pub(crate) fn encode(&self, v: &[u8]) -> String {
+        match self {
+            BytesEncoding::Base64UrlNoPad => BASE64_URL_SAFE_NO_PAD.encode(v),
+            BytesEncoding::Base64Standard => BASE64_STANDARD.encode(v),
+            BytesEncoding::Hex => hex::encode(v),
+        }
+    }
+
+    // Both base64 encodings read either alphabet, with or without padding.
+    pub(crate) fn decode(&self, s: &str) -> Result<Vec<u8>, Box<dyn StdError>> {
+        match self {
+            BytesEncoding::Base64UrlNoPad | BytesEncoding::Base64Standard => {
+                let s: String = s
+                    .trim_end_matches('=')
+                    .chars()
+                    .map(|c| match c {
+                        '-' => '+',
+                        '_' => '/',
+                        c => c,
+                    })
+                    .collect();
+                Ok(BASE64_STANDARD_NO_PAD.decode(s)?)
+            }
+            BytesEncoding::Hex => Ok(hex::decode(s)?),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            BytesEncoding::Base64UrlNoPad | BytesEncoding::Base64Standard => "base64 bytes",
+            BytesEncoding::Hex => "hex bytes",
+        }
+    }
+}
+
+/// A normalization applied to every key.
+#[derive(Clone)]
+pub enum KeyTransform {
+    /// `pageSize`, `PageSize` and `page-size` become `page_size`.
+    SnakeCase,
+    /// `page_size` and `page-size` become `pageSize`.
+    CamelCase,
+    /// `page-size` becomes `page_size`.
+    KebabToSnake,
+    Lowercase,
+    Custom(Arc<dyn Fn(&str) -> String + Send + Sync>),
+}
+
+impl KeyTransform {
+    pub(crate) fn apply(&self, key: &str) -> String {
+        match self {
+            KeyTransform::SnakeCase => {
+                let mut out = String::with_capacity(key.len() + 4);
+                for (i, c) in key.chars().enumerate() {
+                    if c == '-' {
+                        out.push('_');
+                    } else if c.is_uppercase() {
+                        if i > 0 && !out.ends_with('_') {
+                            out.push('_');
+                        }
+                        out.extend(c.to_lowercase());
+                    } else {
+                        out.push(c);
+                    }
+                }
+                out
+            }
+            KeyTransform::CamelCase => {
+                let mut out = String::with_capacity(key.len());
+                let mut upper_next = false;
+                for c in key.chars() {
+                    if c == '_' || c == '-' {
+                        upper_next = !out.is_empty();
+                    } else if upper_next {
+                        out.extend(c.to_uppercase());
+                        upper_next = false;
+                    } else {
+                        out.push(c);
+                    }
+                }
+                out
+            }
+            KeyTransform::KebabToSnake => key.replace('-', "_"),
+            KeyTransform::Lowercase => key.to_lowercase(),
+            KeyTransform::Custom(f) => f(key),
+        }
+    }
+}
+
+impl fmt::Debug for KeyTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyTransform::SnakeCase => write!(f, "SnakeCase"),
+            KeyTransform::CamelCase => write!(f, "CamelCase"),
+            KeyTransform::KebabToSnake => write!(f, "KebabToSnake"),
+            KeyTransform::Lowercase => write!(f, "Lowercase"),
+            KeyTransform::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// A per-key preprocessing of values, registered with
+/// [`value_decoder`](ParseOptions::value_decoder).
+pub type ValueDecoder = dyn Fn(&str) -> Result<String, crate::Error> + Send + Sync;
+
+/// What happens when an entry of a map produces a key written before, e.g. by a map type
+/// keeping repeated keys or by fields flattened into the same struct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MapDuplicateKeys {
+    /// Treat the key like any other repeated key, as
+    /// [`allow_duplicate_keys`](SerializerOptions::allow_duplicate_keys) says.
+    #[default]
+    Allow,
+    /// Replace the pairs written before for the key, so the last entry overrides the others.
+    LastWins,
+    /// Reject the map, even when duplicate keys are allowed otherwise.
+    Error,
+}
+
+/// A rewrite of the serialized pairs before they are joined, registered with
+/// [`post_process`](SerializerOptions::post_process).
+pub type PostProcess = dyn Fn(&mut Vec<(String, String)>) -> Result<(), crate::Error> + Send + Sync;
+
+/// Configuration of the [`Serializer`](crate::Serializer).
+///
+/// The default options reproduce the output of [`to_string`](crate::to_string).
+#[derive(Clone)]
+pub struct SerializerOptions {
+    pub(crate) array_style: ArrayStyle,
+    pub(crate) nesting_style: NestingStyle,
+    pub(crate) bool_format: BoolFormat,
+    pub(crate) float_format: FloatFormat,
+    pub(crate) bytes_encoding: BytesEncoding,
+    pub(crate) none_as_empty: bool,
+    pub(crate) sort_keys: bool,
+    pub(crate) sort_map_entries: bool,
+    pub(crate) allow_duplicate_keys: bool,
+    pub(crate) map_duplicate_keys: MapDuplicateKeys,
+    pub(crate) reject_control_chars: bool,
+    pub(crate) key_transform: Option<KeyTransform>,
+    pub(crate) variant_tag_key: Option<String>,
+    pub(crate) tuple_delimiter: char,
+    pub(crate) skip_defaults: bool,
+    pub(crate) skip_values: Vec<String>,
+    pub(crate) compat: CompatMode,
+    pub(crate) pair_separator: char,
+    pub(crate) kv_separator: char,
+    pub(crate) post_process: Option<Arc<PostProcess>>,
+}
+
+impl fmt::Debug for SerializerOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SerializerOptions")
+            .field("array_style", &self.array_style)
+            .field("nesting_style", &self.nesting_style)
+            .field("bool_format", &self.bool_format)
+            .field("float_format", &self.float_format)
+            .field("bytes_encoding", &self.bytes_encoding)
+            .field("none_as_empty", &self.none_as_empty)
+            .field("sort_keys", &self.sort_keys)
+            .field("sort_map_entries", &self.sort_map_entries)
+            .field("allow_duplicate_keys", &self.allow_duplicate_keys)
+            .field("map_duplicate_keys", &self.map_duplicate_keys)
+            .field("reject_control_chars", &self.reject_control_chars)
+            .field("key_transform", &self.key_transform)
+            .field("variant_tag_key", &self.variant_tag_key)
+            .field("tuple_delimiter", &self.tuple_delimiter)
+            .field("skip_defaults", &self.skip_defaults)
+            .field("skip_values", &self.skip_values)
+            .field("compat", &self.compat)
+            .field("pair_separator", &self.pair_separator)
+            .field("kv_separator", &self.kv_separator)
+            .field("post_process", &self.post_process.is_some())
+            .finish()
+    }
+}
+
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        Self {
+            array_style: ArrayStyle::default(),
+            nesting_style: NestingStyle::default(),
+            bool_format: BoolFormat::default(),
+            float_format: FloatFormat::default(),
+            bytes_encoding: BytesEncoding::default(),
+            none_as_empty: false,
+            sort_keys: false,
+            sort_map_entries: false,
+            allow_duplicate_keys: false,
+            map_duplicate_keys: MapDuplicateKeys::default(),
+            reject_control_chars: false,
+            key_transform: None,
+            variant_tag_key: Some("type".to_string()),
+            tuple_delimiter: ',',
+            skip_defaults: false,
+            skip_values: Vec::new(),
+            compat: CompatMode::default(),
+            pair_separator: '&',
+            kv_separator: '=',
+            post_process: None,
+        }
+    }
+}
+
+impl SerializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn array_style(mut self, style: ArrayStyle) -> Self {
+        self.array_style = style;
+        self
+    }
+
+    pub fn nesting_style(mut self, style: NestingStyle) -> Self {
+        self.nesting_style = style;
+        self
+    }
+
+    pub fn bool_format(mut self, format: BoolFormat) -> Self {
+        self.bool_format = format;
+        self
+    }
+
+    pub fn float_format(mut self, format: FloatFormat) -> Self {
+        self.float_format = format;
+        self
+    }
+
+    pub fn bytes_encoding(mut self, encoding: BytesEncoding) -> Self {
+        self.bytes_encoding = encoding;
+        self
+    }
+
+    /// Write `None` as an empty value (`key=`) instead of omitting the key.
+    pub fn none_as_empty(mut self, none_as_empty: bool) -> Self {
+        self.none_as_empty = none_as_empty;
+        self
+    }
+
+    /// Sort all pairs bytewise by key. Repeated values of the same key keep their relative order.
+    pub fn sort_keys(mut self, sort: bool) -> Self {
+        self.sort_keys = sort;
+        self
+    }
+
+    /// Emit the entries of every serialized map sorted by key instead of in iteration order.
+    ///
+    /// Note that `#[serde(flatten)]` makes the containing struct serialize as a map, so its
+    /// fields are sorted as well.
+    pub fn sort_map_entries(mut self, sort: bool) -> Self {
+        self.sort_map_entries = sort;
+        self
+    }
+
+    /// Allow the same key to be produced by different fields, e.g. by two flattened structs
+    /// sharing a field name. Repeated keys produced by sequences are always allowed.
+    pub fn allow_duplicate_keys(mut self, allow: bool) -> Self {
+        self.allow_duplicate_keys = allow;
+        self
+    }
+
+    /// How a key repeated by the entries of a map is treated.
+    pub fn map_duplicate_keys(mut self, policy: MapDuplicateKeys) -> Self {
+        self.map_duplicate_keys = policy;
+        self
+    }
+
+    /// Reject keys and values containing control characters such as `\n`, `\0` or DEL instead
+    /// of percent-encoding them.
+    pub fn reject_control_chars(mut self, reject: bool) -> Self {
+        self.reject_control_chars = reject;
+        self
+    }
+
+    /// Transform every key before it is written.
+    pub fn key_transform(mut self, transform: KeyTransform) -> Self {
+        self.key_transform = Some(transform);
+        self
+    }
+
+    /// The key of the pair naming the variant of a struct variant, or of a newtype variant
+    /// at the top level, `type` by default. The pair is written before the fields, so the
+    /// output reads back into an enum with the matching `#[serde(tag = "...")]`. `None` writes
+    /// the fields only.
+    pub fn variant_tag_key(mut self, key: Option<&str>) -> Self {
+        self.variant_tag_key = key.map(str::to_string);
+        self
+    }
+
+    /// The character joining the fields of a tuple struct into one value, `,` by default, e.g.
+    /// `center=52.1,13.4` for `GeoPoint(52.1, 13.4)`.
+    pub fn tuple_delimiter(mut self, delimiter: char) -> Self {
+        self.tuple_delimiter = delimiter;
+        self
+    }
+
+    /// The character written between pairs, `&` by default, e.g. `;` for matrix URIs. It is
+    /// percent-encoded wherever it occurs in a key or value, except in [`Raw`](crate::Raw) values.
+    pub fn pair_separator(mut self, separator: char) -> Self {
+        self.pair_separator = separator;
+        self
+    }
+
+    /// The character written between a key and its value, `=` by default. It is
+    /// percent-encoded like [`pair_separator`](Self::pair_separator), and serialization fails
+    /// when both are the same character.
+    pub fn kv_separator(mut self, separator: char) -> Self {
+        self.kv_separator = separator;
+        self
+    }
+
+    /// Omit pairs whose value is empty or one of [`skip_values`](Self::skip_values), e.g. to
+    /// keep pagination links short. The value is compared as it is written, before encoding,
+    /// so this works for any type but cannot know its `Default`. Elements of sequences and
+    /// tuples are always written.
+    pub fn skip_defaults(mut self, skip: bool) -> Self {
+        self.skip_defaults = skip;
+        self
+    }
+
+    /// Further values omitted with [`skip_defaults`](Self::skip_defaults), e.g. `["0", "false"]`.
+    pub fn skip_values(mut self, values: &[&str]) -> Self {
+        self.skip_values = values.iter().map(|v| v.to_string()).collect();
+        self
+    }
+
+    /// Match the output of another implementation, e.g. for clients comparing query strings
+    /// byte for byte. This sets the float format too, which can be changed afterwards.
+    pub fn compat(mut self, mode: CompatMode) -> Self {
+        self.compat = mode;
+        #[cfg(feature = "ryu")]
+        if mode == CompatMode::SerdeUrlencoded {
+            self.float_format = FloatFormat::Ryu;
+        }
+        self
+    }
+
+    /// Rewrite the pairs before they are joined, e.g. to append a signature over the other
+    /// pairs or to drop some of them. `hook` receives the decoded keys and values, in the order
+    /// they were serialized and with the key prefixes and
+    /// [`key_transform`](Self::key_transform) applied. The pairs are encoded again afterwards,
+    /// then sorted if [`sort_keys`](Self::sort_keys) is set; a pair the hook leaves as it was
+    /// keeps its original encoding. An error of the hook is returned by the serialization.
+    ///
+    /// It is applied by [`to_string_with`](crate::to_string_with),
+    /// [`to_strings_with`](crate::to_strings_with) and [`write_strings`](crate::write_strings),
+    /// not by [`Serializer::output`](crate::Serializer::output).
+    pub fn post_process(mut self, hook: Box<PostProcess>) -> Self {
+        self.post_process = Some(Arc::from(hook));
+        self
+    }
+}
+
+/// How repeated keys in the input are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Duplicates {
+    /// Keep every value. Sequences receive all of them, scalars the first one.
+    #[default]
+    Collect,
+    /// Keep only the first value.
+    First,
+    /// Keep only the last value.
+    Last,
+    /// Reject the input.
+    Error,
+}
+
+/// How pairs with an empty value (`key=`) are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyValue {
+    /// Keep the empty string as the value. An `Option` whose inner type cannot be read from it,
+    /// e.g. `qty=` for an `Option<u32>`, is `None`.
+    #[default]
+    Keep,
+    /// Drop the pair, as if the key was absent, so that fields with `#[serde(default)]` take
+    /// their default.
+    AsNone,
+    /// Reject the input.
+    Error,
+}
+
+/// Configuration of the [`Deserializer`](crate::Deserializer).
+///
+/// The default options reproduce the behavior of [`from_str`](crate::from_str).
+#[derive(Clone)]
+pub struct ParseOptions {
+    pub(crate) duplicates: Duplicates,
+    pub(crate) empty_value: EmptyValue,
+    pub(crate) lenient_bool: bool,
+    pub(crate) lenient_numbers: bool,
+    pub(crate) number_underscores: bool,
+    pub(crate) trim_values: bool,
+    pub(crate) raw_values: bool,
+    pub(crate) max_input_len: Option<usize>,
+    pub(crate) max_pairs: Option<usize>,
+    pub(crate) max_value_len: Option<usize>,
+    pub(crate) max_values_per_key: Option<usize>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) deny_unknown: bool,
+    pub(crate) deny_fragment: bool,
+    pub(crate) deny_duplicate_values: bool,
+    pub(crate) separators: Vec<char>,
+    pub(crate) kv_separator: char,
+    pub(crate) bare_keys: bool,
+    pub(crate) nesting_separator: Option<char>,
+    pub(crate) bytes_encoding: BytesEncoding,
+    pub(crate) key_transform: Option<KeyTransform>,
+    pub(crate) tuple_delimiter: char,
+    pub(crate) value_decoders: Vec<(String, Arc<ValueDecoder>)>,
+    pub(crate) key_filter: Option<KeyFilter>,
+    #[cfg(feature = "tracing")]
+    pub(crate) trace_full_values: bool,
+}
+
+impl fmt::Debug for ParseOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("ParseOptions");
+        s.field("duplicates", &self.duplicates)
+            .field("empty_value", &self.empty_value)
+            .field("lenient_bool", &self.lenient_bool)
+            .field("lenient_numbers", &self.lenient_numbers)
+            .field("number_underscores", &self.number_underscores)
+            .field("trim_values", &self.trim_values)
+            .field("raw_values", &self.raw_values)
+            .field("max_input_len", &self.max_input_len)
+            .field("max_pairs", &self.max_pairs)
+            .field("max_value_len", &self.max_value_len)
+            .field("max_values_per_key", &self.max_values_per_key)
+            .field("max_depth", &self.max_depth)
+            .field("deny_unknown", &self.deny_unknown)
+            .field("deny_fragment", &self.deny_fragment)
+            .field("deny_duplicate_values", &self.deny_duplicate_values)
+            .field("separators", &self.separators)
+            .field("kv_separator", &self.kv_separator)
+            .field("bare_keys", &self.bare_keys)
+            .field("nesting_separator", &self.nesting_separator)
+            .field("bytes_encoding", &self.bytes_encoding)
+            .field("key_transform", &self.key_transform)
+            .field("tuple_delimiter", &self.tuple_delimiter)
+            .field(
+                "value_decoders",
+                &self
+                    .value_decoders
+                    .iter()
+                    .map(|(key, _)| key)
+                    .collect::<Vec<_>>(),
+            )
+            .field("key_filter", &self.key_filter);
+        #[cfg(feature = "tracing")]
+        s.field("trace_full_values", &self.trace_full_values);
+        s.finish()
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            duplicates: Duplicates::default(),
+            empty_value: EmptyValue::default(),
+            lenient_bool: false,
+            lenient_numbers: false,
+            number_underscores: false,
+            trim_values: false,
+            raw_values: false,
+            max_input_len: None,
+            max_pairs: None,
+            max_value_len: None,
+            max_values_per_key: None,
+            max_depth: Some(32),
+            deny_unknown: false,
+            deny_fragment: false,
+            deny_duplicate_values: false,
+            separators: vec!['&'],
+            kv_separator: '=',
+            bare_keys: false,
+            nesting_separator: None,
+            bytes_encoding: BytesEncoding::default(),
+            key_transform: None,
+            tuple_delimiter: ',',
+            value_decoders: Vec::new(),
+            key_filter: None,
+            #[cfg(feature = "tracing")]
+            trace_full_values: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn duplicates(mut self, duplicates: Duplicates) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    pub fn empty_value(mut self, empty_value: EmptyValue) -> Self {
+        self.empty_value = empty_value;
+        self
+    }
+
+    /// Also accept `1`/`0`, `yes`/`no`, `y`/`n` and `on`/`off` (case-insensitive) for bools.
+    pub fn lenient_bool(mut self, lenient: bool) -> Self {
+        self.lenient_bool = lenient;
+        self
+    }
+
+    /// Accept a single leading `+` on integers and floats. Sent unencoded, as in `count=+5`, a
+    /// `+` is decoded to a space, which is skipped as well; `%2B5` is read as `5` either way.
+    pub fn lenient_numbers(mut self, lenient: bool) -> Self {
+        self.lenient_numbers = lenient;
+        self
+    }
+
+    /// Skip underscores in integers and floats, so `price=1_000` is read as `1000`.
+    pub fn number_underscores(mut self, allow: bool) -> Self {
+        self.number_underscores = allow;
+        self
+    }
+
+    /// Strip leading and trailing whitespace from values.
+    pub fn trim_values(mut self, trim: bool) -> Self {
+        self.trim_values = trim;
+        self
+    }
+
+    /// Keep values exactly as they appear in the input, without decoding escapes or `+`, e.g.
+    /// to forward them with [`Raw`](crate::Raw) without changing a byte. Keys are still decoded.
+    pub fn raw_values(mut self, raw: bool) -> Self {
+        self.raw_values = raw;
+        self
+    }
+
+    /// Reject inputs longer than `max` bytes.
+    pub fn max_input_len(mut self, max: usize) -> Self {
+        self.max_input_len = Some(max);
+        self
+    }
+
+    /// Reject inputs with more than `max` pairs.
+    pub fn max_pairs(mut self, max: usize) -> Self {
+        self.max_pairs = Some(max);
+        self
+    }
+
+    /// Reject inputs containing a value longer than `max` bytes.
+    pub fn max_value_len(mut self, max: usize) -> Self {
+        self.max_value_len = Some(max);
+        self
+    }
+
+    /// Reject inputs repeating a key more than `max` times. Only applies with
+    /// [`Duplicates::Collect`], the other modes keep a single value.
+    pub fn max_values_per_key(mut self, max: usize) -> Self {
+        self.max_values_per_key = Some(max);
+        self
+    }
+
+    /// Reject input nesting structs, maps or indexed sequences deeper than `max`, 32 by default.
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Reject inputs containing keys which were not consumed by the target type.
+    pub fn deny_unknown(mut self, deny: bool) -> Self {
+        self.deny_unknown = deny;
+        self
+    }
+
+    /// Reject inputs containing a `#`, instead of ending the query string at it and ignoring the
+    /// fragment following it, e.g. `#top` in `page=2#top`. A `#` in a value is written `%23`.
+    pub fn deny_fragment(mut self, deny: bool) -> Self {
+        self.deny_fragment = deny;
+        self
+    }
+
+    /// Reject inputs giving a key the same value twice, e.g. `tags=a&tags=a`, which a set would
+    /// otherwise keep once without telling.
+    pub fn deny_duplicate_values(mut self, deny: bool) -> Self {
+        self.deny_duplicate_values = deny;
+        self
+    }
+
+    /// Characters separating pairs, `&` by default.
+    pub fn separators(mut self, separators: &[char]) -> Self {
+        self.separators = separators.to_vec();
+        self
+    }
+
+    /// Separate pairs by `separator` alone, like [`separators`](Self::separators) with one
+    /// character.
+    pub fn pair_separator(mut self, separator: char) -> Self {
+        self.separators = vec![separator];
+        self
+    }
+
+    /// The character splitting a pair into its key and value, `=` by default. Pairs are split at
+    /// its first occurrence, so values may contain it unencoded. Parsing fails when it is also
+    /// one of the pair separators.
+    pub fn kv_separator(mut self, separator: char) -> Self {
+        self.kv_separator = separator;
+        self
+    }
+
+    /// Read a key without the key-value separator, e.g. `debug` in `debug&page=2`, as having an
+    /// empty value, as `serde_urlencoded` does, instead of rejecting the input. An `Option<()>`
+    /// then tells whether such a flag is present.
+    pub fn bare_keys(mut self, allow: bool) -> Self {
+        self.bare_keys = allow;
+        self
+    }
+
+    /// Read the fields of a nested struct from keys prefixed with the name of the field holding
+    /// it and `separator`, e.g. `filter.limit` with `.`. Bracketed keys such as `filter[limit]`
+    /// are always recognized. Without prefixed keys, nested structs read their fields as they are.
+    ///
+    /// Without a separator, the default, a `.` is part of the key, so a field renamed
+    /// `utm.source` reads `utm.source=x`. With one, a key naming a field exactly is still read by
+    /// that field, rather than as `source` nested in a field `utm`, whatever the order the
+    /// fields are declared in. A separator escaped as `\.`, or percent-encoded as `%2E`, is
+    /// always literal, e.g. in the keys given to a map.
+    pub fn nesting_separator(mut self, separator: char) -> Self {
+        self.nesting_separator = Some(separator);
+        self
+    }
+
+    /// The encoding of bytes. Base64 is read in either alphabet, with or without padding, so
+    /// both base64 encodings read the output of both.
+    pub fn bytes_encoding(mut self, encoding: BytesEncoding) -> Self {
+        self.bytes_encoding = encoding;
+        self
+    }
+
+    /// Transform every input key before it is matched against fields.
+    pub fn key_transform(mut self, transform: KeyTransform) -> Self {
+        self.key_transform = Some(transform);
+        self
+    }
+
+    /// The character splitting the value of a tuple struct, tuple or fixed size array into its
+    /// fields, `,` by default. Repeated keys, e.g. `center=52.1&center=13.4`, are read as the
+    /// fields as they are.
+    pub fn tuple_delimiter(mut self, delimiter: char) -> Self {
+        self.tuple_delimiter = delimiter;
+        self
+    }
+
+    /// Pass the values of `key` through `decoder` before they are deserialized, e.g. to undo a
+    /// second layer of percent-encoding or to decode a base32 cursor. It runs on the decoded
+    /// value, after [`trim_values`](Self::trim_values), and is matched against the key as
+    /// transformed by [`key_transform`](Self::key_transform). Decoders registered for the same
+    /// key run in order. An error of the decoder is returned with the key in its message.
+    pub fn value_decoder(mut self, key: &str, decoder: Box<ValueDecoder>) -> Self {
+        self.value_decoders
+            .push((key.to_string(), Arc::from(decoder)));
+        self
+    }
+
+    /// Drop the pairs whose key `filter` rejects while the input is parsed, so that the target
+    /// type never sees them, e.g. to strip `debug` or `internal_*` parameters at a gateway. The
+    /// filter is matched against the whole decoded key, after
+    /// [`key_transform`](Self::key_transform). Dropped pairs still count toward
+    /// [`max_pairs`](Self::max_pairs) and [`max_value_len`](Self::max_value_len).
+    pub fn key_filter(mut self, filter: KeyFilter) -> Self {
+        self.key_filter = Some(filter);
+        self
+    }
+
+    /// Log the whole value which failed to deserialize instead of its first 16 characters.
+    #[cfg(feature = "tracing")]
+    pub fn trace_full_values(mut self, full: bool) -> Self {
+        self.trace_full_values = full;
+        self
+    }
+}
+
+/// Which keys [`key_filter`](ParseOptions::key_filter) keeps. A key is kept when it matches the
+/// allow-list, if there is one, does not match the deny-list and is not longer than the maximum
+/// length. Patterns ending in `*` match every key starting with the rest, e.g. `internal_*`.
+#[derive(Debug, Clone, Default)]
+pub struct KeyFilter {
+    allow: Vec<String>,
+    deny: Vec<String>,
+    max_key_len: Option<usize>,
+    pub(crate) record: bool,
+}
+
+impl KeyFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep only the keys matching one of `patterns`.
+    pub fn allow(mut self, patterns: &[&str]) -> Self {
+        self.allow = patterns.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    /// Drop the keys matching one of `patterns`, even when they are allowed.
+    pub fn deny(mut self, patterns: &[&str]) -> Self {
+        self.deny = patterns.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    /// Drop the keys longer than `max` bytes.
+    pub fn max_key_len(mut self, max: usize) -> Self {
+        self.max_key_len = Some(max);
+        self
+    }
+
+    /// Keep the dropped keys, with their number of values, for
+    /// [`Deserializer::finish`](crate::Deserializer::finish) to return after the unused ones.
+    /// They are dropped silently by default.
+    pub fn record(mut self, record: bool) -> Self {
+        self.record = record;
+        self
+    }
+
+    pub(crate) fn keeps(&self, key: &str) -> bool {
+        self.max_key_len.is_none_or(|max| key.len() <= max)
+            && (self.allow.is_empty() || self.allow.iter().any(|p| matches_pattern(p, key)))
+            && !self.deny.iter().any(|p| matches_pattern(p, key))
+    }
+}
+
+// A key pattern, matching a key by prefix when it ends in `*`.
+fn matches_pattern(pattern: &str, key: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => key.starts_with(prefix),
+        None => key == pattern,
+    }
+}
+
+/// How [`QueryMap::semantic_eq`](crate::query_map::QueryMap::semantic_eq) compares query
+/// strings. Keys are compared in any order and values once decoded, so `b=2&a=%41` equals
+/// `a=A&b=2`. The repeated values of a key are compared in order by default.
+#[derive(Debug, Clone, Default)]
+pub struct EqOptions {
+    pub(crate) unordered_values: bool,
+    pub(crate) ignore_keys: Vec<String>,
+}
+
+impl EqOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare the repeated values of a key in any order, so `id=1&id=2` equals `id=2&id=1`.
+    pub fn unordered_values(mut self, unordered: bool) -> Self {
+        self.unordered_values = unordered;
+        self
+    }
+
+    /// Keys left out of the comparison, e.g. tracking parameters. A key ending in `*` leaves
+    /// out every key starting with the rest, e.g. `utm_*`.
+    pub fn ignore_keys(mut self, keys: &[&str]) -> Self {
+        self.ignore_keys = keys.iter().map(|k| k.to_string()).collect();
+        self
+    }
+
+    pub(crate) fn ignores(&self, key: &str) -> bool {
+        self.ignore_keys
+            .iter()
+            .any(|ignored| matches_pattern(ignored, key))
+    }
+}
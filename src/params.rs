@@ -0,0 +1,235 @@
+//! Parameter types most APIs declare, ready to be `#[serde(flatten)]`ed into query structs.
+//!
+//! ```
+//! use nb_serde_query::{from_str, params::Pagination};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct ListUsers {
+//!     q: Option<String>,
+//!     #[serde(flatten)]
+//!     page: Pagination,
+//! }
+//!
+//! let v: ListUsers = from_str("q=ann&page=3&per_page=50").unwrap();
+//! assert_eq!((v.page.limit(), v.page.offset()), (50, 100));
+//! ```
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::fmt;
+
+/// The page size used when none is requested, and the largest one allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PageLimits {
+    pub default: u32,
+    pub max: u32,
+}
+
+impl PageLimits {
+    pub const fn new(default: u32, max: u32) -> Self {
+        Self { default, max }
+    }
+
+    /// The requested page size, or the default, capped at `max` and at least 1.
+    pub fn clamp(&self, requested: Option<u32>) -> u32 {
+        requested.unwrap_or(self.default).min(self.max).max(1)
+    }
+}
+
+/// 20 items per page, at most 100.
+impl Default for PageLimits {
+    fn default() -> Self {
+        Self::new(20, 100)
+    }
+}
+
+/// Page based pagination, `page=3&per_page=50`. Pages are numbered from 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Pagination {
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+
+impl Pagination {
+    /// The requested page, where a missing page or page 0 is the first.
+    pub fn page(&self) -> u32 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    /// The page size within the [default limits](PageLimits::default).
+    pub fn limit(&self) -> u32 {
+        self.limit_with(&PageLimits::default())
+    }
+
+    pub fn limit_with(&self, limits: &PageLimits) -> u32 {
+        limits.clamp(self.per_page)
+    }
+
+    /// The number of items before the requested page with the
+    /// [default limits](PageLimits::default).
+    pub fn offset(&self) -> u64 {
+        self.offset_with(&PageLimits::default())
+    }
+
+    pub fn offset_with(&self, limits: &PageLimits) -> u64 {
+        (self.page() as u64 - 1) * self.limit_with(limits) as u64
+    }
+}
+
+/// Cursor based pagination, `cursor=abc&limit=50`. A missing cursor asks for the first page.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CursorPage {
+    // a flattened cursor such as `123` is buffered as a number
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+impl CursorPage {
+    /// The page size within the [default limits](PageLimits::default).
+    pub fn limit(&self) -> u32 {
+        self.limit_with(&PageLimits::default())
+    }
+
+    pub fn limit_with(&self, limits: &PageLimits) -> u32 {
+        limits.clamp(self.limit)
+    }
+}
+
+fn string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct CursorVisitor;
+
+    impl<'de> de::Visitor<'de> for CursorVisitor {
+        type Value = Option<String>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a cursor")
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(self)
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+            Ok(Some(v.to_string()))
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+            Ok(Some(v.to_string()))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+            Ok(Some(v.to_string()))
+        }
+
+        fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+            Ok(Some(v.to_string()))
+        }
+
+        fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+            Ok(Some(v.to_string()))
+        }
+    }
+
+    deserializer.deserialize_option(CursorVisitor)
+}
+
+/// An inclusive range of dates or times, `from=2024-01-01&to=2024-01-31`. Either end may be
+/// left open.
+#[cfg(feature = "chrono")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DateRange<T = chrono::NaiveDate> {
+    pub from: Option<T>,
+    pub to: Option<T>,
+}
+
+#[cfg(feature = "chrono")]
+impl<T: PartialOrd> DateRange<T> {
+    pub fn contains(&self, v: &T) -> bool {
+        self.from.as_ref().is_none_or(|from| from <= v) && self.to.as_ref().is_none_or(|to| v <= to)
+    }
+
+    /// Whether `from` is not after `to`. A range open at either end is always ordered.
+    pub fn is_ordered(&self) -> bool {
+        match (&self.from, &self.to) {
+            (Some(from), Some(to)) => from <= to,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pagination_clamping() {
+        let p = |page, per_page| Pagination { page, per_page };
+        assert_eq!(p(None, None).page(), 1);
+        assert_eq!((p(None, None).limit(), p(None, None).offset()), (20, 0));
+        assert_eq!((p(Some(0), None).page(), p(Some(0), None).offset()), (1, 0));
+        assert_eq!(
+            (p(Some(3), Some(50)).limit(), p(Some(3), Some(50)).offset()),
+            (50, 100)
+        );
+        assert_eq!(p(Some(2), Some(1000)).limit(), 100);
+        assert_eq!(p(Some(2), Some(1000)).offset(), 100);
+        assert_eq!(p(None, Some(0)).limit(), 1);
+        assert_eq!(
+            p(Some(u32::MAX), Some(u32::MAX)).offset_with(&PageLimits::new(10, u32::MAX)),
+            (u32::MAX as u64 - 1) * u32::MAX as u64
+        );
+
+        let limits = PageLimits::new(10, 25);
+        assert_eq!(p(Some(2), None).limit_with(&limits), 10);
+        assert_eq!(p(Some(2), None).offset_with(&limits), 10);
+        assert_eq!(p(Some(2), Some(30)).offset_with(&limits), 25);
+        assert_eq!(PageLimits::new(50, 0).clamp(None), 1);
+    }
+
+    #[test]
+    fn test_cursor_clamping() {
+        let c = CursorPage {
+            cursor: None,
+            limit: Some(500),
+        };
+        assert_eq!(c.limit(), 100);
+        assert_eq!(c.limit_with(&PageLimits::new(10, 1000)), 500);
+        assert_eq!(CursorPage::default().limit(), 20);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_date_range() {
+        use chrono::NaiveDate;
+
+        let d = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let r = DateRange {
+            from: Some(d(10)),
+            to: Some(d(20)),
+        };
+        assert!(r.contains(&d(10)) && r.contains(&d(20)));
+        assert!(!r.contains(&d(9)) && !r.contains(&d(21)));
+        assert!(r.is_ordered());
+        let open = DateRange {
+            from: None,
+            to: Some(d(5)),
+        };
+        assert!(open.contains(&d(1)) && !open.contains(&d(6)));
+        assert!(DateRange::<NaiveDate>::default().contains(&d(1)));
+        assert!(!DateRange {
+            from: Some(d(2)),
+            to: Some(d(1))
+        }
+        .is_ordered());
+    }
+}
@@ -0,0 +1,34 @@
+//! The [`Query`] extractor for poem; see [`QueryConfig`] for how rejections are answered.
+use crate::extract::extract_or_reject;
+pub use crate::extract::QueryConfig;
+use poem::{http::StatusCode, FromRequest, Request, RequestBody, Response};
+use serde::Deserialize;
+
+/// Deserializes `T` from the query string with the [`QueryConfig`] registered with
+/// `EndpointExt::data`, or the default one.
+#[derive(Debug)]
+pub struct Query<T>(pub T);
+
+impl<'a, T> FromRequest<'a> for Query<T>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> poem::Result<Self> {
+        extract_or_reject(req.uri().query(), req.data::<QueryConfig>())
+            .map(Query)
+            .map_err(|(_, rejection)| {
+                let status =
+                    StatusCode::from_u16(rejection.status).unwrap_or(StatusCode::BAD_REQUEST);
+                if rejection.json {
+                    poem::Error::from_response(
+                        Response::builder()
+                            .status(status)
+                            .content_type("application/json")
+                            .body(rejection.body),
+                    )
+                } else {
+                    poem::Error::from_string(rejection.body, status)
+                }
+            })
+    }
+}
@@ -0,0 +1,832 @@
+use crate::{
+    error::{Error, LimitKind},
+    options::{
+        Duplicates, EmptyValue, EqOptions, MapDuplicateKeys, ParseOptions, SerializerOptions,
+    },
+    parse_value, to_string, to_string_with,
+    utils::{
+        decode_component, decode_key, encode_component, join_canonical, join_pairs,
+        split_pairs_with, split_segments,
+    },
+};
+use indexmap::IndexMap;
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+use smallvec::{smallvec, SmallVec};
+use std::{borrow::Cow, collections::HashMap, error::Error as StdError, str::FromStr};
+
+/// Parsed query string giving dynamic access to its values.
+///
+/// Keys keep the order they first appeared in, repeated values keep their relative order.
+///
+/// Parsed with [`ParseOptions::raw_values`], the map holds values as they appeared in the
+/// input and writes them back unchanged, including values added to it later.
+#[derive(Debug, Clone, Default)]
+pub struct QueryMap {
+    pub(crate) entries: IndexMap<String, Vec<String>>,
+    // whether the values are still percent-encoded
+    raw: bool,
+    // how the serializer wrote the keys and values it encoded differently than
+    // `encode_component`, e.g. the `,` it leaves as it is in a `CommaSeparated`
+    written: Written,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Written {
+    keys: HashMap<String, String>,
+    values: HashMap<String, Vec<(String, String)>>,
+}
+
+// How the pairs are written is not part of what they are.
+impl PartialEq for QueryMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries && self.raw == other.raw
+    }
+}
+
+impl Eq for QueryMap {}
+
+impl QueryMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        Self::parse_with(s, &ParseOptions::default())
+    }
+
+    /// The pairs `value` serializes to with the default options. The map is displayed as
+    /// [`to_string`] writes `value`.
+    pub fn from_struct<T>(value: &T) -> Result<Self, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Self::from_written(&to_string(value)?, &SerializerOptions::default())
+    }
+
+    /// The pairs `value` serializes to with `options`, e.g. with
+    /// [`MapDuplicateKeys::LastWins`] for maps holding overrides.
+    pub fn from_struct_with<T>(value: &T, options: &SerializerOptions) -> Result<Self, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Self::from_written(&to_string_with(value, options)?, options)
+    }
+
+    // The pairs of `s` as written with `options`, remembering the ones written differently than
+    // they would be encoded.
+    fn from_written(s: &str, options: &SerializerOptions) -> Result<Self, Error> {
+        let separators = [options.pair_separator];
+        let mut map = Self::parse_with(
+            s,
+            &ParseOptions::new()
+                .pair_separator(options.pair_separator)
+                .kv_separator(options.kv_separator),
+        )?;
+        for pair in split_pairs_with(s, &separators, options.kv_separator, false) {
+            let (written_k, written_v) = pair?;
+            let k = decode_component(written_k)?;
+            let v = decode_component(written_v)?;
+            if encode_component(&k) != written_k {
+                (map.written.keys).insert(k.to_string(), written_k.to_string());
+            }
+            if encode_component(&v) != written_v {
+                (map.written.values.entry(k.into_owned()).or_default())
+                    .push((v.into_owned(), written_v.to_string()));
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn parse_with(s: &str, options: &ParseOptions) -> Result<Self, Error> {
+        let mut map = Self::new();
+        map.reparse_with(s, options)?;
+        Ok(map)
+    }
+
+    /// Replace the pairs with those of `s`, keeping the allocated capacity. Unlike
+    /// [`Deserializer::reset`](crate::Deserializer::reset), `s` need not outlive the map, so
+    /// one map can read a new `String` per request and
+    /// [`deserialize_into`](Self::deserialize_into) each.
+    pub fn reparse(&mut self, s: &str) -> Result<(), Error> {
+        self.reparse_with(s, &ParseOptions::default())
+    }
+
+    /// Like [`reparse`](Self::reparse) with `options`. The map is left empty if `s` is rejected.
+    pub fn reparse_with(&mut self, s: &str, options: &ParseOptions) -> Result<(), Error> {
+        self.clear();
+        self.raw = options.raw_values;
+        let pairs = parse_pairs(s, options)?;
+        self.entries.extend(pairs.into_iter().map(|(k, vals)| {
+            (
+                k.into_owned(),
+                vals.into_iter().map(Cow::into_owned).collect(),
+            )
+        }));
+        Ok(())
+    }
+
+    /// The first value of `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .get(key)
+            .and_then(|vals| vals.first())
+            .map(String::as_str)
+    }
+
+    /// The first value of `key` percent-encoded, as it appeared in the input if the map was
+    /// parsed with [`ParseOptions::raw_values`].
+    pub fn get_raw(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.get(key).map(|v| self.encode_value(v))
+    }
+
+    fn encode_value<'v>(&self, v: &'v str) -> Cow<'v, str> {
+        if self.raw {
+            Cow::Borrowed(v)
+        } else {
+            encode_component(v)
+        }
+    }
+
+    /// All values of `key`, empty if the key is absent.
+    pub fn get_all(&self, key: &str) -> &[String] {
+        self.entries.get(key).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Parse the first value of `key`, `None` if the key is absent, or if its value is empty
+    /// and `T` cannot be parsed from it, like an `Option<T>` field reads `key=`. An invalid
+    /// value is reported as [`from_str`](crate::from_str) reports it for a field of type `T`.
+    pub fn get_parsed<T>(&self, key: &str) -> Result<Option<T>, Error>
+    where
+        T: FromStr,
+        T::Err: StdError + 'static,
+    {
+        match self.get(key) {
+            None => Ok(None),
+            Some(v) => match parse_as(v, key) {
+                Err(_) if v.is_empty() => Ok(None),
+                res => res.map(Some),
+            },
+        }
+    }
+
+    /// Parse the first value of `key`, `default` if the key is absent.
+    pub fn get_or<T>(&self, key: &str, default: T) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: StdError + 'static,
+    {
+        Ok(self.get_parsed(key)?.unwrap_or(default))
+    }
+
+    /// Parse every value of `key`, empty if the key is absent.
+    pub fn get_all_parsed<T>(&self, key: &str) -> Result<Vec<T>, Error>
+    where
+        T: FromStr,
+        T::Err: StdError + 'static,
+    {
+        self.get_all(key).iter().map(|v| parse_as(v, key)).collect()
+    }
+
+    /// Parse the first value of `key`, which must be present: an absent key is a
+    /// [`MissingField`](crate::error::ErrorKind::MissingField) error naming it.
+    pub fn require<T>(&self, key: &str) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: StdError + 'static,
+    {
+        match self.get(key) {
+            Some(v) => parse_as(v, key),
+            None => Err(Error::missing(key)),
+        }
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// The number of distinct keys.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove all pairs, keeping the allocated capacity.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.written = Written::default();
+    }
+
+    /// Iterate over all pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .flat_map(|(k, vals)| vals.iter().map(move |v| (k.as_str(), v.as_str())))
+    }
+
+    /// Replace all values of `key` with `value`, returning the previous values.
+    pub fn insert(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Option<Vec<String>> {
+        self.entries.insert(key.into(), vec![value.into()])
+    }
+
+    /// Replace all values of `key` with `value`. An existing key keeps its position.
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.insert(key, value);
+    }
+
+    /// Add `value` to the values of `key`.
+    pub fn append(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.entries
+            .entry(key.into())
+            .or_default()
+            .push(value.into());
+    }
+
+    /// Remove `key`, keeping the order of the remaining keys.
+    pub fn remove(&mut self, key: &str) -> Option<Vec<String>> {
+        self.entries.shift_remove(key)
+    }
+
+    /// Replace the values of every key of `other` with its values. Keys only in `self` keep
+    /// their values and position, keys only in `other` are added at the end.
+    pub fn merge(mut self, other: QueryMap) -> Self {
+        for (k, vals) in other.entries {
+            self.entries.insert(k, vals);
+        }
+        self
+    }
+
+    /// Merge `other` into `self`, treating keys in both as `policy` says: `Allow` appends the
+    /// values of `other`, `LastWins` replaces them like [`merge`](Self::merge), and `Error`
+    /// rejects the first such key.
+    pub fn merge_with(mut self, other: QueryMap, policy: MapDuplicateKeys) -> Result<Self, Error> {
+        for (k, vals) in other.entries {
+            match (self.entries.get_mut(&k), policy) {
+                (Some(existing), MapDuplicateKeys::Allow) => existing.extend(vals),
+                (Some(_), MapDuplicateKeys::Error) => {
+                    return Err(Error::new(format!("duplicate key `{}` in merge", k), None))
+                }
+                _ => {
+                    self.entries.insert(k, vals);
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// The pairs percent-encoded and joined. A map built by
+    /// [`from_struct`](Self::from_struct) writes the pairs it holds from `value` as
+    /// [`to_string`] wrote them.
+    pub fn to_query_string(&self) -> String {
+        let mut out = String::new();
+        for (k, v) in self.iter() {
+            if !out.is_empty() {
+                out.push('&');
+            }
+            match self.written.keys.get(k) {
+                Some(written) => out.push_str(written),
+                None => out.push_str(&encode_component(k)),
+            }
+            out.push('=');
+            let written =
+                (self.written.values.get(k)).and_then(|vals| vals.iter().find(|(val, _)| val == v));
+            match written {
+                Some((_, written)) => out.push_str(written),
+                None => out.push_str(&self.encode_value(v)),
+            }
+        }
+        out
+    }
+
+    /// The canonical query string of [`to_canonical_string`](crate::to_canonical_string).
+    pub fn to_canonical_string(&self) -> String {
+        join_canonical(
+            self.iter()
+                .map(|(k, v)| {
+                    (
+                        encode_component(k).into_owned(),
+                        self.encode_value(v).into_owned(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// Whether `self` and `other` hold the same pairs under `opts`, e.g. to tell whether two
+    /// requests can share a cache entry. See [`normalized`](Self::normalized).
+    pub fn semantic_eq(&self, other: &QueryMap, opts: &EqOptions) -> bool {
+        self.normalized(opts) == other.normalized(opts)
+    }
+
+    /// The query string of the pairs kept by `opts`, sorted by key, with the values of each
+    /// key sorted too if `opts` compares them in any order. Maps equal under `opts` have the
+    /// same normalized form, so it can serve as a cache key. Values are encoded the same way
+    /// however they appeared in the input.
+    pub fn normalized(&self, opts: &EqOptions) -> String {
+        let mut entries: Vec<(&str, Vec<Cow<'_, str>>)> = self
+            .entries
+            .iter()
+            .filter(|(k, _)| !opts.ignores(k))
+            .map(|(k, vals)| {
+                let mut vals: Vec<_> = vals.iter().map(|v| self.decode_value(v)).collect();
+                if opts.unordered_values {
+                    vals.sort();
+                }
+                (k.as_str(), vals)
+            })
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        join_pairs(
+            entries
+                .iter()
+                .flat_map(|(k, vals)| vals.iter().map(move |v| (*k, v))),
+        )
+    }
+
+    // The value decoded, if the map holds it still encoded. A value which isn't valid
+    // percent-encoding is taken as it is.
+    fn decode_value<'v>(&self, v: &'v str) -> Cow<'v, str> {
+        if self.raw {
+            decode_component(v).unwrap_or(Cow::Borrowed(v))
+        } else {
+            Cow::Borrowed(v)
+        }
+    }
+
+    /// Deserialize `T` from the pairs, as if from the query string they were parsed from.
+    pub fn deserialize_into<'de, T>(&'de self) -> Result<T, Error>
+    where
+        T: Deserialize<'de>,
+    {
+        T::deserialize(self.into_deserializer())
+    }
+}
+
+/// The query string of [`to_query_string`](QueryMap::to_query_string).
+impl std::fmt::Display for QueryMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_query_string())
+    }
+}
+
+impl FromStr for QueryMap {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+// Parse a value of `key` as `T`, with the error of the serde path.
+fn parse_as<T>(v: &str, key: &str) -> Result<T, Error>
+where
+    T: FromStr,
+    T::Err: StdError + 'static,
+{
+    parse_value(v, v, key, std::any::type_name::<T>())
+}
+
+/// The values of one key. Nearly every key has a single value, which is stored inline.
+pub(crate) type Values<'a> = SmallVec<[Cow<'a, str>; 1]>;
+
+/// Split `s` into its pairs, grouped by key. Values without escapes borrow from `s`.
+pub(crate) fn parse_pairs<'a>(
+    s: &'a str,
+    options: &ParseOptions,
+) -> Result<IndexMap<Cow<'a, str>, Values<'a>>, Error> {
+    let mut m = IndexMap::new();
+    parse_pairs_into(s, options, &mut m, &mut IndexMap::new())?;
+    Ok(m)
+}
+
+/// Like [`parse_pairs`], but reuses the allocation of `m`, which is cleared first.
+///
+/// A leading `?`, as in the query part of a URL, is skipped, so `""` and `"?"` both have no pairs,
+/// and a fragment, from `#` on, is ignored.
+pub(crate) fn parse_pairs_into<'a>(
+    s: &'a str,
+    options: &ParseOptions,
+    m: &mut IndexMap<Cow<'a, str>, Values<'a>>,
+    filtered: &mut IndexMap<Cow<'a, str>, usize>,
+) -> Result<(), Error> {
+    m.clear();
+    filtered.clear();
+    let s = s.strip_prefix('?').unwrap_or(s);
+    // the query string ends at a fragment, as in a URL
+    let s = match s.find('#') {
+        Some(i) if options.deny_fragment => {
+            return Err(Error::new(
+                format!(
+                    "unexpected fragment `{}`, `#` in values is written `%23`",
+                    &s[i..]
+                ),
+                None,
+            ))
+        }
+        Some(i) => &s[..i],
+        None => s,
+    };
+    if options.separators.contains(&options.kv_separator) {
+        return Err(Error::new(
+            format!(
+                "`{}` cannot separate both pairs and keys from values",
+                options.kv_separator
+            ),
+            None,
+        ));
+    }
+    if let Some(max) = options.max_input_len.filter(|max| s.len() > *max) {
+        return Err(Error::limit_exceeded(LimitKind::InputLength, max, None));
+    }
+    if let Some(max) = options.max_pairs {
+        if split_segments(s, &options.separators).count() > max {
+            return Err(Error::limit_exceeded(LimitKind::Pairs, max, None));
+        }
+    }
+    for pair in split_pairs_with(
+        s,
+        &options.separators,
+        options.kv_separator,
+        options.bare_keys,
+    ) {
+        let (key, val) = pair?;
+        if let Some(max) = options.max_value_len.filter(|max| val.len() > *max) {
+            let key = Some(key.to_string());
+            return Err(Error::limit_exceeded(LimitKind::ValueLength, max, key));
+        }
+        // decoded only after splitting, and one value at a time, so an encoded `&` or `=`
+        // stays part of the value it is in
+        let val = if options.raw_values {
+            Cow::Borrowed(val)
+        } else {
+            decode_component(val)?
+        };
+        let key = decode_key(key, options.nesting_separator)?;
+        insert_pair(key, val, options, m, filtered)?;
+    }
+    Ok(())
+}
+
+/// Add one decoded pair to `m`, applying the value and key options of `options`. The keys of
+/// pairs dropped by the key filter are counted in `filtered` when it records them.
+pub(crate) fn insert_pair<'a>(
+    key: Cow<'a, str>,
+    mut val: Cow<'a, str>,
+    options: &ParseOptions,
+    m: &mut IndexMap<Cow<'a, str>, Values<'a>>,
+    filtered: &mut IndexMap<Cow<'a, str>, usize>,
+) -> Result<(), Error> {
+    let key = match &options.key_transform {
+        Some(transform) => Cow::Owned(transform.apply(&key)),
+        None => key,
+    };
+    if let Some(filter) = options.key_filter.as_ref().filter(|f| !f.keeps(&key)) {
+        if filter.record {
+            *filtered.entry(key).or_default() += 1;
+        }
+        return Ok(());
+    }
+    if options.trim_values {
+        val = match val {
+            Cow::Borrowed(v) => Cow::Borrowed(v.trim()),
+            Cow::Owned(v) if v.trim().len() != v.len() => Cow::Owned(v.trim().to_string()),
+            v => v,
+        };
+    }
+    for (_, decoder) in options.value_decoders.iter().filter(|(k, _)| *k == key) {
+        val = Cow::Owned(decoder(&val).map_err(|mut err| {
+            err.message = format!("invalid value for key `{}`: {}", key, err.message);
+            err
+        })?);
+    }
+    if val.is_empty() {
+        match options.empty_value {
+            EmptyValue::Keep => {}
+            EmptyValue::AsNone => return Ok(()),
+            EmptyValue::Error => {
+                return Err(Error::new(format!("empty value for key `{}`", key), None))
+            }
+        }
+    }
+    if options.duplicates == Duplicates::Error && m.contains_key(&key) {
+        return Err(Error::new(format!("duplicate key `{}`", key), None));
+    }
+    if options.deny_duplicate_values && m.get(&key).is_some_and(|vals| vals.contains(&val)) {
+        return Err(Error::new(
+            format!("duplicate value `{}` for key `{}`", val, key),
+            None,
+        ));
+    }
+    if let Some(max) = options.max_values_per_key {
+        if options.duplicates == Duplicates::Collect && m.get(&key).is_some_and(|v| v.len() >= max)
+        {
+            let key = Some(key.into_owned());
+            return Err(Error::limit_exceeded(LimitKind::ValuesPerKey, max, key));
+        }
+    }
+    let vals = m.entry(key).or_default();
+    match options.duplicates {
+        Duplicates::Collect | Duplicates::Error => vals.push(val),
+        Duplicates::First if vals.is_empty() => vals.push(val),
+        Duplicates::First => {}
+        Duplicates::Last => *vals = smallvec![val],
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::ErrorKind, from_str};
+
+    #[test]
+    fn test_typed_getters() {
+        let m = QueryMap::parse("limit=10&name=test&ids=1&ids=2&bad=x").unwrap();
+        assert_eq!(m.len(), 4);
+        assert_eq!(m.get("name"), Some("test"));
+        assert_eq!(m.get("missing"), None);
+        assert_eq!(m.get_parsed::<i32>("limit").unwrap(), Some(10));
+        assert_eq!(m.get_parsed::<i32>("missing").unwrap(), None);
+        let err = m.get_parsed::<i32>("bad").unwrap_err();
+        assert_eq!(err.message, "invalid value `x` for key `bad`, expected i32");
+        assert_eq!(m.get_all("ids"), ["1", "2"]);
+        assert!(m.get_all("missing").is_empty());
+        assert!(m.contains("ids"));
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Limits {
+        limit: Option<u8>,
+        ids: Option<Vec<u32>>,
+    }
+
+    #[test]
+    fn test_typed_defaults() {
+        let m = QueryMap::parse("limit=10&ids=1&ids=2&bad=x&big=300&bad_ids=1&bad_ids=-2").unwrap();
+        assert_eq!(m.get_or("limit", 20u8).unwrap(), 10);
+        assert_eq!(m.get_or("missing", 20u8).unwrap(), 20);
+        assert_eq!(m.get_all_parsed::<u32>("ids").unwrap(), [1, 2]);
+        assert!(m.get_all_parsed::<u32>("missing").unwrap().is_empty());
+        assert_eq!(m.require::<u8>("limit").unwrap(), 10);
+        let err = m.require::<u8>("missing").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::MissingField {
+                field: "missing".to_string()
+            }
+        );
+        assert_eq!(err.message, "missing field `missing`");
+
+        // invalid values are reported as when reading a field
+        let same_as_field = |err: Error, s: &str| {
+            let expected = from_str::<Limits>(s).unwrap_err();
+            assert_eq!(
+                (&err.kind, &err.message),
+                (&expected.kind, &expected.message)
+            );
+        };
+        assert_eq!(
+            m.get_or("big", 0u8).unwrap_err().kind,
+            ErrorKind::OutOfRange
+        );
+        let err = m.require::<u8>("bad").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidNumber);
+        assert_eq!(
+            err.to_string(),
+            "invalid value `x` for key `bad`, expected u8: invalid digit found in string"
+        );
+        let err = m.get_all_parsed::<u32>("bad_ids").unwrap_err();
+        assert_eq!(
+            err.message,
+            "invalid value `-2` for key `bad_ids`, expected u32"
+        );
+
+        for s in ["limit=x", "limit=300", "ids=1&ids=y"] {
+            let m = QueryMap::parse(s).unwrap();
+            let err = match m.contains("ids") {
+                true => m.get_all_parsed::<u32>("ids").unwrap_err(),
+                false => m.get_or("limit", 0u8).unwrap_err(),
+            };
+            same_as_field(err, s);
+        }
+
+        // an empty value is no value, unless it is required
+        let m = QueryMap::parse("limit=").unwrap();
+        assert_eq!(m.get_or("limit", 20u8).unwrap(), 20);
+        assert_eq!(m.get_or("limit", "x".to_string()).unwrap(), "");
+        assert!(from_str::<Limits>("limit=").unwrap().limit.is_none());
+        assert_eq!(
+            m.require::<u8>("limit").unwrap_err().message,
+            "invalid value `` for key `limit`, expected u8"
+        );
+
+        // other types name what they expected
+        let m = QueryMap::parse("host=x").unwrap();
+        let err = m.get_or("host", std::net::Ipv4Addr::LOCALHOST).unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::InvalidValue { ref key, ref expected }
+                if key == "host" && expected.ends_with("Ipv4Addr")
+        ));
+
+        // bools and chars are reported as fields of these types are
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Flags {
+            on: Option<bool>,
+            sep: Option<char>,
+        }
+        let m = QueryMap::parse("on=maybe&sep=ab").unwrap();
+        for (input, key) in [("on=maybe", "on"), ("sep=ab", "sep")] {
+            let expected = from_str::<Flags>(input).unwrap_err();
+            let err = match key {
+                "on" => m.get_parsed::<bool>(key).unwrap_err(),
+                _ => m.get_parsed::<char>(key).unwrap_err(),
+            };
+            assert_eq!(err.kind, expected.kind);
+            assert_eq!(err.message, expected.message);
+        }
+        assert_eq!(
+            from_str::<Flags>("on=maybe").unwrap_err().message,
+            "invalid value `maybe` for key `on`, expected bool"
+        );
+        assert_eq!(
+            from_str::<Flags>("sep=ab").unwrap_err().kind,
+            ErrorKind::InvalidValue {
+                key: "sep".to_string(),
+                expected: "char".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_mutation() {
+        let mut m = QueryMap::parse("a=1&b=2&a=3&c=4").unwrap();
+        assert_eq!(
+            m.iter().collect::<Vec<_>>(),
+            [("a", "1"), ("a", "3"), ("b", "2"), ("c", "4")]
+        );
+        assert_eq!(m.insert("b", "5"), Some(vec!["2".to_string()]));
+        m.append("c", "6");
+        m.append("d", "7");
+        assert_eq!(m.remove("a"), Some(vec!["1".to_string(), "3".to_string()]));
+        assert_eq!(m.remove("a"), None);
+        m.append("e", "x&y=z");
+        assert_eq!(m.to_query_string(), "b=5&c=4&c=6&d=7&e=x%26y%3Dz");
+        assert_eq!(QueryMap::parse(&m.to_query_string()).unwrap(), m);
+    }
+
+    #[test]
+    fn test_merge() {
+        let incoming = QueryMap::parse("q=rust&page_size=10&debug=1&tag=a&tag=b").unwrap();
+        let mut m = incoming.clone();
+        m.set("page_size", "100");
+        m.remove("debug");
+        m.append("tag", "c");
+        assert_eq!(
+            m.to_query_string(),
+            "q=rust&page_size=100&tag=a&tag=b&tag=c"
+        );
+
+        let overrides = QueryMap::parse("tag=x&page_size=100&sort=new").unwrap();
+        let m = incoming.merge(overrides);
+        assert_eq!(
+            m.to_query_string(),
+            "q=rust&page_size=100&debug=1&tag=x&sort=new"
+        );
+        assert_eq!(m.clone().merge(QueryMap::new()), m);
+        let s = m.to_query_string();
+        assert_eq!(QueryMap::parse(&s).unwrap().to_query_string(), s);
+    }
+
+    #[test]
+    fn test_merge_with() {
+        let base = QueryMap::parse("q=rust&tag=a").unwrap();
+        let other = QueryMap::parse("tag=b&page=2").unwrap();
+        let merged = |policy| base.clone().merge_with(other.clone(), policy);
+        assert_eq!(
+            merged(MapDuplicateKeys::Allow).unwrap().to_query_string(),
+            "q=rust&tag=a&tag=b&page=2"
+        );
+        assert_eq!(
+            merged(MapDuplicateKeys::LastWins).unwrap(),
+            base.clone().merge(other.clone())
+        );
+        assert_eq!(
+            merged(MapDuplicateKeys::Error).unwrap_err().message,
+            "duplicate key `tag` in merge"
+        );
+        assert!(base
+            .clone()
+            .merge_with(QueryMap::parse("page=2").unwrap(), MapDuplicateKeys::Error)
+            .is_ok());
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Search {
+        q: String,
+        page_size: u32,
+        tag: Vec<String>,
+    }
+
+    #[test]
+    fn test_typed_bridge() {
+        let v = Search {
+            q: "a b".to_string(),
+            page_size: 10,
+            tag: vec!["x".to_string(), "y".to_string()],
+        };
+        let mut m = QueryMap::from_struct(&v).unwrap();
+        assert_eq!(m.get_all("tag"), ["x", "y"]);
+        assert_eq!(m.get("q"), Some("a b"));
+        assert_eq!(m.deserialize_into::<Search>().unwrap(), v);
+
+        m.set("page_size", "100");
+        m.append("debug", "1");
+        let v: Search = m.deserialize_into().unwrap();
+        assert_eq!(v.page_size, 100);
+        assert_eq!(
+            m.to_query_string(),
+            "q=a%20b&page_size=100&tag=x&tag=y&debug=1"
+        );
+    }
+
+    #[test]
+    fn test_raw_values() {
+        let s = "path=%2Fa%2fb&q=a+b%c3%BC&q=%7E&e=";
+        let m = QueryMap::parse_with(s, &ParseOptions::new().raw_values(true)).unwrap();
+        assert_eq!(m.get("path"), Some("%2Fa%2fb"));
+        assert_eq!(m.get_raw("q").as_deref(), Some("a+b%c3%BC"));
+        assert_eq!(m.to_query_string(), s);
+
+        let m = QueryMap::parse(s).unwrap();
+        assert_eq!(m.get("path"), Some("/a/b"));
+        assert_eq!(m.get_raw("path").as_deref(), Some("%2Fa%2Fb"));
+        assert_eq!(m.get_raw("missing"), None);
+    }
+
+    #[test]
+    fn test_semantic_eq() {
+        let eq = |a: &str, b: &str, opts: &EqOptions| {
+            QueryMap::parse(a)
+                .unwrap()
+                .semantic_eq(&QueryMap::parse(b).unwrap(), opts)
+        };
+        let opts = EqOptions::new();
+        assert!(eq("b=2&a=1", "a=1&b=2", &opts));
+        assert!(eq("a=%41&q=x+y", "q=x%20y&a=A", &opts));
+        assert!(eq("id=1&x=0&id=2", "x=0&id=1&id=2", &opts));
+        assert!(!eq("id=1&id=2", "id=2&id=1", &opts));
+        assert!(!eq("a=1", "a=1&b=", &opts));
+        assert!(!eq("a=1", "a=2", &opts));
+        let unordered = EqOptions::new().unordered_values(true);
+        assert!(eq("id=1&id=2", "id=2&id=1", &unordered));
+        assert!(!eq("id=1&id=1", "id=1", &unordered));
+
+        let tracking = EqOptions::new().ignore_keys(&["utm_*", "fbclid"]);
+        assert!(eq(
+            "q=shoes&utm_source=mail&fbclid=x",
+            "utm_campaign=spring&q=shoes",
+            &tracking
+        ));
+        assert!(!eq("q=shoes&utm=1", "q=shoes", &tracking));
+
+        let m = QueryMap::parse("z=1&utm_source=a&b=%41&id=2&id=1").unwrap();
+        assert_eq!(m.normalized(&opts), "b=A&id=2&id=1&utm_source=a&z=1");
+        let opts = unordered.ignore_keys(&["utm_*"]);
+        assert_eq!(m.normalized(&opts), "b=A&id=1&id=2&z=1");
+        // still encoded values are compared decoded
+        let raw =
+            QueryMap::parse_with("b=%41&z=1&id=1&id=2", &ParseOptions::new().raw_values(true))
+                .unwrap();
+        assert_eq!(raw.normalized(&opts), m.normalized(&opts));
+        assert!(raw.semantic_eq(&m, &opts));
+    }
+
+    #[test]
+    fn test_reparse() {
+        let mut m = QueryMap::new();
+        for limit in [3, 7] {
+            // the input is dropped at the end of each iteration
+            let s = format!("limit={}&ids=1&ids=2", limit);
+            m.reparse(&s).unwrap();
+            let v: Limits = m.deserialize_into().unwrap();
+            assert_eq!(v.limit, Some(limit));
+            assert_eq!(v.ids, Some(vec![1, 2]));
+        }
+        assert_eq!(m.len(), 2);
+
+        m.reparse_with("a=%41", &ParseOptions::new().raw_values(true))
+            .unwrap();
+        assert_eq!(m.to_query_string(), "a=%41");
+        assert!(m.reparse("a=%zz").is_err());
+        assert!(m.is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+//! The [`Query`] extractor for salvo; see [`QueryConfig`] for how rejections are answered.
+pub use crate::extract::QueryConfig;
+use crate::extract::{extract_or_reject, Rejection};
+use salvo_core::{
+    extract::{Extractible, Metadata},
+    http::StatusCode,
+    writing::Text,
+    Request, Response, Scribe, Writer,
+};
+use serde::Deserialize;
+use std::fmt::Debug;
+
+/// Deserializes `T` from the query string with the [`QueryConfig`] inserted into the
+/// extensions of the request, e.g. by a hoop, or the default one.
+#[derive(Debug)]
+pub struct Query<T>(pub T);
+
+impl<'ex, T> Extractible<'ex> for Query<T>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    fn metadata() -> &'ex Metadata {
+        static METADATA: Metadata = Metadata::new("Query");
+        &METADATA
+    }
+
+    async fn extract(req: &'ex mut Request) -> Result<Self, impl Writer + Send + Debug + 'static> {
+        extract_or_reject(req.uri().query(), req.extensions().get::<QueryConfig>())
+            .map(Query)
+            .map_err(|(_, rejection)| rejection)
+    }
+}
+
+impl Scribe for Rejection {
+    fn render(self, res: &mut Response) {
+        res.status_code(StatusCode::from_u16(self.status).unwrap_or(StatusCode::BAD_REQUEST));
+        if self.json {
+            res.render(Text::Json(self.body));
+        } else {
+            res.render(Text::Plain(self.body));
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! `JsonSchema` impls of the wrapper types, so they are documented as the values they hold
+//! rather than as opaque strings.
+//!
+//! [`Array<T>`] is documented as a string holding a JSON array of `T`, [`Separated<T, SEP>`]
+//! as a string of `T`s joined by `SEP`, [`Raw`] as a string, and the timestamps of the `chrono`
+//! feature as integers.
+use crate::{Array, Raw, Separated};
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Metadata, Schema, SchemaObject},
+    JsonSchema,
+};
+use std::borrow::Cow;
+
+// Document `$ty` as `$target`, sharing its name so both refer to the same definition.
+macro_rules! forward_schema {
+    ([$($param:ident),*] $ty:ty => $target:ty) => {
+        impl<$($param: JsonSchema),*> JsonSchema for $ty {
+            fn is_referenceable() -> bool {
+                <$target>::is_referenceable()
+            }
+
+            fn schema_name() -> String {
+                <$target>::schema_name()
+            }
+
+            fn schema_id() -> Cow<'static, str> {
+                <$target>::schema_id()
+            }
+
+            fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+                <$target>::json_schema(gen)
+            }
+        }
+    };
+}
+
+// A string described by `description`.
+fn described_string(description: String) -> SchemaObject {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        metadata: Some(Box::new(Metadata {
+            description: Some(description),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Array<T> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        format!("Array_of_{}", T::schema_name())
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        Cow::Owned(format!("nb_serde_query::Array<{}>", T::schema_id()))
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            extensions: [("contentMediaType".to_string(), "application/json".into())]
+                .into_iter()
+                .collect(),
+            ..described_string(format!(
+                "A JSON array of {}, e.g. `[1,2]`",
+                T::schema_name()
+            ))
+        }
+        .into()
+    }
+}
+
+impl<T: JsonSchema, const SEP: char> JsonSchema for Separated<T, SEP> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        format!("Separated_{}_{}", T::schema_name(), SEP as u32)
+    }
+
+    fn schema_id() -> Cow<'static, str> {
+        Cow::Owned(format!(
+            "nb_serde_query::Separated<{}, {:?}>",
+            T::schema_id(),
+            SEP
+        ))
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        described_string(format!(
+            "{} values separated by `{}`",
+            T::schema_name(),
+            SEP
+        ))
+        .into()
+    }
+}
+
+forward_schema!([] Raw => String);
+#[cfg(feature = "chrono")]
+forward_schema!([] crate::chrono::UnixTimestamp => i64);
+#[cfg(feature = "chrono")]
+forward_schema!([] crate::chrono::UnixTimestampMillis => i64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_str, CommaSeparated};
+    use schemars::schema_for;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Params {
+        ids: Array<u32>,
+        tags: Option<Array<String>>,
+        sig: Raw,
+        #[serde(default)]
+        codes: CommaSeparated<u16>,
+        words: Option<Separated<String, ' '>>,
+    }
+
+    #[test]
+    fn test_schema() {
+        let schema = serde_json::to_value(schema_for!(Params)).unwrap();
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "ids": {
+                    "type": "string",
+                    "contentMediaType": "application/json",
+                    "description": "A JSON array of uint32, e.g. `[1,2]`"
+                },
+                "tags": {
+                    "type": ["string", "null"],
+                    "contentMediaType": "application/json",
+                    "description": "A JSON array of String, e.g. `[1,2]`"
+                },
+                "sig": {"type": "string"},
+                "codes": {
+                    "type": "string",
+                    "description": "uint16 values separated by `,`",
+                    "default": ""
+                },
+                "words": {
+                    "type": ["string", "null"],
+                    "description": "String values separated by ` `"
+                }
+            })
+        );
+        assert_eq!(schema["required"], json!(["ids", "sig"]));
+
+        // the documented string is what is read, a form-style array is not
+        let v: Params = from_str("ids=%5B1,2%5D&sig=x&codes=4,5&words=a%20b").unwrap();
+        assert_eq!(v.ids, Array(vec![1, 2]));
+        assert_eq!(v.codes, Separated(vec![4, 5]));
+        assert_eq!(
+            v.words,
+            Some(Separated(vec!["a".to_string(), "b".to_string()]))
+        );
+        assert!(from_str::<Params>("ids=1&ids=2&sig=x").is_err());
+    }
+}
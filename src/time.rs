@@ -0,0 +1,200 @@
+//! Date and time parameters of the `time` crate.
+//!
+//! `Date` and `Time` fields work as they are, through the string impls of `time`: `2024-01-01`
+//! and `08:30:00.5`. An `OffsetDateTime` field is written by them as `2024-02-01 12:00:00.0
+//! +00:00:00`; [`Rfc3339`] reads and writes RFC 3339 (`2024-02-01T12:00:00Z`) instead, as does
+//! `#[serde(with = "time::serde::rfc3339")]`. Other formats can be used with
+//! `#[serde(with = "...")]` and the `time::serde` macros.
+//!
+//! [`UnixTimestamp`] and [`UnixTimestampMillis`] read and write epoch seconds and milliseconds.
+use serde::{de::Error as DeError, Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::Deref;
+use time::OffsetDateTime;
+
+/// A point in time written in RFC 3339, e.g. `since=2024-02-01T12:00:00Z`. Offsets are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rfc3339(pub OffsetDateTime);
+
+/// A point in time written as seconds since the Unix epoch, e.g. `since=1704067200`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnixTimestamp(pub OffsetDateTime);
+
+/// A point in time written as milliseconds since the Unix epoch, e.g. `since=1704067200000`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnixTimestampMillis(pub OffsetDateTime);
+
+impl Deref for Rfc3339 {
+    type Target = OffsetDateTime;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Deref for UnixTimestamp {
+    type Target = OffsetDateTime;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Deref for UnixTimestampMillis {
+    type Target = OffsetDateTime;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Serialize for Rfc3339 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        time::serde::rfc3339::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rfc3339 {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        time::serde::rfc3339::deserialize(deserializer).map(Rfc3339)
+    }
+}
+
+impl Serialize for UnixTimestamp {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i64(self.0.unix_timestamp())
+    }
+}
+
+impl<'de> Deserialize<'de> for UnixTimestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let secs = i64::deserialize(deserializer)?;
+        OffsetDateTime::from_unix_timestamp(secs)
+            .map(UnixTimestamp)
+            .map_err(|_| D::Error::custom(format!("timestamp `{}` out of range", secs)))
+    }
+}
+
+impl Serialize for UnixTimestampMillis {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // in range of an i64 for every year `time` supports without `large-dates`
+        let millis = self.0.unix_timestamp_nanos() / 1_000_000;
+        serializer.serialize_i64(millis as i64)
+    }
+}
+
+impl<'de> Deserialize<'de> for UnixTimestampMillis {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let millis = i64::deserialize(deserializer)?;
+        OffsetDateTime::from_unix_timestamp_nanos(millis as i128 * 1_000_000)
+            .map(UnixTimestampMillis)
+            .map_err(|_| D::Error::custom(format!("timestamp `{}` out of range", millis)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::ErrorKind, from_str, to_string};
+    use time::{Date, Month, Time, UtcOffset};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Range {
+        from: Date,
+        to: Rfc3339,
+        at: Option<Time>,
+        #[serde(default, with = "time::serde::rfc3339::option")]
+        until: Option<OffsetDateTime>,
+        since: Option<UnixTimestamp>,
+        before: Option<UnixTimestampMillis>,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let v: Range = from_str("from=2024-01-01&to=2024-02-01T12:00:00Z").unwrap();
+        assert_eq!(
+            v.from,
+            Date::from_calendar_date(2024, Month::January, 1).unwrap()
+        );
+        assert_eq!(
+            *v.to,
+            Date::from_calendar_date(2024, Month::February, 1)
+                .unwrap()
+                .with_hms(12, 0, 0)
+                .unwrap()
+                .assume_utc()
+        );
+        assert_eq!((v.at, v.until), (None, None));
+        let s = to_string(&v).unwrap();
+        assert_eq!(s, "from=2024-01-01&to=2024-02-01T12%3A00%3A00Z");
+        assert_eq!(from_str::<Range>(&s).unwrap(), v);
+
+        let v: Range = from_str(
+            "from=2024-01-01&to=2024-02-01T12:00:00%2B08:00&at=08:30:00.5\
+             &until=2024-03-01T00:00:00.25-05:00&since=1704067200&before=1704067200123",
+        )
+        .unwrap();
+        assert_eq!(v.to.offset(), UtcOffset::from_hms(8, 0, 0).unwrap());
+        assert_eq!(v.to.unix_timestamp(), 1706760000);
+        assert_eq!(v.at, Some(Time::from_hms_milli(8, 30, 0, 500).unwrap()));
+        assert_eq!(v.until.unwrap().millisecond(), 250);
+        assert_eq!(v.since.unwrap().unix_timestamp(), 1704067200);
+        assert_eq!(
+            v.before.unwrap().unix_timestamp_nanos(),
+            1_704_067_200_123_000_000
+        );
+        let s = to_string(&v).unwrap();
+        assert!(
+            s.ends_with("&since=1704067200&before=1704067200123"),
+            "{}",
+            s
+        );
+        assert_eq!(from_str::<Range>(&s).unwrap(), v);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let err = from_str::<Range>("from=2024-13-01&to=2024-02-01T12:00:00Z").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::InvalidValue {
+                key: "from".to_string(),
+                expected: "a `Date`".to_string(),
+            }
+        );
+        let err = from_str::<Range>("from=2024-01-01&to=2024-02-01").unwrap_err();
+        assert_eq!(
+            err.message,
+            "invalid value for key `to`, expected an RFC3339-formatted `OffsetDateTime`"
+        );
+        let err =
+            from_str::<Range>("from=2024-01-01&to=2024-02-01T12:00:00Z&at=25:00").unwrap_err();
+        assert_eq!(err.message, "invalid value for key `at`, expected a `Time`");
+        let err =
+            from_str::<Range>("from=2024-01-01&to=2024-02-01T12:00:00Z&since=soon").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidNumber);
+        let err = from_str::<Range>(&format!(
+            "from=2024-01-01&to=2024-02-01T12:00:00Z&since={}",
+            i64::MAX
+        ))
+        .unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+    }
+}
@@ -1 +1,403 @@
+use crate::error::Error;
+use memchr::{memchr, memchr2, memchr3};
+use std::borrow::Cow;
 
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~')
+}
+
+/// Percent-encode everything but the unreserved characters of RFC 3986.
+pub fn encode_component(s: &str) -> Cow<'_, str> {
+    if s.bytes().all(is_unreserved) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len() * 3);
+    push_encoded(&mut out, s);
+    Cow::Owned(out)
+}
+
+/// Append `s` to `out` percent-encoded like [`encode_component`] does, without allocating.
+pub fn push_encoded(out: &mut String, s: &str) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    for b in s.bytes() {
+        if is_unreserved(b) {
+            out.push(b as char);
+        } else {
+            out.push('%');
+            out.push(HEX[(b >> 4) as usize] as char);
+            out.push(HEX[(b & 0xf) as usize] as char);
+        }
+    }
+}
+
+/// A value written by [`query_string!`](crate::query_string) as [`to_string`](crate::to_string)
+/// writes it: scalars as one pair, sequences as a pair per element and `None` as no pair.
+pub trait QueryValue {
+    /// Append the pairs of the value under `key` to `out`, after a `&` unless `out` is empty.
+    fn push_pairs(&self, key: &str, out: &mut String);
+}
+
+// The separator, the encoded key and `=`, before a value.
+fn push_key(out: &mut String, key: &str) {
+    if !out.is_empty() {
+        out.push('&');
+    }
+    push_encoded(out, key);
+    out.push('=');
+}
+
+macro_rules! query_value_display {
+    ($($ty:ty),*) => {
+        $(
+            impl QueryValue for $ty {
+                fn push_pairs(&self, key: &str, out: &mut String) {
+                    use std::fmt::Write;
+                    push_key(out, key);
+                    // writing to a `String` doesn't fail
+                    let _ = write!(out, "{}", self);
+                }
+            }
+        )*
+    };
+}
+
+query_value_display!(
+    bool, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64
+);
+
+impl QueryValue for str {
+    fn push_pairs(&self, key: &str, out: &mut String) {
+        push_key(out, key);
+        push_encoded(out, self);
+    }
+}
+
+impl QueryValue for String {
+    fn push_pairs(&self, key: &str, out: &mut String) {
+        self.as_str().push_pairs(key, out);
+    }
+}
+
+impl QueryValue for char {
+    fn push_pairs(&self, key: &str, out: &mut String) {
+        self.encode_utf8(&mut [0; 4]).push_pairs(key, out);
+    }
+}
+
+impl<T: QueryValue + ?Sized> QueryValue for &T {
+    fn push_pairs(&self, key: &str, out: &mut String) {
+        (**self).push_pairs(key, out);
+    }
+}
+
+impl<T: QueryValue> QueryValue for Option<T> {
+    fn push_pairs(&self, key: &str, out: &mut String) {
+        if let Some(v) = self {
+            v.push_pairs(key, out);
+        }
+    }
+}
+
+impl<T: QueryValue> QueryValue for [T] {
+    fn push_pairs(&self, key: &str, out: &mut String) {
+        for v in self {
+            v.push_pairs(key, out);
+        }
+    }
+}
+
+impl<T: QueryValue, const N: usize> QueryValue for [T; N] {
+    fn push_pairs(&self, key: &str, out: &mut String) {
+        self.as_slice().push_pairs(key, out);
+    }
+}
+
+impl<T: QueryValue> QueryValue for Vec<T> {
+    fn push_pairs(&self, key: &str, out: &mut String) {
+        self.as_slice().push_pairs(key, out);
+    }
+}
+
+/// Percent-encode like `application/x-www-form-urlencoded` serializers such as
+/// `serde_urlencoded`: alphanumerics and `*-._` are kept, spaces become `+`.
+pub fn encode_form_component(s: &str) -> Cow<'_, str> {
+    let is_kept = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'*' | b'-' | b'.' | b'_');
+    if s.bytes().all(is_kept) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len() * 3);
+    for b in s.bytes() {
+        match b {
+            b' ' => out.push('+'),
+            b if is_kept(b) => out.push(b as char),
+            b => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Decode percent escapes and `+` as space.
+pub fn decode_component(s: &str) -> Result<Cow<'_, str>, Error> {
+    decode(s, true)
+}
+
+// Decode percent escapes only, leaving `+` as it is.
+pub(crate) fn decode_percent(s: &str) -> Result<Cow<'_, str>, Error> {
+    decode(s, false)
+}
+
+// Decode a key like `decode_component`, keeping a percent-encoded `sep`, e.g. `%2E` for `.`,
+// escaped as `\.` so that it is read as a literal separator rather than nesting.
+pub(crate) fn decode_key(s: &str, sep: Option<char>) -> Result<Cow<'_, str>, Error> {
+    let Some(sep) = sep.filter(|_| s.contains('%')) else {
+        return decode(s, true);
+    };
+    let mut buf = [0; 4];
+    let encoded: String = sep
+        .encode_utf8(&mut buf)
+        .bytes()
+        .map(|b| format!("%{:02X}", b))
+        .collect();
+    let mut escaped = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        match rest.get(..encoded.len()) {
+            Some(head) if head.eq_ignore_ascii_case(&encoded) => {
+                escaped.push_str("%5C");
+                escaped.push_str(&encoded);
+                rest = &rest[encoded.len()..];
+            }
+            _ => {
+                escaped.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    if escaped.len() == s.len() {
+        return decode(s, true);
+    }
+    Ok(Cow::Owned(decode(&escaped, true)?.into_owned()))
+}
+
+fn decode(s: &str, plus_as_space: bool) -> Result<Cow<'_, str>, Error> {
+    if !s.bytes().any(|b| b == b'%' || (plus_as_space && b == b'+')) {
+        return Ok(Cow::Borrowed(s));
+    }
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let b = bytes
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+                    .ok_or_else(|| {
+                        Error::new(format!("invalid percent escape in `{}`", s), None)
+                    })?;
+                out.push(b);
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out)
+        .map(Cow::Owned)
+        .map_err(|e| Error::new(format!("invalid utf-8 in `{}`", s), Some(Box::new(e))))
+}
+
+/// Split `s` into its `key=value` pairs at `&`, skipping empty segments. Keys and values are
+/// returned as they are, still percent-encoded. A pair is split at its first `=`, so the value
+/// may contain unencoded `=`, e.g. the padding of base64 or a JSON string. A segment without
+/// `=` is an error.
+pub fn split_pairs(s: &str) -> impl Iterator<Item = Result<(&str, &str), Error>> {
+    split_pairs_with(s, &['&'], '=', false)
+}
+
+pub(crate) fn split_pairs_with<'a, 's>(
+    s: &'a str,
+    separators: &'s [char],
+    kv_separator: char,
+    bare_keys: bool,
+) -> impl Iterator<Item = Result<(&'a str, &'a str), Error>> + 's
+where
+    'a: 's,
+{
+    split_segments(s, separators)
+        .filter(|pair| !pair.is_empty())
+        .map(move |pair| {
+            let found = if kv_separator.is_ascii() {
+                memchr(kv_separator as u8, pair.as_bytes())
+            } else {
+                pair.find(kv_separator)
+            };
+            match found {
+                Some(i) => Ok((&pair[..i], &pair[i + kv_separator.len_utf8()..])),
+                None if bare_keys => Ok((pair, "")),
+                None => Err(Error::new(
+                    format!(
+                        "pair `{}` has no `{}` separating its key from its value",
+                        pair, kv_separator
+                    ),
+                    None,
+                )),
+            }
+        })
+}
+
+/// Percent-encode the keys and values of `pairs` and join them into a query string.
+pub fn join_pairs<I, K, V>(pairs: I) -> String
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut out = String::new();
+    for (k, v) in pairs {
+        if !out.is_empty() {
+            out.push('&');
+        }
+        out.push_str(&encode_component(k.as_ref()));
+        out.push('=');
+        out.push_str(&encode_component(v.as_ref()));
+    }
+    out
+}
+
+/// Sort percent-encoded pairs by key, then by value, and join them, as required by request
+/// signing schemes such as AWS Signature Version 4.
+pub(crate) fn join_canonical(mut pairs: Vec<(String, String)>) -> String {
+    pairs.sort_unstable();
+    let mut out = String::new();
+    for (k, v) in pairs {
+        if !out.is_empty() {
+            out.push('&');
+        }
+        out.push_str(&k);
+        out.push('=');
+        out.push_str(&v);
+    }
+    out
+}
+
+/// Split `s` at any of `separators`. Up to three ASCII separators are searched for with memchr.
+pub(crate) fn split_segments<'a, 's>(s: &'a str, separators: &'s [char]) -> Segments<'a, 's> {
+    Segments {
+        rest: Some(s),
+        separators,
+        ascii: separators.iter().all(char::is_ascii),
+    }
+}
+
+pub(crate) struct Segments<'a, 's> {
+    rest: Option<&'a str>,
+    separators: &'s [char],
+    ascii: bool,
+}
+
+impl<'a, 's> Iterator for Segments<'a, 's> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let s = self.rest?;
+        let bytes = s.as_bytes();
+        let found = match (self.ascii, self.separators) {
+            (true, [a]) => memchr(*a as u8, bytes).map(|i| (i, 1)),
+            (true, [a, b]) => memchr2(*a as u8, *b as u8, bytes).map(|i| (i, 1)),
+            (true, [a, b, c]) => memchr3(*a as u8, *b as u8, *c as u8, bytes).map(|i| (i, 1)),
+            _ => s
+                .char_indices()
+                .find(|(_, c)| self.separators.contains(c))
+                .map(|(i, c)| (i, c.len_utf8())),
+        };
+        match found {
+            Some((i, len)) => {
+                self.rest = Some(&s[i + len..]);
+                Some(&s[..i])
+            }
+            None => {
+                self.rest = None;
+                Some(s)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_components() {
+        assert_eq!(encode_component("a-b_c.d~e"), Cow::Borrowed("a-b_c.d~e"));
+        assert_eq!(encode_component("a b&c=d/ü"), "a%20b%26c%3Dd%2F%C3%BC");
+        assert_eq!(
+            encode_form_component("a-b_c.d*e"),
+            Cow::Borrowed("a-b_c.d*e")
+        );
+        assert_eq!(encode_form_component("a b~c+d/ü"), "a+b%7Ec%2Bd%2F%C3%BC");
+        assert!(matches!(
+            decode_component("plain"),
+            Ok(Cow::Borrowed("plain"))
+        ));
+        assert_eq!(decode_component("a+b%20c%C3%BC").unwrap(), "a b cü");
+        assert_eq!(decode_component("").unwrap(), "");
+        for s in ["%", "%2", "%zz", "a%g1", "%C3"] {
+            assert!(decode_component(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_split_pairs() {
+        let pairs: Vec<_> = split_pairs("a=1&&b=&c=x%3Dy&")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(pairs, [("a", "1"), ("b", ""), ("c", "x%3Dy")]);
+        assert_eq!(split_pairs("").count(), 0);
+        assert_eq!(split_pairs("&&").count(), 0);
+        let err = split_pairs("a=1&b").nth(1).unwrap().unwrap_err();
+        assert_eq!(
+            err.message,
+            "pair `b` has no `=` separating its key from its value"
+        );
+        let pairs: Vec<_> = split_pairs_with("a=1&b", &['&'], '=', true)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(pairs, [("a", "1"), ("b", "")]);
+        assert_eq!(split_pairs("a=1=2").next().unwrap().unwrap(), ("a", "1=2"));
+        let pairs: Vec<_> = split_pairs_with("a=1;b=2&c=3", &[';', '&'], '=', false)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(pairs, [("a", "1"), ("b", "2"), ("c", "3")]);
+        let pairs: Vec<_> = split_pairs_with("a:1;b:x=y:z", &[';'], ':', false)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(pairs, [("a", "1"), ("b", "x=y:z")]);
+    }
+
+    #[test]
+    fn test_join_pairs() {
+        assert_eq!(join_pairs([("a", "1"), ("b c", "x&y")]), "a=1&b%20c=x%26y");
+        assert_eq!(join_pairs(Vec::<(String, String)>::new()), "");
+        assert_eq!(join_pairs([("k", "")]), "k=");
+        let s = join_pairs([("q", "a=b & c"), ("n", "ü")]);
+        let pairs: Vec<_> = split_pairs(&s)
+            .map(|pair| {
+                let (k, v) = pair.unwrap();
+                (decode_component(k).unwrap(), decode_component(v).unwrap())
+            })
+            .collect();
+        assert_eq!(
+            pairs,
+            [("q".into(), "a=b & c".into()), ("n".into(), "ü".into())]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! `ToSchema` impls of the wrapper types, so they are documented as the values they hold
+//! rather than as opaque components.
+//!
+//! [`Array<T>`] is documented as a string holding a JSON array, [`Separated<T, SEP>`] as a
+//! string of values joined by `SEP`, [`Raw`] as a string, and the timestamps of the `chrono`
+//! feature as integers. Mark fields of these types with
+//! `#[schema(inline)]` or `#[param(inline)]` so the schema is used in place, and pass the
+//! parameters of [`Array`] fields through [`array_param`] to describe their elements.
+use crate::{Array, Raw, Separated};
+use serde_json::json;
+use utoipa::{
+    openapi::{path::Parameter, ArrayBuilder, ObjectBuilder, RefOr, Schema, SchemaType},
+    PartialSchema, ToSchema,
+};
+
+impl<'s, T> ToSchema<'s> for Array<T> {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        (
+            "Array",
+            ObjectBuilder::new()
+                .schema_type(SchemaType::String)
+                .description(Some("A JSON array, e.g. `[1,2]`"))
+                .into(),
+        )
+    }
+}
+
+/// Documents `param` as an [`Array<T>`], a single value holding a JSON array like
+/// `ids=%5B1,2%5D`, with `content: application/json` and the schema of the array in place of
+/// its string schema.
+///
+/// ```
+/// # use nb_serde_query::{utoipa::array_param, Array};
+/// # use utoipa::IntoParams;
+/// #[derive(IntoParams)]
+/// #[into_params(parameter_in = Query)]
+/// struct Params {
+///     #[param(inline)]
+///     ids: Array<u32>,
+/// }
+///
+/// let params = Params::into_params(|| None);
+/// let ids = array_param::<u32>(params[0].clone());
+/// assert!(ids.schema.is_none());
+/// ```
+pub fn array_param<T: PartialSchema>(mut param: Parameter) -> Parameter {
+    param.schema = None;
+    param
+        .extensions
+        .get_or_insert_with(Default::default)
+        .insert(
+            "content".to_string(),
+            json!({"application/json": {"schema": ArrayBuilder::new().items(T::schema()).build()}}),
+        );
+    param
+}
+
+impl<'s, T, const SEP: char> ToSchema<'s> for Separated<T, SEP> {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        (
+            "Separated",
+            ObjectBuilder::new()
+                .schema_type(SchemaType::String)
+                .description(Some(format!("Values separated by `{}`", SEP)))
+                .into(),
+        )
+    }
+}
+
+impl<'s> ToSchema<'s> for Raw {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        ("Raw", String::schema())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<'s> ToSchema<'s> for crate::chrono::UnixTimestamp {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        ("UnixTimestamp", i64::schema())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<'s> ToSchema<'s> for crate::chrono::UnixTimestampMillis {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        ("UnixTimestampMillis", i64::schema())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_str, CommaSeparated};
+    use serde::Deserialize;
+    use serde_json::json;
+    use utoipa::IntoParams;
+
+    #[derive(Debug, Deserialize, ToSchema, IntoParams)]
+    #[into_params(parameter_in = Query)]
+    #[allow(dead_code)]
+    struct Params {
+        #[schema(inline)]
+        #[param(inline)]
+        ids: Array<u32>,
+        #[schema(inline)]
+        #[param(inline)]
+        sig: Raw,
+        #[schema(inline)]
+        #[param(inline)]
+        codes: CommaSeparated<u16>,
+    }
+
+    #[test]
+    fn test_schema() {
+        let (name, schema) = <Params as ToSchema>::schema();
+        assert_eq!(name, "Params");
+        let schema = serde_json::to_value(schema).unwrap();
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "ids": {"type": "string", "description": "A JSON array, e.g. `[1,2]`"},
+                "sig": {"type": "string"},
+                "codes": {"type": "string", "description": "Values separated by `,`"}
+            })
+        );
+    }
+
+    #[test]
+    fn test_params() {
+        let params = serde_json::to_value(Params::into_params(|| None)).unwrap();
+        assert_eq!(params[0]["name"], "ids");
+        assert_eq!(params[0]["in"], "query");
+        assert_eq!(params[0]["schema"]["type"], "string");
+        assert_eq!(params[1]["name"], "sig");
+        assert_eq!(params[1]["schema"], json!({"type": "string"}));
+        assert_eq!(params[2]["name"], "codes");
+        assert_eq!(
+            params[2]["schema"],
+            json!({"type": "string", "description": "Values separated by `,`"})
+        );
+    }
+
+    #[test]
+    fn test_array_param() {
+        let param = array_param::<u32>(Params::into_params(|| None).remove(0));
+        let param = serde_json::to_value(param).unwrap();
+        assert_eq!(param["name"], "ids");
+        assert!(param.get("schema").is_none());
+        let schema = &param["content"]["application/json"]["schema"];
+        assert_eq!(
+            *schema,
+            json!({
+                "type": "array",
+                "items": {"type": "integer", "format": "int32", "minimum": 0}
+            })
+        );
+
+        // a value of the documented content is read, a form-style array is not
+        let v: Params = from_str("ids=%5B1,2%5D&sig=x&codes=4,5").unwrap();
+        assert_eq!(v.ids, Array(vec![1, 2]));
+        assert_eq!(v.codes, Separated(vec![4, 5]));
+        assert!(from_str::<Params>("ids=1&ids=2&sig=x&codes=4").is_err());
+    }
+}
@@ -0,0 +1,36 @@
+//! Conversions between query parameter types and the browser's `URLSearchParams`, so the same
+//! structs can be used in a wasm frontend and on the server.
+use crate::{error::Error, options::ParseOptions, to_string, Deserializer};
+use js_sys::Array;
+use serde::{de::DeserializeOwned, Serialize};
+use web_sys::UrlSearchParams;
+
+/// Deserialize `T` from the entries of `params` with the default options.
+pub fn from_url_search_params<T>(params: &UrlSearchParams) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    let mut pairs = Vec::new();
+    for entry in params.entries() {
+        let entry = Array::from(&entry.map_err(|e| js_error("failed to read entry", e))?);
+        match (entry.get(0).as_string(), entry.get(1).as_string()) {
+            (Some(key), Some(val)) => pairs.push((key, val)),
+            _ => return Err(Error::new("entry is not a pair of strings", None)),
+        }
+    }
+    let mut deserializer = Deserializer::try_from_pairs(pairs, &ParseOptions::default())?;
+    T::deserialize(&mut deserializer)
+}
+
+/// Serialize `value` into a new `URLSearchParams` with the default options.
+pub fn to_url_search_params<T>(value: &T) -> Result<UrlSearchParams, Error>
+where
+    T: ?Sized + Serialize,
+{
+    UrlSearchParams::new_with_str(&to_string(value)?)
+        .map_err(|e| js_error("failed to create URLSearchParams", e))
+}
+
+fn js_error(msg: &str, e: wasm_bindgen::JsValue) -> Error {
+    Error::new(format!("{}: {:?}", msg, e), None)
+}
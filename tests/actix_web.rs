@@ -0,0 +1,79 @@
+#![cfg(feature = "actix-web")]
+//! The actix-web `Query` extractor with a different `QueryConfig` per scope.
+
+use actix_web::{
+    http::StatusCode,
+    test::{self, TestRequest},
+    web, App,
+};
+use nb_serde_query::{
+    actix_web::{Query, QueryConfig},
+    options::ParseOptions,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct Listing {
+    page: u32,
+    archived: Option<bool>,
+}
+
+async fn list(q: Query<Listing>) -> String {
+    format!("page {} archived {:?}", q.0.page, q.0.archived)
+}
+
+#[actix_web::test]
+async fn test_scoped_config() {
+    let strict = ParseOptions::new().deny_unknown(true);
+    let lenient = ParseOptions::new()
+        .lenient_bool(true)
+        .number_underscores(true);
+    let app = test::init_service(
+        App::new()
+            .app_data(QueryConfig::default().options(strict))
+            .service(web::scope("/admin").route("/items", web::get().to(list)))
+            .service(
+                web::scope("/api")
+                    .app_data(QueryConfig::default().options(lenient))
+                    .route("/items", web::get().to(list))
+                    .service(
+                        web::scope("/v0")
+                            .app_data(QueryConfig::default().error_status(422))
+                            .route("/items", web::get().to(list)),
+                    ),
+            ),
+    )
+    .await;
+
+    let query = "page=1_000&archived=yes&utm=x";
+    let call = |uri: String| {
+        let app = &app;
+        async move {
+            let resp = test::call_service(app, TestRequest::with_uri(&uri).to_request()).await;
+            let status = resp.status();
+            let body = test::read_body(resp).await;
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+    };
+
+    // the innermost scope with a config decides
+    let (status, body) = call(format!("/api/items?{}", query)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "page 1000 archived Some(true)");
+
+    let (status, body) = call(format!("/admin/items?{}", query)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body,
+        "invalid value `1_000` for key `page`, expected u32: invalid digit found in string"
+    );
+    let (status, body) = call("/admin/items?page=1&utm=x".to_string()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body, "unknown keys: utm");
+
+    let (status, _) = call(format!("/api/v0/items?{}", query)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, body) = call("/api/v0/items?page=2&utm=x".to_string()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "page 2 archived None");
+}
@@ -0,0 +1,1057 @@
+//! Serde attributes renaming, skipping and defaulting fields, in both directions, and the
+//! identifiers of hand written impls.
+
+use nb_serde_query::{
+    error::ErrorKind,
+    from_str, from_str_with,
+    options::{ArrayStyle, EmptyValue, ParseOptions, SerializerOptions},
+    to_string, to_string_with,
+};
+use serde::{
+    de::{self, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Search {
+    page_size: u32,
+    sort_by: Option<String>,
+    tag_ids: Vec<u32>,
+    #[serde(rename = "q")]
+    query_text: String,
+}
+
+#[test]
+fn test_rename_all() {
+    let v = Search {
+        page_size: 10,
+        sort_by: Some("name".to_string()),
+        tag_ids: vec![3, 1],
+        query_text: "rust".to_string(),
+    };
+    let s = "pageSize=10&sortBy=name&tagIds=3&tagIds=1&q=rust";
+    assert_eq!(to_string(&v).unwrap(), s);
+    assert_eq!(from_str::<Search>(s).unwrap(), v);
+
+    // the Rust names are not accepted
+    let err = from_str::<Search>("page_size=10&q=rust").unwrap_err();
+    assert!(err.to_string().contains("pageSize"), "{}", err);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Paging {
+    page_number: u32,
+    #[serde(default)]
+    per_page: Option<u32>,
+    #[serde(rename = "skip")]
+    skipped_ids: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Listing {
+    user_id: u64,
+    #[serde(flatten)]
+    paging: Paging,
+    #[serde(rename = "tag")]
+    tags: Vec<String>,
+}
+
+#[test]
+fn test_flatten_rename() {
+    let v = Listing {
+        user_id: 7,
+        paging: Paging {
+            page_number: 2,
+            per_page: Some(50),
+            skipped_ids: vec![4, 5],
+        },
+        tags: vec!["a".to_string(), "b".to_string()],
+    };
+    let s = "userId=7&page-number=2&per-page=50&skip=4&skip=5&tag=a&tag=b";
+    assert_eq!(to_string(&v).unwrap(), s);
+    assert_eq!(from_str::<Listing>(s).unwrap(), v);
+
+    let v: Listing = from_str("tag=x&page-number=1&skip=1&skip=2&userId=3").unwrap();
+    assert_eq!(v.paging.per_page, None);
+    assert_eq!(v.paging.skipped_ids, vec![1, 2]);
+    assert_eq!(v.tags, vec!["x".to_string()]);
+    let v: Listing = from_str("page-number=1&userId=3&tag=a&skip=1&skip=2").unwrap();
+    assert_eq!(v.paging.skipped_ids, vec![1, 2]);
+
+    // flattened fields are buffered without knowing their types, so a single value is not
+    // read as a sequence of one
+    assert!(from_str::<Listing>("page-number=1&userId=3&skip=1").is_err());
+    assert_eq!(v.tags, vec!["a".to_string()]);
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Bounds {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max: Option<i32>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Filters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner: Option<u64>,
+    #[serde(flatten)]
+    range: Bounds,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Report {
+    q: String,
+    #[serde(flatten)]
+    filters: Filters,
+    page: u32,
+}
+
+#[test]
+fn test_flatten_skipped() {
+    // flattened structs are written through a map, whose skipped entries write nothing at all
+    for (filters, s) in [
+        (Filters::default(), "q=x&page=1"),
+        (
+            Filters {
+                owner: Some(7),
+                ..Filters::default()
+            },
+            "q=x&owner=7&page=1",
+        ),
+        (
+            Filters {
+                status: Some(String::new()),
+                range: Bounds {
+                    min: None,
+                    max: Some(-3),
+                },
+                ..Filters::default()
+            },
+            "q=x&status=&max=-3&page=1",
+        ),
+        (
+            Filters {
+                status: Some("open".to_string()),
+                owner: Some(1),
+                range: Bounds {
+                    min: Some(0),
+                    max: Some(9),
+                },
+            },
+            "q=x&status=open&owner=1&min=0&max=9&page=1",
+        ),
+    ] {
+        let v = Report {
+            q: "x".to_string(),
+            filters,
+            page: 1,
+        };
+        assert_eq!(to_string(&v).unwrap(), s);
+        assert_eq!(from_str::<Report>(s).unwrap(), v);
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Criteria {
+    status: Option<Status>,
+    limit: Option<u32>,
+    #[serde(default)]
+    ids: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Query {
+    q: Option<String>,
+    #[serde(flatten)]
+    criteria: Criteria,
+    #[serde(flatten)]
+    extra: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Dynamic {
+    #[serde(default)]
+    tag: Vec<String>,
+    #[serde(flatten)]
+    values: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Wrapped {
+    #[serde(flatten)]
+    query: Query,
+}
+
+#[test]
+fn test_flatten_combinations() {
+    // a struct and a map flattened side by side, the map taking what the struct leaves
+    let v: Query = from_str("q=x&limit=10&status=open&ids=1&ids=2&source=mail").unwrap();
+    assert_eq!(v.q.as_deref(), Some("x"));
+    assert_eq!(
+        v.criteria,
+        Criteria {
+            status: Some(Status::Open),
+            limit: Some(10),
+            ids: vec![1, 2],
+        }
+    );
+    assert_eq!(v.extra.len(), 1);
+    assert_eq!(v.extra["source"], "mail");
+    let v: Query = from_str("status=closed").unwrap();
+    assert_eq!(v.criteria.status, Some(Status::Closed));
+    assert_eq!(v.criteria.limit, None);
+    assert!(v.extra.is_empty());
+    // buffered values are typed by how they are written, so a number is not a string
+    assert!(from_str::<Query>("source=7").is_err());
+
+    // a flattened map alongside a sequence, its values typed as they are written
+    let v: Dynamic = from_str("tag=a&page=2&tag=b&ratio=0.5&debug=true&id=3&id=4&q=rust").unwrap();
+    assert_eq!(v.tag, ["a", "b"]);
+    assert_eq!(
+        serde_json::to_value(&v.values).unwrap(),
+        serde_json::json!({
+            "page": 2,
+            "ratio": 0.5,
+            "debug": true,
+            "id": [3, 4],
+            "q": "rust",
+        })
+    );
+
+    // flattened inside a flattened struct
+    let v: Wrapped = from_str("limit=5&q=y&ids=7&ids=8&lang=en").unwrap();
+    assert_eq!(v.query.q.as_deref(), Some("y"));
+    assert_eq!(v.query.criteria.limit, Some(5));
+    assert_eq!(v.query.criteria.ids, [7, 8]);
+    assert_eq!(v.query.extra["lang"], "en");
+    let v: Wrapped = from_str("").unwrap();
+    assert_eq!(v.query.criteria.status, None);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Pagination {
+    page: u32,
+    per_page: u32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PagedFirst {
+    #[serde(flatten)]
+    pagination: Option<Pagination>,
+    #[serde(default)]
+    ids: Vec<u32>,
+    q: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PagedMiddle {
+    #[serde(default)]
+    ids: Vec<u32>,
+    #[serde(flatten)]
+    pagination: Option<Pagination>,
+    q: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PagedLast {
+    q: String,
+    #[serde(default)]
+    ids: Vec<u32>,
+    #[serde(flatten)]
+    pagination: Option<Pagination>,
+}
+
+#[test]
+fn test_flatten_none() {
+    // a flattened `None` writes nothing, wherever it is and whatever its neighbors write
+    let pagination = || {
+        Some(Pagination {
+            page: 2,
+            per_page: 50,
+        })
+    };
+    let first = |pagination, ids| PagedFirst {
+        pagination,
+        ids,
+        q: "a".to_string(),
+    };
+    let middle = |pagination, ids| PagedMiddle {
+        pagination,
+        ids,
+        q: "a".to_string(),
+    };
+    let last = |pagination, ids| PagedLast {
+        pagination,
+        ids,
+        q: "a".to_string(),
+    };
+    let cases = [
+        (vec![1, 2], "ids=1&ids=2&q=a", "q=a&ids=1&ids=2"),
+        (vec![1], "ids=1&q=a", "q=a&ids=1"),
+        (vec![], "q=a", "q=a"),
+    ];
+    for (ids, s, last_s) in cases {
+        let v = first(None, ids.clone());
+        assert_eq!(to_string(&v).unwrap(), s);
+        assert_eq!(from_str::<PagedFirst>(s).unwrap(), v);
+        let v = middle(None, ids.clone());
+        assert_eq!(to_string(&v).unwrap(), s);
+        assert_eq!(from_str::<PagedMiddle>(s).unwrap(), v);
+        let v = last(None, ids);
+        assert_eq!(to_string(&v).unwrap(), last_s);
+        assert_eq!(from_str::<PagedLast>(last_s).unwrap(), v);
+    }
+
+    assert_eq!(
+        to_string(&first(pagination(), vec![1, 2])).unwrap(),
+        "page=2&per_page=50&ids=1&ids=2&q=a"
+    );
+    assert_eq!(
+        to_string(&middle(pagination(), vec![])).unwrap(),
+        "page=2&per_page=50&q=a"
+    );
+    let v = last(pagination(), vec![3]);
+    assert_eq!(to_string(&v).unwrap(), "q=a&ids=3&page=2&per_page=50");
+    assert_eq!(
+        from_str::<PagedLast>("q=a&ids=3&page=2&per_page=50").unwrap(),
+        v
+    );
+    // the comma style writes one pair for the sequence
+    let opts = SerializerOptions::new().array_style(ArrayStyle::Comma);
+    assert_eq!(
+        to_string_with(&middle(None, vec![1, 2]), &opts).unwrap(),
+        "ids=1,2&q=a"
+    );
+    assert_eq!(
+        to_string_with(&last(None, vec![1, 2]), &opts).unwrap(),
+        "q=a&ids=1,2"
+    );
+}
+
+fn default_limit() -> u32 {
+    25
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Options {
+    name: String,
+    #[serde(skip_serializing)]
+    password: Option<String>,
+    #[serde(skip_deserializing)]
+    computed: u32,
+    #[serde(skip)]
+    cache: Vec<u8>,
+    #[serde(default = "default_limit")]
+    limit: u32,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+#[test]
+fn test_skip_and_default() {
+    let v = Options {
+        name: "n".to_string(),
+        password: Some("secret".to_string()),
+        computed: 3,
+        cache: vec![1],
+        limit: 10,
+        tags: vec![],
+        note: None,
+    };
+    assert_eq!(to_string(&v).unwrap(), "name=n&computed=3&limit=10");
+
+    let v: Options = from_str("name=n&password=p&computed=9&cache=1&limit=25").unwrap();
+    assert_eq!(
+        v,
+        Options {
+            name: "n".to_string(),
+            password: Some("p".to_string()),
+            computed: 0,
+            cache: vec![],
+            limit: 25,
+            tags: vec![],
+            note: None,
+        }
+    );
+    let v: Options = from_str("name=n&limit=5&tags=a&note=hi").unwrap();
+    assert_eq!(
+        (v.limit, v.tags, v.note),
+        (5, vec!["a".to_string()], Some("hi".to_string()))
+    );
+
+    // fields without a key take their default
+    let v: Options = from_str("name=n").unwrap();
+    assert_eq!((v.limit, v.tags, v.note), (25, vec![], None));
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+struct Defaults {
+    page: u32,
+    per_page: u32,
+    sort: String,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            per_page: 20,
+            sort: "id".to_string(),
+        }
+    }
+}
+
+#[test]
+fn test_missing_fields() {
+    assert_eq!(from_str::<Defaults>("").unwrap(), Defaults::default());
+    assert_eq!(
+        from_str::<Defaults>("per_page=50").unwrap(),
+        Defaults {
+            per_page: 50,
+            ..Defaults::default()
+        }
+    );
+
+    let err = from_str::<Options>("limit=5").unwrap_err();
+    assert_eq!(
+        err.kind,
+        ErrorKind::MissingField {
+            field: "name".to_string()
+        }
+    );
+    assert_eq!(err.to_string(), "missing field `name`");
+    // an empty value is a value
+    assert_eq!(from_str::<Options>("name=").unwrap().name, "");
+
+    let err = from_str::<Listing>("userId=1&page-number=1").unwrap_err();
+    assert_eq!(err.to_string(), "missing field `tag`");
+}
+
+#[derive(Debug, Deserialize)]
+struct OptionalQty {
+    qty: Option<u32>,
+    note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DefaultQty {
+    #[serde(default)]
+    qty: u32,
+}
+
+#[test]
+fn test_blank_numbers() {
+    let drop_empty = ParseOptions::new().empty_value(EmptyValue::AsNone);
+    // input, then `Option<u32>`, and `#[serde(default)] u32` with empty values kept or dropped,
+    // where `None` in the last two columns is an error
+    let matrix = [
+        ("qty=", None, None, Some(0)),
+        ("", None, Some(0), Some(0)),
+        ("qty=0", Some(0), Some(0), Some(0)),
+    ];
+    for (s, optional, kept, dropped) in matrix {
+        assert_eq!(from_str::<OptionalQty>(s).unwrap().qty, optional, "{}", s);
+        assert_eq!(
+            from_str_with::<OptionalQty>(s, &drop_empty).unwrap().qty,
+            optional,
+            "{}",
+            s
+        );
+        assert_eq!(from_str::<DefaultQty>(s).ok().map(|v| v.qty), kept, "{}", s);
+        assert_eq!(
+            from_str_with::<DefaultQty>(s, &drop_empty)
+                .ok()
+                .map(|v| v.qty),
+            dropped,
+            "{}",
+            s
+        );
+    }
+
+    // a present value is still read, so a blank plain number is an error
+    let err = from_str::<DefaultQty>("qty=").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidNumber);
+    assert_eq!(
+        err.to_string(),
+        "invalid value `` for key `qty`, expected u32: cannot parse integer from empty string"
+    );
+    // only values the inner type cannot be read from are `None`
+    assert!(from_str::<OptionalQty>("qty=x").is_err());
+    assert_eq!(
+        from_str::<OptionalQty>("qty=&note=")
+            .unwrap()
+            .note
+            .as_deref(),
+        Some("")
+    );
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE", deny_unknown_fields)]
+struct Env {
+    api_key: String,
+    #[serde(alias = "RETRIES", alias = "retries")]
+    max_retries: u8,
+}
+
+#[test]
+fn test_rename_with_alias() {
+    let v: Env = from_str("API_KEY=k&retries=3").unwrap();
+    assert_eq!(v.max_retries, 3);
+    assert_eq!(to_string(&v).unwrap(), "API_KEY=k&MAX_RETRIES=3");
+    assert_eq!(from_str::<Env>("API_KEY=k&MAX_RETRIES=3").unwrap(), v);
+}
+
+#[derive(Debug, PartialEq)]
+struct Range {
+    lo: i32,
+    hi: i32,
+}
+
+impl<'de> Deserialize<'de> for Range {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "lowercase")]
+        enum Field {
+            Lo,
+            Hi,
+        }
+
+        struct RangeVisitor;
+
+        impl<'de> Visitor<'de> for RangeVisitor {
+            type Value = Range;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("struct Range")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Range, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let (mut lo, mut hi) = (None, None);
+                while let Some(key) = map.next_key()? {
+                    match key {
+                        Field::Lo => lo = Some(map.next_value()?),
+                        Field::Hi => hi = Some(map.next_value()?),
+                    }
+                }
+                Ok(Range {
+                    lo: lo.ok_or_else(|| de::Error::missing_field("lo"))?,
+                    hi: hi.ok_or_else(|| de::Error::missing_field("hi"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct("Range", &["lo", "hi"], RangeVisitor)
+    }
+}
+
+#[test]
+fn test_field_identifier() {
+    assert_eq!(
+        from_str::<Range>("hi=9&lo=-1").unwrap(),
+        Range { lo: -1, hi: 9 }
+    );
+    assert!(from_str::<Range>("lo=1").is_err());
+}
+
+// A struct read with `u8` keys, which are never grouped as names of one field.
+#[derive(Debug, PartialEq)]
+struct Bits([Option<bool>; 3]);
+
+impl<'de> Deserialize<'de> for Bits {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BitsVisitor;
+
+        impl<'de> Visitor<'de> for BitsVisitor {
+            type Value = Bits;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("struct Bits")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Bits, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut bits = [None; 3];
+                while let Some(key) = map.next_key::<u8>()? {
+                    let bit = bits
+                        .get_mut(key as usize)
+                        .ok_or_else(|| de::Error::custom("no such bit"))?;
+                    *bit = Some(map.next_value()?);
+                }
+                Ok(Bits(bits))
+            }
+        }
+
+        deserializer.deserialize_struct("Bits", &["0", "1", "2"], BitsVisitor)
+    }
+}
+
+#[test]
+fn test_number_keyed_struct() {
+    assert_eq!(
+        from_str::<Bits>("2=true&0=false&1=true").unwrap(),
+        Bits([Some(false), Some(true), Some(true)])
+    );
+    assert_eq!(
+        from_str::<Bits>("1=true").unwrap(),
+        Bits([None, Some(true), None])
+    );
+}
+
+// More fields than a one byte field enum has variants for.
+#[rustfmt::skip]
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+struct Wide {
+    f0: Option<u8>,
+    f1: Option<u8>,
+    f2: Option<u8>,
+    f3: Option<u8>,
+    f4: Option<u8>,
+    f5: Option<u8>,
+    f6: Option<u8>,
+    f7: Option<u8>,
+    f8: Option<u8>,
+    f9: Option<u8>,
+    f10: Option<u8>,
+    f11: Option<u8>,
+    f12: Option<u8>,
+    f13: Option<u8>,
+    f14: Option<u8>,
+    f15: Option<u8>,
+    f16: Option<u8>,
+    f17: Option<u8>,
+    f18: Option<u8>,
+    f19: Option<u8>,
+    f20: Option<u8>,
+    f21: Option<u8>,
+    f22: Option<u8>,
+    f23: Option<u8>,
+    f24: Option<u8>,
+    f25: Option<u8>,
+    f26: Option<u8>,
+    f27: Option<u8>,
+    f28: Option<u8>,
+    f29: Option<u8>,
+    f30: Option<u8>,
+    f31: Option<u8>,
+    f32: Option<u8>,
+    f33: Option<u8>,
+    f34: Option<u8>,
+    f35: Option<u8>,
+    f36: Option<u8>,
+    f37: Option<u8>,
+    f38: Option<u8>,
+    f39: Option<u8>,
+    f40: Option<u8>,
+    f41: Option<u8>,
+    f42: Option<u8>,
+    f43: Option<u8>,
+    f44: Option<u8>,
+    f45: Option<u8>,
+    f46: Option<u8>,
+    f47: Option<u8>,
+    f48: Option<u8>,
+    f49: Option<u8>,
+    f50: Option<u8>,
+    f51: Option<u8>,
+    f52: Option<u8>,
+    f53: Option<u8>,
+    f54: Option<u8>,
+    f55: Option<u8>,
+    f56: Option<u8>,
+    f57: Option<u8>,
+    f58: Option<u8>,
+    f59: Option<u8>,
+    f60: Option<u8>,
+    f61: Option<u8>,
+    f62: Option<u8>,
+    f63: Option<u8>,
+    f64: Option<u8>,
+    f65: Option<u8>,
+    f66: Option<u8>,
+    f67: Option<u8>,
+    f68: Option<u8>,
+    f69: Option<u8>,
+    f70: Option<u8>,
+    f71: Option<u8>,
+    f72: Option<u8>,
+    f73: Option<u8>,
+    f74: Option<u8>,
+    f75: Option<u8>,
+    f76: Option<u8>,
+    f77: Option<u8>,
+    f78: Option<u8>,
+    f79: Option<u8>,
+    f80: Option<u8>,
+    f81: Option<u8>,
+    f82: Option<u8>,
+    f83: Option<u8>,
+    f84: Option<u8>,
+    f85: Option<u8>,
+    f86: Option<u8>,
+    f87: Option<u8>,
+    f88: Option<u8>,
+    f89: Option<u8>,
+    f90: Option<u8>,
+    f91: Option<u8>,
+    f92: Option<u8>,
+    f93: Option<u8>,
+    f94: Option<u8>,
+    f95: Option<u8>,
+    f96: Option<u8>,
+    f97: Option<u8>,
+    f98: Option<u8>,
+    f99: Option<u8>,
+    f100: Option<u8>,
+    f101: Option<u8>,
+    f102: Option<u8>,
+    f103: Option<u8>,
+    f104: Option<u8>,
+    f105: Option<u8>,
+    f106: Option<u8>,
+    f107: Option<u8>,
+    f108: Option<u8>,
+    f109: Option<u8>,
+    f110: Option<u8>,
+    f111: Option<u8>,
+    f112: Option<u8>,
+    f113: Option<u8>,
+    f114: Option<u8>,
+    f115: Option<u8>,
+    f116: Option<u8>,
+    f117: Option<u8>,
+    f118: Option<u8>,
+    f119: Option<u8>,
+    f120: Option<u8>,
+    f121: Option<u8>,
+    f122: Option<u8>,
+    f123: Option<u8>,
+    f124: Option<u8>,
+    f125: Option<u8>,
+    f126: Option<u8>,
+    f127: Option<u8>,
+    f128: Option<u8>,
+    f129: Option<u8>,
+    f130: Option<u8>,
+    f131: Option<u8>,
+    f132: Option<u8>,
+    f133: Option<u8>,
+    f134: Option<u8>,
+    f135: Option<u8>,
+    f136: Option<u8>,
+    f137: Option<u8>,
+    f138: Option<u8>,
+    f139: Option<u8>,
+    f140: Option<u8>,
+    f141: Option<u8>,
+    f142: Option<u8>,
+    f143: Option<u8>,
+    f144: Option<u8>,
+    f145: Option<u8>,
+    f146: Option<u8>,
+    f147: Option<u8>,
+    f148: Option<u8>,
+    f149: Option<u8>,
+    f150: Option<u8>,
+    f151: Option<u8>,
+    f152: Option<u8>,
+    f153: Option<u8>,
+    f154: Option<u8>,
+    f155: Option<u8>,
+    f156: Option<u8>,
+    f157: Option<u8>,
+    f158: Option<u8>,
+    f159: Option<u8>,
+    f160: Option<u8>,
+    f161: Option<u8>,
+    f162: Option<u8>,
+    f163: Option<u8>,
+    f164: Option<u8>,
+    f165: Option<u8>,
+    f166: Option<u8>,
+    f167: Option<u8>,
+    f168: Option<u8>,
+    f169: Option<u8>,
+    f170: Option<u8>,
+    f171: Option<u8>,
+    f172: Option<u8>,
+    f173: Option<u8>,
+    f174: Option<u8>,
+    f175: Option<u8>,
+    f176: Option<u8>,
+    f177: Option<u8>,
+    f178: Option<u8>,
+    f179: Option<u8>,
+    f180: Option<u8>,
+    f181: Option<u8>,
+    f182: Option<u8>,
+    f183: Option<u8>,
+    f184: Option<u8>,
+    f185: Option<u8>,
+    f186: Option<u8>,
+    f187: Option<u8>,
+    f188: Option<u8>,
+    f189: Option<u8>,
+    f190: Option<u8>,
+    f191: Option<u8>,
+    f192: Option<u8>,
+    f193: Option<u8>,
+    f194: Option<u8>,
+    f195: Option<u8>,
+    f196: Option<u8>,
+    f197: Option<u8>,
+    f198: Option<u8>,
+    f199: Option<u8>,
+    f200: Option<u8>,
+    f201: Option<u8>,
+    f202: Option<u8>,
+    f203: Option<u8>,
+    f204: Option<u8>,
+    f205: Option<u8>,
+    f206: Option<u8>,
+    f207: Option<u8>,
+    f208: Option<u8>,
+    f209: Option<u8>,
+    f210: Option<u8>,
+    f211: Option<u8>,
+    f212: Option<u8>,
+    f213: Option<u8>,
+    f214: Option<u8>,
+    f215: Option<u8>,
+    f216: Option<u8>,
+    f217: Option<u8>,
+    f218: Option<u8>,
+    f219: Option<u8>,
+    f220: Option<u8>,
+    f221: Option<u8>,
+    f222: Option<u8>,
+    f223: Option<u8>,
+    f224: Option<u8>,
+    f225: Option<u8>,
+    f226: Option<u8>,
+    f227: Option<u8>,
+    f228: Option<u8>,
+    f229: Option<u8>,
+    f230: Option<u8>,
+    f231: Option<u8>,
+    f232: Option<u8>,
+    f233: Option<u8>,
+    f234: Option<u8>,
+    f235: Option<u8>,
+    f236: Option<u8>,
+    f237: Option<u8>,
+    f238: Option<u8>,
+    f239: Option<u8>,
+    f240: Option<u8>,
+    f241: Option<u8>,
+    f242: Option<u8>,
+    f243: Option<u8>,
+    f244: Option<u8>,
+    f245: Option<u8>,
+    f246: Option<u8>,
+    f247: Option<u8>,
+    f248: Option<u8>,
+    f249: Option<u8>,
+    f250: Option<u8>,
+    f251: Option<u8>,
+    f252: Option<u8>,
+    f253: Option<u8>,
+    f254: Option<u8>,
+    f255: Option<u8>,
+    #[serde(alias = "last")]
+    f256: Option<u8>,
+}
+
+#[test]
+fn test_alias_on_wide_struct() {
+    let opts = ParseOptions::new().deny_unknown(true);
+    let v: Wide = from_str_with("f0=1&last=2&f255=3", &opts).unwrap();
+    assert_eq!(
+        (v.f0, v.f1, v.f255, v.f256),
+        (Some(1), None, Some(3), Some(2))
+    );
+    // the name appearing first wins, the other is consumed
+    let v: Wide = from_str_with("last=2&f256=4", &opts).unwrap();
+    assert_eq!(v.f256, Some(2));
+    let v: Wide = from_str_with("f256=4&last=2", &opts).unwrap();
+    assert_eq!(v.f256, Some(4));
+}
+
+// A key that asks for a number first, as hand written identifiers indexing fields do.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Slot {
+    Index(u64),
+    Name(String),
+}
+
+impl<'de> Deserialize<'de> for Slot {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SlotVisitor;
+
+        impl<'de> Visitor<'de> for SlotVisitor {
+            type Value = Slot;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a slot index or name")
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Slot, E> {
+                Ok(Slot::Index(v))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Slot, E> {
+                Ok(Slot::Name(v.to_string()))
+            }
+        }
+
+        deserializer.deserialize_u64(SlotVisitor)
+    }
+}
+
+// A key read only from bytes.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct RawKey(Vec<u8>);
+
+impl<'de> Deserialize<'de> for RawKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawKeyVisitor;
+
+        impl<'de> Visitor<'de> for RawKeyVisitor {
+            type Value = RawKey;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("key bytes")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<RawKey, E> {
+                Ok(RawKey(v.to_vec()))
+            }
+        }
+
+        deserializer.deserialize_bytes(RawKeyVisitor)
+    }
+}
+
+#[test]
+fn test_identifier_probing() {
+    let m: BTreeMap<Slot, u32> = from_str("0=5&name=6&12=7").unwrap();
+    assert_eq!(
+        m.into_iter().collect::<Vec<_>>(),
+        [
+            (Slot::Index(0), 5),
+            (Slot::Index(12), 7),
+            (Slot::Name("name".to_string()), 6)
+        ]
+    );
+
+    let m: BTreeMap<RawKey, u8> = from_str("b=1&%C3%BC=2").unwrap();
+    assert_eq!(
+        m.into_keys().collect::<Vec<_>>(),
+        [RawKey(b"b".to_vec()), RawKey("ü".as_bytes().to_vec())]
+    );
+}
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+struct Utm {
+    source: Option<String>,
+    medium: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Campaign {
+    // declared before the literal field, which still takes `utm.source`
+    utm: Option<Utm>,
+    #[serde(rename = "utm.source")]
+    utm_source: Option<String>,
+    #[serde(rename = "utm.medium")]
+    utm_medium: Option<String>,
+}
+
+#[test]
+fn test_dotted_names() {
+    // without a nesting separator, dots are part of the key
+    let v: Campaign = from_str("utm.source=news&utm.medium=email").unwrap();
+    assert_eq!(v.utm, None);
+    assert_eq!(v.utm_source.as_deref(), Some("news"));
+    assert_eq!(v.utm_medium.as_deref(), Some("email"));
+    let m: BTreeMap<String, String> = from_str("utm.source=news").unwrap();
+    assert_eq!(m["utm.source"], "news");
+
+    // with one, a key naming a field literally is read by it, only the rest nests
+    let opts = ParseOptions::new().nesting_separator('.');
+    let v: Campaign = from_str_with("utm.source=news&utm.medium=email", &opts).unwrap();
+    assert_eq!((v.utm, v.utm_source.as_deref()), (None, Some("news")));
+    #[derive(Debug, Deserialize)]
+    struct Tracking {
+        utm: Utm,
+        #[serde(rename = "utm.source")]
+        utm_source: Option<String>,
+    }
+    let v: Tracking = from_str_with("utm.source=news&utm.medium=email", &opts).unwrap();
+    assert_eq!(v.utm_source.as_deref(), Some("news"));
+    assert_eq!(
+        v.utm,
+        Utm {
+            source: None,
+            medium: Some("email".to_string()),
+        }
+    );
+
+    // an escaped separator, `\.` or `%2E`, is literal
+    for s in [
+        "utm%2Esource=news&utm%2emedium=email",
+        "utm\\.source=news&utm%5C.medium=email",
+    ] {
+        let v: Campaign = from_str_with(s, &opts).unwrap();
+        assert_eq!(v.utm, None, "{}", s);
+        assert_eq!(v.utm_source.as_deref(), Some("news"), "{}", s);
+        assert_eq!(v.utm_medium.as_deref(), Some("email"), "{}", s);
+    }
+    let m: BTreeMap<String, String> = from_str_with("a%2Eb=1&c.d=2", &opts).unwrap();
+    assert_eq!(m.into_keys().collect::<Vec<_>>(), ["a.b", "c.d"]);
+    #[derive(Debug, Deserialize)]
+    struct Labels {
+        labels: BTreeMap<String, String>,
+    }
+    let v: Labels = from_str_with("labels.app%2Ename=api&labels.env=prod", &opts).unwrap();
+    assert_eq!(
+        v.labels.into_keys().collect::<Vec<_>>(),
+        ["app.name", "env"]
+    );
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "axum")]
+//! The axum `Query` extractor, through a router.
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    routing::get,
+    Extension, Router,
+};
+use nb_serde_query::{
+    axum::{Query, QueryConfig},
+    options::ParseOptions,
+};
+use serde::Deserialize;
+use tower::ServiceExt;
+
+#[derive(Debug, Deserialize)]
+struct Search {
+    q: String,
+    page: Option<u32>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+async fn search(Query(v): Query<Search>) -> String {
+    format!(
+        "{} page {} tags {}",
+        v.q,
+        v.page.unwrap_or(1),
+        v.tags.join("+")
+    )
+}
+
+async fn call(app: &Router, uri: &str) -> (StatusCode, Option<String>, String) {
+    let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    let content_type =
+        (resp.headers().get(header::CONTENT_TYPE)).map(|v| v.to_str().unwrap().to_string());
+    let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (
+        status,
+        content_type,
+        String::from_utf8(body.to_vec()).unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn test_extractor() {
+    let app = Router::new().route("/search", get(search));
+    let (status, _, body) = call(&app, "/search?q=rust&tags=a&page=2&tags=b").await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "rust page 2 tags a+b")
+    );
+    let (status, _, body) = call(&app, "/search").await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::BAD_REQUEST, "missing field `q`")
+    );
+    let (status, _, body) = call(&app, "/search?q=rust&page=x").await;
+    assert_eq!(
+        (status, body.as_str()),
+        (
+            StatusCode::BAD_REQUEST,
+            "invalid value `x` for key `page`, expected u32: invalid digit found in string"
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_limits() {
+    let config = QueryConfig::default().options(ParseOptions::new().max_values_per_key(2));
+    let app = Router::new()
+        .route("/search", get(search))
+        .layer(Extension(config));
+    let (status, content_type, body) = call(&app, "/search?q=rust&tags=a&tags=b&tags=c").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(content_type.as_deref(), Some("application/json"));
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        serde_json::json!({
+            "error": "too many values for key `tags`, at most 2 allowed",
+            "limit": {"kind": "values_per_key", "key": "tags", "max": 2},
+        })
+    );
+
+    let config = QueryConfig::default().options(ParseOptions::new().max_input_len(8));
+    let app = Router::new()
+        .route("/search", get(search))
+        .layer(Extension(config));
+    let (status, _, body) = call(&app, "/search?q=rust&page=2").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap()["limit"],
+        serde_json::json!({"kind": "input_length", "key": null, "max": 8})
+    );
+}
+
+#[tokio::test]
+async fn test_all_errors() {
+    let config = QueryConfig::default().all_errors(true).error_status(422);
+    let app = Router::new()
+        .route("/search", get(search))
+        .layer(Extension(config));
+    let (status, _, body) = call(&app, "/search?page=x&tags=a").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        serde_json::json!({"errors": [
+            {
+                "param": "page",
+                "message": "invalid value `x` for key `page`, expected u32: \
+                            invalid digit found in string",
+            },
+            {"param": "q", "message": "missing field `q`"},
+        ]})
+    );
+}
@@ -0,0 +1,76 @@
+//! Canonical query strings, checked against cases of the AWS Signature Version 4 test suite.
+
+use nb_serde_query::{query_map::QueryMap, to_canonical_string};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+const UNRESERVED: &str = "-._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+// (query string, canonical query string)
+const FIXTURES: &[(&str, &str)] = &[
+    // get-vanilla-query-order-key-case
+    ("Param2=value2&Param1=value1", "Param1=value1&Param2=value2"),
+    // get-vanilla-query-order-key
+    ("Param1=value2&Param1=value1", "Param1=value1&Param1=value2"),
+    // get-vanilla-query-order-value
+    ("Param1=value2&Param1=Value1", "Param1=Value1&Param1=value2"),
+    // get-vanilla-utf8-query
+    ("%E1%88%B4=bar", "%E1%88%B4=bar"),
+    (
+        "-._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz=\
+         -._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz",
+        "-._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz=\
+         -._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz",
+    ),
+];
+
+#[test]
+fn test_query_map_fixtures() {
+    for (s, canonical) in FIXTURES {
+        let m = QueryMap::parse(s).unwrap();
+        assert_eq!(m.to_canonical_string(), *canonical, "{}", s);
+    }
+}
+
+#[test]
+fn test_serialize_fixtures() {
+    let m = HashMap::from([("Param2", "value2"), ("Param1", "value1")]);
+    assert_eq!(to_canonical_string(&m).unwrap(), FIXTURES[0].1);
+    let m = HashMap::from([("Param1", vec!["value2", "Value1"])]);
+    assert_eq!(to_canonical_string(&m).unwrap(), FIXTURES[2].1);
+    let m = BTreeMap::from([("ሴ", "bar")]);
+    assert_eq!(to_canonical_string(&m).unwrap(), FIXTURES[3].1);
+    let m = BTreeMap::from([(UNRESERVED, UNRESERVED)]);
+    assert_eq!(
+        to_canonical_string(&m).unwrap(),
+        format!("{}={}", UNRESERVED, UNRESERVED)
+    );
+}
+
+#[derive(Serialize)]
+struct ListObjects {
+    prefix: String,
+    #[serde(rename = "max-keys")]
+    max_keys: u32,
+    delimiter: Option<String>,
+    marker: String,
+    tags: Vec<&'static str>,
+    #[serde(rename = "Action")]
+    action: &'static str,
+}
+
+#[test]
+fn test_struct() {
+    let v = ListObjects {
+        prefix: "photos/2024 summer".to_string(),
+        max_keys: 100,
+        delimiter: None,
+        marker: String::new(),
+        tags: vec!["b", "a+c", "a"],
+        action: "List",
+    };
+    assert_eq!(
+        to_canonical_string(&v).unwrap(),
+        "Action=List&marker=&max-keys=100&prefix=photos%2F2024%20summer&tags=a&tags=a%2Bc&tags=b"
+    );
+}
@@ -0,0 +1,187 @@
+//! Output and acceptance compared with `serde_urlencoded`, on fixtures both can represent.
+//!
+//! The known differences are asserted explicitly at the bottom of this file.
+
+use nb_serde_query::{
+    from_str, from_str_with,
+    options::{CompatMode, ParseOptions, SerializerOptions},
+    to_string, to_string_with,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Search {
+    q: String,
+    page: u32,
+    exact: bool,
+    ratio: f64,
+    sort: Option<String>,
+    c: char,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Order {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Listing {
+    order: Order,
+    #[serde(rename = "per page")]
+    per_page: Option<u8>,
+    offset: i64,
+}
+
+fn searches() -> Vec<Search> {
+    [
+        "",
+        "rust",
+        "a b",
+        "a+b",
+        "x*y",
+        "~home",
+        "a&b=c",
+        "100%",
+        "ünï",
+        "'quoted' (x)!",
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, q)| Search {
+        q: q.to_string(),
+        page: i as u32,
+        exact: i % 2 == 0,
+        ratio: [0.5, -2.25, 0.1][i % 3],
+        sort: (i % 3 == 0).then(|| format!("by {}", q)),
+        c: ['a', ' ', '*', '~', '+'][i % 5],
+    })
+    .collect()
+}
+
+fn listings() -> Vec<Listing> {
+    vec![
+        Listing {
+            order: Order::Asc,
+            per_page: Some(25),
+            offset: -1,
+        },
+        Listing {
+            order: Order::Desc,
+            per_page: None,
+            offset: 0,
+        },
+    ]
+}
+
+fn assert_compatible<T>(v: &T)
+where
+    T: Serialize + for<'de> Deserialize<'de> + PartialEq + Debug,
+{
+    let theirs = serde_urlencoded::to_string(v).unwrap();
+    let opts = SerializerOptions::new().compat(CompatMode::SerdeUrlencoded);
+    assert_eq!(to_string_with(v, &opts).unwrap(), theirs);
+
+    // either reads the other's output back, whatever the mode
+    let ours = to_string(v).unwrap();
+    assert_eq!(&from_str::<T>(&theirs).unwrap(), v, "{}", theirs);
+    assert_eq!(
+        &serde_urlencoded::from_str::<T>(&ours).unwrap(),
+        v,
+        "{}",
+        ours
+    );
+}
+
+#[test]
+fn test_compat_output() {
+    for v in searches() {
+        assert_compatible(&v);
+    }
+    for v in listings() {
+        assert_compatible(&v);
+    }
+}
+
+#[test]
+fn test_default_output_differences() {
+    // spaces, `*` and `~` are encoded by RFC 3986 rules unless in compat mode
+    let v = Listing {
+        order: Order::Asc,
+        per_page: Some(1),
+        offset: 0,
+    };
+    assert_eq!(to_string(&v).unwrap(), "order=asc&per%20page=1&offset=0");
+    assert_eq!(
+        serde_urlencoded::to_string(&v).unwrap(),
+        "order=asc&per+page=1&offset=0"
+    );
+    let v = &searches()[4];
+    assert_eq!(
+        to_string(v).unwrap(),
+        "q=x%2Ay&page=4&exact=true&ratio=-2.25&c=%2B"
+    );
+    assert_eq!(
+        serde_urlencoded::to_string(v).unwrap(),
+        "q=x*y&page=4&exact=true&ratio=-2.25&c=%2B"
+    );
+}
+
+#[test]
+fn test_floats() {
+    #[derive(Serialize)]
+    struct Scale {
+        f: f64,
+    }
+    let opts = SerializerOptions::new().compat(CompatMode::SerdeUrlencoded);
+    for f in [1.0, -0.0, 1e21, 1e-7] {
+        let theirs = serde_urlencoded::to_string(Scale { f }).unwrap();
+        let ours = to_string_with(&Scale { f }, &opts).unwrap();
+        // without `ryu`, floats are written as `FloatFormat::Decimal`, e.g. `1` for `1.0`
+        if cfg!(feature = "ryu") {
+            assert_eq!(ours, theirs);
+        } else {
+            assert_ne!(ours, theirs);
+        }
+    }
+}
+
+#[test]
+fn test_known_differences() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Tags {
+        tag: Vec<String>,
+    }
+    // sequences are written as repeated keys, which serde_urlencoded does not support at all
+    let v = Tags {
+        tag: vec!["a".to_string(), "b".to_string()],
+    };
+    assert_eq!(to_string(&v).unwrap(), "tag=a&tag=b");
+    assert!(serde_urlencoded::to_string(&v).is_err());
+    assert_eq!(from_str::<Tags>("tag=a&tag=b").unwrap(), v);
+    assert!(serde_urlencoded::from_str::<Tags>("tag=a&tag=b").is_err());
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Flag {
+        q: String,
+    }
+    // a repeated key is read as its first value rather than rejected
+    assert_eq!(from_str::<Flag>("q=a&q=b").unwrap().q, "a");
+    assert!(serde_urlencoded::from_str::<Flag>("q=a&q=b").is_err());
+    // a key without `=` is rejected rather than read as an empty value, unless asked to
+    assert!(from_str::<Flag>("q").is_err());
+    let opts = ParseOptions::new().bare_keys(true);
+    assert_eq!(from_str_with::<Flag>("q", &opts).unwrap().q, "");
+    assert_eq!(serde_urlencoded::from_str::<Flag>("q").unwrap().q, "");
+    // a malformed escape is rejected rather than kept as it is
+    assert!(from_str::<Flag>("q=%zz").is_err());
+    assert_eq!(
+        serde_urlencoded::from_str::<Flag>("q=%zz").unwrap().q,
+        "%zz"
+    );
+    // a leading `?` is skipped
+    assert_eq!(from_str::<Flag>("?q=a").unwrap().q, "a");
+    assert!(serde_urlencoded::from_str::<Flag>("?q=a").is_err());
+}
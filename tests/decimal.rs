@@ -0,0 +1,47 @@
+#![cfg(feature = "decimal")]
+
+use nb_serde_query::{error::ErrorKind, from_str, to_string};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Payment {
+    amount: Decimal,
+    fee: Option<Decimal>,
+}
+
+#[test]
+fn test_round_trip() {
+    for (s, amount) in [
+        ("amount=19.99", "19.99"),
+        ("amount=-0.01", "-0.01"),
+        ("amount=1000", "1000"),
+        (
+            "amount=7922816251426433759354395033.5",
+            "7922816251426433759354395033.5",
+        ),
+        (
+            "amount=0.0000000000000000000000000001",
+            "0.0000000000000000000000000001",
+        ),
+    ] {
+        let v: Payment = from_str(s).unwrap();
+        assert_eq!(v.amount, Decimal::from_str(amount).unwrap());
+        assert_eq!(v.fee, None);
+        assert_eq!(to_string(&v).unwrap(), s);
+    }
+
+    let v: Payment = from_str("amount=-250.000&fee=1.50").unwrap();
+    assert_eq!(v.fee, Some(Decimal::new(150, 2)));
+    assert_eq!(to_string(&v).unwrap(), "amount=-250.000&fee=1.50");
+}
+
+#[test]
+fn test_invalid() {
+    let err = from_str::<Payment>("amount=19.99&fee=1%2C50").unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidValue { ref key, .. } if key == "fee"));
+    assert!(err.message.starts_with("invalid value for key `fee`"));
+    assert!(from_str::<Payment>("amount=abc").is_err());
+    assert!(from_str::<Payment>("amount=").is_err());
+}
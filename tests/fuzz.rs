@@ -0,0 +1,129 @@
+//! Inputs found by the fuzz targets in `fuzz/`, checked in so they keep being exercised by
+//! `cargo test`.
+//!
+//! None of them may panic; they either parse or return an error.
+
+use nb_serde_query::{from_str, to_string};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SampleStruct {
+    q: String,
+    c: char,
+    exact: bool,
+    page: Option<u32>,
+    offset: i64,
+    ratio: f64,
+    status: Option<Status>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    ids: Vec<u16>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Marker;
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Point(i32, i32);
+
+#[derive(Debug, Deserialize)]
+struct Extras {
+    #[serde(default)]
+    unit: (),
+    marker: Option<Marker>,
+    pair: Option<(u8, String)>,
+    point: Option<Point>,
+    #[serde(flatten)]
+    rest: HashMap<String, IgnoredAny>,
+}
+
+#[test]
+fn test_no_panic() {
+    for s in [
+        "",
+        "&",
+        "=",
+        "&&==&",
+        "%",
+        "%0",
+        "%FF=%C3",
+        "q=&c=&exact=&offset=&ratio=",
+        "q&c&exact&offset&ratio",
+        "q=a&c=ab&exact=true&offset=1&ratio=1",
+        "q=a&c=%00&exact=true&offset=-1&ratio=NaN&page=18446744073709551616",
+        "q=a&c=b&exact=false&offset=1&ratio=1e309&status=half",
+        "q=a&q=b&c=b&c=d&exact=true&offset=1&ratio=1",
+        "q=a&c=b&exact=true&offset=1&ratio=1&ids=1&ids=x&ids=65536",
+        "q=a&c=b&exact=true&offset=1&ratio=1&tags[]=a&tags[0]=b&tags[1]=c",
+        "q=a&c=b&exact=true&offset=1&ratio=1&page[0]=1&status[]=open",
+        "q[]=a&q[1]=b&c[x]=1",
+        "[]=1&[0]=2&.=3&=4",
+    ] {
+        let _ = from_str::<SampleStruct>(s);
+        let _ = from_str::<HashMap<String, String>>(s);
+        let _ = from_str::<Extras>(s);
+    }
+}
+
+#[test]
+fn test_extras() {
+    // each of these used to hit an `unimplemented!()` in the deserializer
+    let v: Extras = from_str("unit=&marker=&pair=1&pair=a&point=3&point=-4&other=x").unwrap();
+    assert_eq!(v.unit, ());
+    assert_eq!(v.marker, Some(Marker));
+    assert_eq!(v.pair, Some((1, "a".to_string())));
+    assert_eq!(v.point, Some(Point(3, -4)));
+    assert_eq!(v.rest.len(), 1);
+
+    let err = from_str::<Extras>("unit=x").unwrap_err();
+    assert_eq!(
+        err.message,
+        "invalid value `x` for key `unit`, expected an empty value"
+    );
+    assert!(from_str::<Extras>("pair=1").is_err());
+    assert_eq!(
+        from_str::<Extras>("point=3,-4").unwrap().point,
+        Some(Point(3, -4))
+    );
+    assert!(from_str::<Extras>("point=1,2,3").is_err());
+}
+
+#[test]
+fn test_round_trip() {
+    for v in [
+        SampleStruct {
+            q: "&=%+".to_string(),
+            c: '&',
+            exact: false,
+            page: None,
+            offset: i64::MIN,
+            ratio: -0.0,
+            status: None,
+            tags: vec![],
+            ids: vec![],
+        },
+        SampleStruct {
+            q: String::new(),
+            c: '=',
+            exact: true,
+            page: Some(0),
+            offset: 0,
+            ratio: f64::MAX,
+            status: Some(Status::Closed),
+            tags: vec![String::new(), "a&b".to_string(), "=".to_string()],
+            ids: vec![0, u16::MAX],
+        },
+    ] {
+        let s = to_string(&v).unwrap();
+        assert_eq!(from_str::<SampleStruct>(&s).unwrap(), v, "{}", s);
+    }
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "http")]
+
+use http::Uri;
+use nb_serde_query::from_uri;
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Search {
+    q: Option<String>,
+    page: Option<u32>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[test]
+fn test_from_uri() {
+    let uri: Uri = "https://example.com/search?q=rust%20serde&page=2&tags=a&tags=b"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        from_uri::<Search>(&uri).unwrap(),
+        Search {
+            q: Some("rust serde".to_string()),
+            page: Some(2),
+            tags: vec!["a".to_string(), "b".to_string()],
+        }
+    );
+
+    for uri in ["/search", "/search?", "https://example.com"] {
+        let uri: Uri = uri.parse().unwrap();
+        assert_eq!(
+            from_uri::<Search>(&uri).unwrap(),
+            Search {
+                q: None,
+                page: None,
+                tags: vec![],
+            }
+        );
+    }
+
+    let uri: Uri = "/search?page=x".parse().unwrap();
+    assert!(from_uri::<Search>(&uri).is_err());
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "ipnet")]
+//! CIDR parameters of the `ipnet` crate, read and written as strings like `10.0.0.0/24`.
+
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use nb_serde_query::{error::ErrorKind, from_str, to_string};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Route {
+    cidr: IpNet,
+    v4: Option<Ipv4Net>,
+    v6: Option<Ipv6Net>,
+    #[serde(default)]
+    allow: Vec<IpNet>,
+}
+
+#[test]
+fn test_round_trip() {
+    let v = Route {
+        cidr: "10.0.0.0/24".parse().unwrap(),
+        v4: None,
+        v6: None,
+        allow: vec![],
+    };
+    let s = to_string(&v).unwrap();
+    assert_eq!(s, "cidr=10.0.0.0%2F24");
+    assert_eq!(from_str::<Route>(&s).unwrap(), v);
+
+    let v = Route {
+        cidr: "fd00::/8".parse().unwrap(),
+        v4: Some("192.168.1.0/30".parse().unwrap()),
+        v6: Some("2001:db8::/32".parse().unwrap()),
+        allow: vec!["10.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()],
+    };
+    let s = to_string(&v).unwrap();
+    assert_eq!(
+        s,
+        "cidr=fd00%3A%3A%2F8&v4=192.168.1.0%2F30&v6=2001%3Adb8%3A%3A%2F32\
+         &allow=10.0.0.0%2F8&allow=%3A%3A1%2F128"
+    );
+    assert_eq!(from_str::<Route>(&s).unwrap(), v);
+
+    // the slash and colons are read unencoded too
+    let v: Route = from_str("cidr=10.0.0.0/24&v6=fe80::/10").unwrap();
+    assert_eq!(v.cidr, "10.0.0.0/24".parse::<IpNet>().unwrap());
+    assert_eq!(v.v6.unwrap().prefix_len(), 10);
+}
+
+#[test]
+fn test_malformed() {
+    for (s, key, expected) in [
+        ("cidr=10.0.0.0", "cidr", "IPv4 or IPv6 network address"),
+        ("cidr=10.0.0.0/33", "cidr", "IPv4 or IPv6 network address"),
+        ("cidr=::/0&v4=::/0", "v4", "IPv4 network address"),
+        ("cidr=::/0&v6=10.0.0.0/8", "v6", "IPv6 network address"),
+        (
+            "cidr=::/0&allow=::/0&allow=x",
+            "allow",
+            "IPv4 or IPv6 network address",
+        ),
+    ] {
+        let err = from_str::<Route>(s).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::InvalidValue {
+                key: key.to_string(),
+                expected: expected.to_string(),
+            },
+            "{}",
+            s
+        );
+        assert_eq!(
+            err.message,
+            format!("invalid value for key `{}`, expected {}", key, expected)
+        );
+    }
+}
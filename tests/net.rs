@@ -0,0 +1,99 @@
+//! IP and socket address parameters, read and written through their string impls.
+
+use nb_serde_query::{error::ErrorKind, from_str, to_string, CommaSeparated, Separated};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Target {
+    ip: IpAddr,
+    v6: Option<Ipv6Addr>,
+    addr: Option<SocketAddr>,
+    #[serde(default)]
+    peers: Vec<IpAddr>,
+}
+
+#[test]
+fn test_round_trip() {
+    let v = Target {
+        ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        v6: None,
+        addr: Some("10.0.0.1:8080".parse().unwrap()),
+        peers: vec![],
+    };
+    let s = to_string(&v).unwrap();
+    assert_eq!(s, "ip=10.0.0.1&addr=10.0.0.1%3A8080");
+    assert_eq!(from_str::<Target>(&s).unwrap(), v);
+
+    // the colons of IPv6 addresses and ports, and the brackets, are encoded
+    let v = Target {
+        ip: IpAddr::V6(Ipv6Addr::LOCALHOST),
+        v6: Some("fe80::1:2".parse().unwrap()),
+        addr: Some("[2001:db8::1]:443".parse().unwrap()),
+        peers: vec![
+            "::ffff:10.0.0.2".parse().unwrap(),
+            "10.0.0.3".parse().unwrap(),
+        ],
+    };
+    let s = to_string(&v).unwrap();
+    assert_eq!(
+        s,
+        "ip=%3A%3A1&v6=fe80%3A%3A1%3A2&addr=%5B2001%3Adb8%3A%3A1%5D%3A443\
+         &peers=%3A%3Affff%3A10.0.0.2&peers=10.0.0.3"
+    );
+    assert_eq!(from_str::<Target>(&s).unwrap(), v);
+}
+
+#[test]
+fn test_unencoded() {
+    // colons and brackets are read as they are
+    let v: Target = from_str("ip=::1&v6=fe80::1&addr=[::1]:8080&peers=10.0.0.1").unwrap();
+    assert_eq!(v.ip, IpAddr::V6(Ipv6Addr::LOCALHOST));
+    assert_eq!(v.v6, Some("fe80::1".parse().unwrap()));
+    assert_eq!(v.addr.unwrap().port(), 8080);
+    assert!(v.addr.unwrap().is_ipv6());
+    assert_eq!(v.peers, [IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Peers {
+        v4: CommaSeparated<Ipv4Addr>,
+        any: Separated<IpAddr, ' '>,
+    }
+    let v: Peers = from_str("v4=10.0.0.1,10.0.0.2&any=::1%2010.0.0.3").unwrap();
+    assert_eq!(
+        v.v4.0,
+        [Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)]
+    );
+    assert_eq!(v.any.0.len(), 2);
+    let s = to_string(&v).unwrap();
+    assert_eq!(s, "v4=10.0.0.1,10.0.0.2&any=%3A%3A1%2010.0.0.3");
+    assert_eq!(from_str::<Peers>(&s).unwrap(), v);
+}
+
+#[test]
+fn test_malformed() {
+    for (s, key, expected) in [
+        ("ip=10.0.0", "ip", "IP address"),
+        ("ip=::1&v6=10.0.0.1", "v6", "IPv6 address"),
+        ("ip=::1&addr=10.0.0.1", "addr", "socket address"),
+        ("ip=::1&addr=10.0.0.1:65536", "addr", "socket address"),
+        ("ip=::1&addr=::1:80", "addr", "socket address"),
+        ("ip=::1&peers=::1&peers=x", "peers", "IP address"),
+    ] {
+        let err = from_str::<Target>(s).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::InvalidValue {
+                key: key.to_string(),
+                expected: expected.to_string(),
+            },
+            "{}",
+            s
+        );
+        assert_eq!(
+            err.message,
+            format!("invalid value for key `{}`, expected {}", key, expected)
+        );
+        assert!(err.to_string().ends_with(" syntax"), "{}", err);
+    }
+}
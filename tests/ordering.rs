@@ -0,0 +1,99 @@
+//! Repeated values of a key reach sequences in the order they appear in the input, however
+//! they are interleaved with other keys.
+
+use nb_serde_query::{
+    from_str, from_str_partial, from_str_with,
+    options::{Duplicates, ParseOptions},
+    query_map::QueryMap,
+};
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Listing {
+    ids: Vec<u32>,
+    page: u32,
+    tags: Vec<String>,
+}
+
+#[test]
+fn test_interleaved_keys() {
+    let v: Listing = from_str("ids=3&page=1&ids=1&tags=b&ids=2&tags=a").unwrap();
+    assert_eq!(v.ids, vec![3, 1, 2]);
+    assert_eq!(v.tags, vec!["b", "a"]);
+
+    let v: Listing = from_str("tags=z&ids=2&tags=y&page=1&tags=x&ids=1&ids=2").unwrap();
+    assert_eq!(v.ids, vec![2, 1, 2]);
+    assert_eq!(v.tags, vec!["z", "y", "x"]);
+}
+
+#[test]
+fn test_many_values() {
+    // a permutation of 0..1000 interleaved with another key
+    let ids: Vec<u32> = (0..1000).map(|i| (i * 7919) % 1000).collect();
+    let s = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| format!("ids={}&tags={}", id, 999 - i))
+        .collect::<Vec<_>>()
+        .join("&");
+    let v: Listing = from_str(&format!("page=1&{}", s)).unwrap();
+    assert_eq!(v.ids, ids);
+    let tags: Vec<String> = (0..1000).rev().map(|i| i.to_string()).collect();
+    assert_eq!(v.tags, tags);
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+enum Kind {
+    A,
+    B,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Mixed {
+    floats: Vec<f64>,
+    flags: Vec<bool>,
+    chars: Vec<char>,
+    kinds: Vec<Kind>,
+    signed: Option<Vec<i64>>,
+}
+
+#[test]
+fn test_value_types() {
+    let v: Mixed = from_str(
+        "floats=2.5&flags=true&kinds=B&chars=z&floats=-1&signed=5&flags=false&kinds=A\
+         &chars=a&floats=0.1&signed=-5&kinds=B&flags=true",
+    )
+    .unwrap();
+    assert_eq!(v.floats, vec![2.5, -1.0, 0.1]);
+    assert_eq!(v.flags, vec![true, false, true]);
+    assert_eq!(v.chars, vec!['z', 'a']);
+    assert_eq!(v.kinds, vec![Kind::B, Kind::A, Kind::B]);
+    assert_eq!(v.signed, Some(vec![5, -5]));
+}
+
+#[test]
+fn test_indexed_order() {
+    // indexed elements are ordered by their index instead
+    let v: Listing = from_str("ids[2]=30&page=1&tags=a&ids[0]=10&ids[1]=20").unwrap();
+    assert_eq!(v.ids, vec![10, 20, 30]);
+}
+
+#[test]
+fn test_duplicates_and_leftovers() {
+    let opts = ParseOptions::new().duplicates(Duplicates::Last);
+    let v: Listing = from_str_with("ids=3&page=1&ids=1&page=2&tags=a", &opts).unwrap();
+    assert_eq!(v.ids, vec![1]);
+    assert_eq!(v.page, 2);
+
+    let (v, leftovers) =
+        from_str_partial::<Listing>("x=3&ids=1&page=1&y=1&x=1&tags=a&x=2").unwrap();
+    assert_eq!(v.ids, vec![1]);
+    assert_eq!(
+        leftovers,
+        [("x", "3"), ("x", "1"), ("x", "2"), ("y", "1")]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+    );
+
+    let m = QueryMap::parse("ids=3&page=1&ids=1&ids=2").unwrap();
+    assert_eq!(m.get_all("ids"), ["3", "1", "2"]);
+}
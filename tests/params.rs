@@ -0,0 +1,117 @@
+#![cfg(feature = "params")]
+
+use nb_serde_query::{
+    from_str, params::CursorPage, params::PageLimits, params::Pagination, to_string,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ListUsers {
+    q: Option<String>,
+    #[serde(flatten)]
+    page: Pagination,
+}
+
+#[test]
+fn test_pagination() {
+    let v = ListUsers {
+        q: Some("ann".to_string()),
+        page: Pagination {
+            page: Some(3),
+            per_page: Some(50),
+        },
+    };
+    let s = "q=ann&page=3&per_page=50";
+    assert_eq!(to_string(&v).unwrap(), s);
+    assert_eq!(from_str::<ListUsers>(s).unwrap(), v);
+    assert_eq!((v.page.limit(), v.page.offset()), (50, 100));
+
+    let v: ListUsers = from_str("q=ann").unwrap();
+    assert_eq!(v.page, Pagination::default());
+    assert_eq!(to_string(&v).unwrap(), "q=ann");
+    assert_eq!((v.page.limit(), v.page.offset()), (20, 0));
+
+    let v: ListUsers = from_str("per_page=1000&page=2").unwrap();
+    let limits = PageLimits::new(10, 200);
+    assert_eq!(
+        (v.page.limit_with(&limits), v.page.offset_with(&limits)),
+        (200, 200)
+    );
+
+    assert!(from_str::<ListUsers>("page=-1").is_err());
+    assert!(from_str::<ListUsers>("per_page=many").is_err());
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Feed {
+    #[serde(flatten)]
+    page: CursorPage,
+    tag: Option<String>,
+}
+
+#[test]
+fn test_cursor_page() {
+    for cursor in [
+        "eyJpZCI6NDJ9",
+        "a b&c",
+        "123",
+        "-7",
+        "+7",
+        "007",
+        "true",
+        "1.5",
+    ] {
+        let v = Feed {
+            page: CursorPage {
+                cursor: Some(cursor.to_string()),
+                limit: Some(10),
+            },
+            tag: Some("rust".to_string()),
+        };
+        assert_eq!(from_str::<Feed>(&to_string(&v).unwrap()).unwrap(), v);
+    }
+
+    let v: Feed = from_str("tag=rust").unwrap();
+    assert_eq!(v.page, CursorPage::default());
+    assert_eq!(v.page.limit(), 20);
+    let v: CursorPage = from_str("cursor=00123&limit=5").unwrap();
+    assert_eq!(v.cursor.as_deref(), Some("00123"));
+    let v: Feed = from_str("cursor=00123&limit=5").unwrap();
+    assert_eq!(v.page.cursor.as_deref(), Some("00123"));
+}
+
+#[cfg(feature = "chrono")]
+mod date_range {
+    use super::*;
+    use chrono::{DateTime, NaiveDate, Utc};
+    use nb_serde_query::params::DateRange;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Report {
+        #[serde(flatten)]
+        range: DateRange,
+        group: String,
+    }
+
+    #[test]
+    fn test_date_range() {
+        let v = Report {
+            range: DateRange {
+                from: NaiveDate::from_ymd_opt(2024, 1, 1),
+                to: NaiveDate::from_ymd_opt(2024, 1, 31),
+            },
+            group: "day".to_string(),
+        };
+        let s = "from=2024-01-01&to=2024-01-31&group=day";
+        assert_eq!(to_string(&v).unwrap(), s);
+        assert_eq!(from_str::<Report>(s).unwrap(), v);
+
+        let v: Report = from_str("group=week&to=2024-02-01").unwrap();
+        assert_eq!(v.range.from, None);
+        assert!(from_str::<Report>("group=day&from=2024-13-01").is_err());
+
+        let v: DateRange<DateTime<Utc>> =
+            from_str("from=2024-01-01T00:00:00Z&to=2024-01-02T12:00:00Z").unwrap();
+        assert!(v.contains(&"2024-01-02T00:00:00Z".parse().unwrap()));
+    }
+}
@@ -0,0 +1,83 @@
+//! A struct and a `HashMap<String, String>` read from the same input see the same values, under
+//! each of the parse options.
+//!
+//! `deny_unknown` is left out: unknown keys are an error for a struct only, a map has none.
+
+use nb_serde_query::{
+    error::ErrorKind,
+    from_str_with,
+    options::{Duplicates, EmptyValue, KeyTransform, ParseOptions},
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize)]
+struct Fields {
+    a: Option<String>,
+    b: Option<String>,
+    c: Option<String>,
+    d: Option<String>,
+    #[serde(rename = "x.y")]
+    xy: Option<String>,
+}
+
+const NAMES: [&str; 5] = ["a", "b", "c", "d", "x.y"];
+
+const INPUTS: [&str; 5] = [
+    "a=1&b=&c=%20x%20&a=2&d=p+q%2Br",
+    "B=3&x.y=4&c=a%3Bb&c=&unknown=5",
+    "a=1;b=2&c=3:4&d==",
+    "a&b=%7A%7a&c=%E2%9C%93&x%2Ey=5",
+    "?d=1&d=2&d=3#c=4",
+];
+
+fn read_struct(s: &str, opts: &ParseOptions) -> Result<Vec<Option<String>>, ErrorKind> {
+    let v: Fields = from_str_with(s, opts).map_err(|e| e.kind)?;
+    Ok(vec![v.a, v.b, v.c, v.d, v.xy])
+}
+
+fn read_map(s: &str, opts: &ParseOptions) -> Result<Vec<Option<String>>, ErrorKind> {
+    let mut m: HashMap<String, String> = from_str_with(s, opts).map_err(|e| e.kind)?;
+    Ok(NAMES.iter().map(|name| m.remove(*name)).collect())
+}
+
+#[test]
+fn test_struct_and_map() {
+    let options = [
+        ParseOptions::new(),
+        ParseOptions::new().duplicates(Duplicates::First),
+        ParseOptions::new().duplicates(Duplicates::Last),
+        ParseOptions::new().duplicates(Duplicates::Error),
+        ParseOptions::new().empty_value(EmptyValue::AsNone),
+        ParseOptions::new().empty_value(EmptyValue::Error),
+        ParseOptions::new().trim_values(true),
+        ParseOptions::new().raw_values(true),
+        ParseOptions::new().max_values_per_key(2),
+        ParseOptions::new().max_pairs(4),
+        ParseOptions::new().max_value_len(2),
+        ParseOptions::new().deny_fragment(true),
+        ParseOptions::new().deny_duplicate_values(true),
+        ParseOptions::new().separators(&['&', ';']),
+        ParseOptions::new().kv_separator(':'),
+        ParseOptions::new().bare_keys(true),
+        ParseOptions::new().nesting_separator('.'),
+        ParseOptions::new().key_transform(KeyTransform::Lowercase),
+        ParseOptions::new().value_decoder("c", Box::new(|v| Ok(v.to_uppercase()))),
+        ParseOptions::new()
+            .duplicates(Duplicates::Last)
+            .empty_value(EmptyValue::AsNone)
+            .trim_values(true)
+            .nesting_separator('.'),
+    ];
+    for opts in &options {
+        for input in INPUTS {
+            assert_eq!(
+                read_struct(input, opts),
+                read_map(input, opts),
+                "{} with {:?}",
+                input,
+                opts
+            );
+        }
+    }
+}
@@ -0,0 +1,96 @@
+#![cfg(feature = "poem")]
+//! The poem `Query` extractor, through a routed endpoint.
+
+use nb_serde_query::{
+    options::ParseOptions,
+    poem::{Query, QueryConfig},
+};
+use poem::{get, handler, http::StatusCode, Endpoint, EndpointExt, Request, Route};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct Search {
+    q: String,
+    page: Option<u32>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[handler]
+fn search(Query(v): Query<Search>) -> String {
+    format!(
+        "{} page {} tags {}",
+        v.q,
+        v.page.unwrap_or(1),
+        v.tags.join("+")
+    )
+}
+
+async fn call(app: &impl Endpoint, uri: &str) -> (StatusCode, String) {
+    let resp = app
+        .get_response(Request::builder().uri_str(uri).finish())
+        .await;
+    (resp.status(), resp.into_body().into_string().await.unwrap())
+}
+
+#[tokio::test]
+async fn test_extractor() {
+    let app = Route::new().at("/search", get(search));
+    assert_eq!(
+        call(&app, "/search?q=rust&tags=a&page=2&tags=b").await,
+        (StatusCode::OK, "rust page 2 tags a+b".to_string())
+    );
+    assert_eq!(
+        call(&app, "/search?q=rust").await,
+        (StatusCode::OK, "rust page 1 tags ".to_string())
+    );
+    assert_eq!(
+        call(&app, "/search?page=2").await,
+        (StatusCode::BAD_REQUEST, "missing field `q`".to_string())
+    );
+    assert_eq!(
+        call(&app, "/search").await,
+        (StatusCode::BAD_REQUEST, "missing field `q`".to_string())
+    );
+    assert_eq!(
+        call(&app, "/search?q=rust&page=x").await,
+        (
+            StatusCode::BAD_REQUEST,
+            "invalid value `x` for key `page`, expected u32: invalid digit found in string"
+                .to_string()
+        )
+    );
+
+    let app = Route::new()
+        .at("/search", get(search))
+        .data(QueryConfig::default().options(ParseOptions::new().max_pairs(2)));
+    let (status, body) = call(&app, "/search?q=rust&tags=a&tags=b").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        serde_json::json!({
+            "error": "too many pairs, at most 2 allowed",
+            "limit": {"kind": "pairs", "key": null, "max": 2},
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_all_errors() {
+    let app = Route::new()
+        .at("/search", get(search))
+        .data(QueryConfig::default().all_errors(true).error_status(422));
+    let (status, body) = call(&app, "/search?page=x&tags=a").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        serde_json::json!({"errors": [
+            {
+                "param": "page",
+                "message": "invalid value `x` for key `page`, expected u32: \
+                            invalid digit found in string",
+            },
+            {"param": "q", "message": "missing field `q`"},
+        ]})
+    );
+}
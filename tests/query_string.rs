@@ -0,0 +1,107 @@
+//! `query_string!` writes the same bytes as `to_string` for a struct with the same fields.
+
+use nb_serde_query::{query_string, to_string, utils::QueryValue};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Beacon<'a> {
+    event: &'a str,
+    ms: f64,
+    ok: bool,
+    retries: u8,
+}
+
+#[derive(Serialize)]
+struct Metrics {
+    name: String,
+    delta: i64,
+    total: u128,
+    ratio: f32,
+    unit: char,
+}
+
+#[derive(Serialize)]
+struct Tagged<'a> {
+    host: Option<&'a str>,
+    tags: Vec<&'a str>,
+    sizes: [u16; 3],
+    note: Option<String>,
+}
+
+#[test]
+fn test_same_as_to_string() {
+    for (event, ms, ok, retries) in [
+        ("page view", 12.5, true, 0),
+        ("click&go=1", -0.0000001, false, 255),
+        ("ü/#?+%", 1e21, true, 7),
+        ("", f64::MAX, false, 1),
+    ] {
+        let v = Beacon {
+            event,
+            ms,
+            ok,
+            retries,
+        };
+        assert_eq!(
+            query_string!("event" => event, "ms" => ms, "ok" => ok, "retries" => retries),
+            to_string(&v).unwrap()
+        );
+    }
+
+    for (name, delta, total, ratio, unit) in [
+        ("cpu load".to_string(), i64::MIN, u128::MAX, 0.1, '%'),
+        ("a~b-c_d.e".to_string(), 0, 0, f32::NAN, 'é'),
+    ] {
+        let s = query_string!(
+            "name" => name,
+            "delta" => delta,
+            "total" => total,
+            "ratio" => ratio,
+            "unit" => unit,
+        );
+        let v = Metrics {
+            name,
+            delta,
+            total,
+            ratio,
+            unit,
+        };
+        assert_eq!(s, to_string(&v).unwrap());
+    }
+
+    let v = Tagged {
+        host: Some("a b"),
+        tags: vec!["x", "y&z"],
+        sizes: [1, 2, 3],
+        note: None,
+    };
+    assert_eq!(
+        query_string!("host" => v.host, "tags" => v.tags, "sizes" => v.sizes, "note" => v.note),
+        to_string(&v).unwrap()
+    );
+    let v = Tagged {
+        host: None,
+        tags: vec![],
+        sizes: [0; 3],
+        note: Some(String::new()),
+    };
+    assert_eq!(
+        query_string!("host" => v.host, "tags" => v.tags, "sizes" => v.sizes, "note" => v.note),
+        to_string(&v).unwrap()
+    );
+    assert_eq!(query_string!(), "");
+}
+
+#[test]
+fn test_reuse_buffer() {
+    let mut out = String::with_capacity(64);
+    for page in [1u32, 2] {
+        out.clear();
+        "a b".push_pairs("q", &mut out);
+        page.push_pairs("page", &mut out);
+        None::<bool>.push_pairs("debug", &mut out);
+        assert_eq!(out, format!("q=a%20b&page={}", page));
+    }
+    // keys are encoded like values
+    assert_eq!(query_string!("a b" => 1, "c&d" => "e"), "a%20b=1&c%26d=e");
+}
@@ -0,0 +1,479 @@
+//! `from_str(&to_string(&x)?)? == x` for every struct composed of supported types.
+//!
+//! The known exceptions are asserted explicitly at the bottom of this file.
+
+use nb_serde_query::{error::ErrorKind, from_str, to_string, Array};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::rc::Rc;
+use std::sync::Arc;
+
+const STRINGS: &[&str] = &[
+    "",
+    "a",
+    "hello world",
+    "a&b",
+    "k=v",
+    "a=b&c=d",
+    "100%",
+    "%41",
+    "%zz",
+    "+",
+    "1+1=2",
+    "#frag",
+    "?q",
+    "a,b;c",
+    "[]{}",
+    "\"quoted\"",
+    "ünïcödé",
+    "日本語",
+    "🎉",
+    "  padded  ",
+];
+
+const CHARS: &[char] = &['a', '&', '=', '%', '+', ' ', '#', 'ß', '🎉'];
+
+const INTS: &[i64] = &[
+    0,
+    1,
+    -1,
+    i64::MIN,
+    i64::MAX,
+    i32::MIN as i64,
+    u32::MAX as i64,
+];
+
+const FLOATS: &[f64] = &[
+    0.0,
+    -0.0,
+    0.1,
+    -2.5,
+    1e300,
+    -1e-300,
+    f64::MIN_POSITIVE,
+    f64::MAX,
+    f64::EPSILON,
+    f64::INFINITY,
+    f64::NEG_INFINITY,
+];
+
+fn assert_round_trip<T>(v: &T)
+where
+    T: Serialize + for<'de> Deserialize<'de> + PartialEq + Debug,
+{
+    let s = to_string(v).unwrap_or_else(|e| panic!("failed to serialize {:?}: {}", v, e));
+    let back: T = from_str(&s).unwrap_or_else(|e| panic!("failed to parse {:?}: {}", s, e));
+    assert_eq!(&back, v, "round trip through {:?}", s);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Scalars {
+    s: String,
+    c: char,
+    b: bool,
+    i8: i8,
+    i16: i16,
+    i32: i32,
+    i64: i64,
+    i128: i128,
+    u8: u8,
+    u16: u16,
+    u32: u32,
+    u64: u64,
+    u128: u128,
+    f32: f32,
+    f64: f64,
+}
+
+#[test]
+fn test_scalars() {
+    for (i, s) in STRINGS.iter().enumerate() {
+        let n = INTS[i % INTS.len()];
+        assert_round_trip(&Scalars {
+            s: s.to_string(),
+            c: CHARS[i % CHARS.len()],
+            b: i % 2 == 0,
+            i8: n as i8,
+            i16: n as i16,
+            i32: n as i32,
+            i64: n,
+            i128: n as i128 * i64::MAX as i128,
+            u8: n as u8,
+            u16: n as u16,
+            u32: n as u32,
+            u64: n as u64,
+            u128: n as u128,
+            f32: FLOATS[i % FLOATS.len()] as f32,
+            f64: FLOATS[i % FLOATS.len()],
+        });
+    }
+    assert_round_trip(&Scalars {
+        s: String::new(),
+        c: '\0',
+        b: false,
+        i8: i8::MIN,
+        i16: i16::MIN,
+        i32: i32::MIN,
+        i64: i64::MIN,
+        i128: i128::MIN,
+        u8: u8::MAX,
+        u16: u16::MAX,
+        u32: u32::MAX,
+        u64: u64::MAX,
+        u128: u128::MAX,
+        f32: f32::MIN,
+        f64: f64::MIN,
+    });
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Containers {
+    tags: Vec<String>,
+    ids: Vec<i64>,
+    name: Option<String>,
+    limit: Option<i32>,
+    hobbies: Option<Vec<String>>,
+    json: Array<String>,
+}
+
+#[test]
+fn test_containers() {
+    // empty sequences outside an Option are an exception, see below
+    for i in 1..STRINGS.len() {
+        let strings: Vec<String> = STRINGS[..i].iter().map(|s| s.to_string()).collect();
+        assert_round_trip(&Containers {
+            tags: strings.clone(),
+            ids: INTS[..=i % INTS.len()].to_vec(),
+            name: (i % 2 == 0).then(|| STRINGS[i].to_string()),
+            limit: (i % 3 == 0).then_some(i as i32),
+            hobbies: (i > 1).then(|| strings.clone()),
+            json: Array(strings),
+        });
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Mixed {
+    ids: Array<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    scores: Array<i32>,
+    #[serde(default)]
+    pages: Vec<u8>,
+}
+
+#[test]
+fn test_array_and_vec() {
+    // the JSON of the `Array` fields is full of `[`, `,`, `"` and `=`, none of which may leak into
+    // the repeated `tags` and `pages` pairs around it
+    for i in 0..STRINGS.len() {
+        assert_round_trip(&Mixed {
+            ids: Array(STRINGS[i..].iter().map(|s| s.to_string()).collect()),
+            tags: STRINGS[..i].iter().map(|s| s.to_string()).collect(),
+            scores: Array(vec![i as i32, -1, i32::MAX]),
+            pages: (0..i as u8).collect(),
+        });
+    }
+
+    // written by hand, JSON is usually left unencoded
+    let v: Mixed =
+        from_str(r#"tags=a&ids=["YQ==","b,c","[d]"]&tags=b&scores=[1,-2]&pages=3"#).unwrap();
+    assert_eq!(
+        v,
+        Mixed {
+            ids: Array(vec!["YQ==".into(), "b,c".into(), "[d]".into()]),
+            tags: vec!["a".into(), "b".into()],
+            scores: Array(vec![1, -2]),
+            pages: vec![3],
+        }
+    );
+}
+
+// A DTO generic over its items, shared with serde_json, without bounds of its own.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Page<T> {
+    items: Array<T>,
+    next: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    name: String,
+    score: i64,
+}
+
+// Each layer escapes exactly once: JSON escapes `"` and `\` in the value, percent-encoding
+// the rest, and a query string reads back the same as JSON does.
+fn assert_shared_with_json<T>(v: &Page<T>, query: &str, json: &str)
+where
+    T: Serialize + for<'de> Deserialize<'de> + PartialEq + Debug,
+{
+    assert_eq!(to_string(v).unwrap(), query);
+    assert_eq!(&from_str::<Page<T>>(query).unwrap(), v);
+    assert_eq!(serde_json::to_string(v).unwrap(), json);
+    assert_eq!(&serde_json::from_str::<Page<T>>(json).unwrap(), v);
+}
+
+#[test]
+fn test_generic_pages() {
+    assert_shared_with_json(
+        &Page {
+            items: Array(vec![1i64, -2, i64::MAX]),
+            next: None,
+        },
+        "items=%5B1%2C-2%2C9223372036854775807%5D",
+        r#"{"items":[1,-2,9223372036854775807],"next":null}"#,
+    );
+    assert_shared_with_json(
+        &Page {
+            items: Array(vec![
+                r#"a"b"#.to_string(),
+                r"c\d".to_string(),
+                "%41 &+".to_string(),
+            ]),
+            next: Some("x&y".to_string()),
+        },
+        "items=%5B%22a%5C%22b%22%2C%22c%5C%5Cd%22%2C%22%2541%20%26%2B%22%5D&next=x%26y",
+        r#"{"items":["a\"b","c\\d","%41 &+"],"next":"x&y"}"#,
+    );
+    assert_shared_with_json(
+        &Page {
+            items: Array(vec![Entry {
+                name: "=\"é\"".to_string(),
+                score: -1,
+            }]),
+            next: Some(String::new()),
+        },
+        "items=%5B%7B%22name%22%3A%22%3D%5C%22%C3%A9%5C%22%22%2C%22score%22%3A-1%7D%5D&next=",
+        r#"{"items":[{"name":"=\"é\"","score":-1}],"next":""}"#,
+    );
+    for i in 0..STRINGS.len() {
+        assert_round_trip(&Page {
+            items: Array(STRINGS[i..].iter().map(|s| s.to_string()).collect()),
+            next: STRINGS.get(i + 1).map(|s| s.to_string()),
+        });
+    }
+
+    // JSON written before `Array` was an array there, a string of JSON, still reads
+    let v: Page<i64> = serde_json::from_str(r#"{"items":"[1,2]","next":null}"#).unwrap();
+    assert_eq!(v.items.0, [1, 2]);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Optionals {
+    a: Option<String>,
+    b: Option<u64>,
+    c: Option<Vec<bool>>,
+}
+
+#[test]
+fn test_all_absent() {
+    let v = Optionals {
+        a: None,
+        b: None,
+        c: None,
+    };
+    assert_eq!(to_string(&v).unwrap(), "");
+    assert_round_trip(&v);
+    assert_round_trip(&Optionals {
+        a: Some(String::new()),
+        b: Some(0),
+        c: Some(vec![true, false]),
+    });
+}
+
+#[test]
+fn test_maps() {
+    let m: HashMap<String, String> = STRINGS
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| !s.is_empty())
+        .map(|(i, s)| (s.to_string(), STRINGS[(i + 1) % STRINGS.len()].to_string()))
+        .collect();
+    assert_round_trip(&m);
+
+    let m: HashMap<String, Vec<i64>> = STRINGS
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| !s.is_empty())
+        .map(|(i, s)| (s.to_string(), INTS[..i % INTS.len() + 1].to_vec()))
+        .collect();
+    assert_round_trip(&m);
+}
+
+#[test]
+fn test_scalar_keys() {
+    let m: HashMap<u32, String> = STRINGS
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (i as u32 * 7, s.to_string()))
+        .collect();
+    assert_round_trip(&m);
+
+    let m: HashMap<i64, i64> = INTS.iter().map(|n| (*n, n / 2)).collect();
+    assert_round_trip(&m);
+
+    let m: BTreeMap<char, i32> = CHARS
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (*c, INTS[i % INTS.len()] as i32))
+        .collect();
+    assert_round_trip(&m);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Chars {
+    c: char,
+    cs: Vec<char>,
+}
+
+#[test]
+fn test_chars() {
+    let ascii: Vec<char> = (' '..='~').collect();
+    for c in ascii
+        .iter()
+        .chain(CHARS)
+        .chain(['é', '中', '\u{7f}', '\0'].iter())
+    {
+        assert_round_trip(&Chars {
+            c: *c,
+            cs: vec![*c, 'a', *c],
+        });
+    }
+    assert_round_trip(&Chars { c: 'x', cs: ascii });
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Binary {
+    data: ByteBuf,
+    digest: Option<ByteBuf>,
+}
+
+#[test]
+fn test_bytes() {
+    let all: Vec<u8> = (0..=255).collect();
+    for i in 0..all.len() {
+        assert_round_trip(&Binary {
+            data: ByteBuf::from(&all[i..]),
+            digest: (i % 2 == 0).then(|| ByteBuf::from(&all[..i % 7])),
+        });
+    }
+}
+
+// `Rc` and `Arc` need the `rc` feature of serde
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Pointers<'a> {
+    boxed: Box<str>,
+    rc: Rc<str>,
+    arc: Arc<str>,
+    number: Box<u32>,
+    #[serde(borrow)]
+    cow: Cow<'a, str>,
+    shared: Option<Arc<str>>,
+    #[serde(default)]
+    names: Vec<Box<str>>,
+}
+
+#[test]
+fn test_pointers() {
+    for (i, s) in STRINGS.iter().enumerate() {
+        let v = Pointers {
+            boxed: (*s).into(),
+            rc: (*s).into(),
+            arc: (*s).into(),
+            number: Box::new(i as u32),
+            cow: Cow::Owned(s.to_string()),
+            shared: (i % 2 == 0).then(|| (*s).into()),
+            names: STRINGS[..i].iter().map(|s| (*s).into()).collect(),
+        };
+        let s = to_string(&v).unwrap();
+        assert_eq!(
+            from_str::<Pointers>(&s).unwrap(),
+            v,
+            "round trip through {:?}",
+            s
+        );
+    }
+
+    // values without escapes are borrowed from the input
+    let v: Pointers = from_str("boxed=a&rc=b&arc=c&number=1&cow=d&names=e").unwrap();
+    assert!(matches!(v.cow, Cow::Borrowed("d")));
+    let v: Pointers = from_str("boxed=a&rc=b&arc=c&number=1&cow=d%20e&names=e").unwrap();
+    assert!(matches!(v.cow, Cow::Owned(_)));
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Defaulted {
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[test]
+fn test_exceptions() {
+    // NaN round trips, but is never equal to itself
+    let v: Scalars = from_str(
+        &to_string(&Scalars {
+            s: String::new(),
+            c: 'a',
+            b: true,
+            i8: 0,
+            i16: 0,
+            i32: 0,
+            i64: 0,
+            i128: 0,
+            u8: 0,
+            u16: 0,
+            u32: 0,
+            u64: 0,
+            u128: 0,
+            f32: f32::NAN,
+            f64: f64::NAN,
+        })
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(v.f32.is_nan() && v.f64.is_nan());
+
+    // an empty sequence is written as no pairs at all, so a field without `#[serde(default)]`
+    // is missing
+    let v = Containers {
+        tags: vec![],
+        ids: vec![1],
+        name: None,
+        limit: None,
+        hobbies: None,
+        json: Array(vec![]),
+    };
+    let err = from_str::<Containers>(&to_string(&v).unwrap()).unwrap_err();
+    assert_eq!(
+        err.kind,
+        ErrorKind::MissingField {
+            field: "tags".to_string()
+        }
+    );
+    let back: Defaulted = from_str(&to_string(&Defaulted { tags: vec![] }).unwrap()).unwrap();
+    assert_eq!(back.tags, Vec::<String>::new());
+
+    // an empty sequence inside an Option is indistinguishable from None
+    let v = Containers {
+        tags: vec![String::new()],
+        ids: vec![1],
+        name: None,
+        limit: None,
+        hobbies: Some(vec![]),
+        json: Array(vec![]),
+    };
+    let back: Containers = from_str(&to_string(&v).unwrap()).unwrap();
+    assert_eq!(back.hobbies, None);
+
+    // and a single empty string inside an Option is read back as an empty sequence
+    let v = Containers {
+        hobbies: Some(vec![String::new()]),
+        ..v
+    };
+    let back: Containers = from_str(&to_string(&v).unwrap()).unwrap();
+    assert_eq!(back.hobbies, Some(vec![]));
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "salvo")]
+//! The salvo `Query` extractor, through a handler.
+
+use nb_serde_query::{
+    options::ParseOptions,
+    salvo::{Query, QueryConfig},
+};
+use salvo_core::{
+    http::{ResBody, StatusCode},
+    prelude::*,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct Search {
+    q: String,
+    page: Option<u32>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[handler]
+async fn search(query: Query<Search>) -> String {
+    let v = query.0;
+    format!(
+        "{} page {} tags {}",
+        v.q,
+        v.page.unwrap_or(1),
+        v.tags.join("+")
+    )
+}
+
+// Calls the handler with the query of `uri` and `config` as an extension of the request, the
+// way a hoop registers it.
+async fn call(uri: &str, config: Option<QueryConfig>) -> (StatusCode, String) {
+    let mut req = Request::new();
+    req.set_uri(uri.parse().unwrap());
+    if let Some(config) = config {
+        req.extensions_mut().insert(config);
+    }
+    let mut res = Response::new();
+    let mut ctrl = FlowCtrl::new(vec![]);
+    search
+        .handle(&mut req, &mut Depot::new(), &mut res, &mut ctrl)
+        .await;
+    let body = match res.take_body() {
+        ResBody::Once(bytes) => String::from_utf8(bytes.to_vec()).unwrap(),
+        _ => String::new(),
+    };
+    (res.status_code.unwrap_or(StatusCode::OK), body)
+}
+
+#[tokio::test]
+async fn test_extractor() {
+    assert_eq!(
+        call("/search?q=rust&tags=a&page=2&tags=b", None).await,
+        (StatusCode::OK, "rust page 2 tags a+b".to_string())
+    );
+    assert_eq!(
+        call("/search", None).await,
+        (StatusCode::BAD_REQUEST, "missing field `q`".to_string())
+    );
+    assert_eq!(
+        call("/search?q=rust&page=x", None).await,
+        (
+            StatusCode::BAD_REQUEST,
+            "invalid value `x` for key `page`, expected u32: invalid digit found in string"
+                .to_string()
+        )
+    );
+
+    let config = QueryConfig::default().options(ParseOptions::new().max_values_per_key(2));
+    let (status, body) = call("/search?q=rust&tags=a&tags=b&tags=c", Some(config)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        serde_json::json!({
+            "error": "too many values for key `tags`, at most 2 allowed",
+            "limit": {"kind": "values_per_key", "key": "tags", "max": 2},
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_all_errors() {
+    let config = QueryConfig::default().all_errors(true).error_status(422);
+    let (status, body) = call("/search?page=x&tags=a", Some(config)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        serde_json::json!({"errors": [
+            {
+                "param": "page",
+                "message": "invalid value `x` for key `page`, expected u32: \
+                            invalid digit found in string",
+            },
+            {"param": "q", "message": "missing field `q`"},
+        ]})
+    );
+}
@@ -0,0 +1,286 @@
+//! The exact output of `to_string` for representative types, and the values `from_str` reads
+//! from pinned inputs.
+//!
+//! The output is a wire format for clients, so any change to these strings is a format change
+//! and must be intended. Maps are `BTreeMap`s, or sorted with `to_string_sorted`, because the
+//! order of a `HashMap` is not deterministic.
+
+use nb_serde_query::{
+    from_str,
+    options::{NestingStyle, SerializerOptions},
+    to_string, to_string_sorted, to_string_with, Array, CommaSeparated, Separated,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+
+// Pin both directions: `value` is written as `expected`, and `expected` reads as `value`.
+fn snapshot<T>(value: &T, expected: &str)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    assert_eq!(to_string(value).unwrap(), expected, "{:?}", value);
+    assert_eq!(&from_str::<T>(expected).unwrap(), value, "{}", expected);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Scalars {
+    flag: bool,
+    small: i8,
+    big: u64,
+    ratio: f64,
+    initial: char,
+    name: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Optionals {
+    first: Option<u32>,
+    middle: Option<String>,
+    last: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Sequences {
+    first: Vec<u32>,
+    page: u32,
+    #[serde(default)]
+    middle: Vec<String>,
+    sort: String,
+    #[serde(default)]
+    last: Vec<bool>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Order {
+    Newest,
+    Oldest,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Bounds {
+    min: i32,
+    max: i32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Point(f64, f64);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Paging {
+    page: u32,
+    per_page: u32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Search {
+    q: String,
+    order: Order,
+    near: Point,
+    #[serde(flatten)]
+    paging: Paging,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Filter {
+    bounds: Bounds,
+    labels: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Wrappers {
+    json: Array<u32>,
+    ids: CommaSeparated<u32>,
+    tags: Separated<String, '|'>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct WithMap {
+    name: String,
+    labels: BTreeMap<String, String>,
+}
+
+#[test]
+fn test_scalars() {
+    snapshot(
+        &Scalars {
+            flag: true,
+            small: -128,
+            big: u64::MAX,
+            ratio: 0.5,
+            initial: 'é',
+            name: "Ada Lovelace".to_string(),
+        },
+        "flag=true&small=-128&big=18446744073709551615&ratio=0.5&initial=%C3%A9\
+         &name=Ada%20Lovelace",
+    );
+    snapshot(
+        &Scalars {
+            flag: false,
+            small: 0,
+            big: 0,
+            ratio: -1e-7,
+            initial: '&',
+            name: "a=b&c/d?e#f+g%h".to_string(),
+        },
+        "flag=false&small=0&big=0&ratio=-0.0000001&initial=%26\
+         &name=a%3Db%26c%2Fd%3Fe%23f%2Bg%25h",
+    );
+}
+
+#[test]
+fn test_optionals() {
+    let cases = [
+        (None, None, None, ""),
+        (Some(1), None, None, "first=1"),
+        (None, Some(""), None, "middle="),
+        (None, Some("x"), None, "middle=x"),
+        (None, None, Some(false), "last=false"),
+        (
+            Some(0),
+            Some("y z"),
+            Some(true),
+            "first=0&middle=y%20z&last=true",
+        ),
+    ];
+    for (first, middle, last, expected) in cases {
+        let v = Optionals {
+            first,
+            middle: middle.map(str::to_string),
+            last,
+        };
+        snapshot(&v, expected);
+    }
+}
+
+#[test]
+fn test_sequences() {
+    snapshot(
+        &Sequences {
+            first: vec![3, 1, 2],
+            page: 1,
+            middle: vec!["a".to_string(), String::new(), "b c".to_string()],
+            sort: "id".to_string(),
+            last: vec![true, false],
+        },
+        "first=3&first=1&first=2&page=1&middle=a&middle=&middle=b%20c&sort=id\
+         &last=true&last=false",
+    );
+    snapshot(
+        &Sequences {
+            first: vec![7],
+            page: 2,
+            middle: vec![],
+            sort: String::new(),
+            last: vec![],
+        },
+        "first=7&page=2&sort=",
+    );
+}
+
+#[test]
+fn test_nested() {
+    snapshot(
+        &Search {
+            q: "rust".to_string(),
+            order: Order::Oldest,
+            near: Point(52.5, 13.25),
+            paging: Paging {
+                page: 3,
+                per_page: 50,
+            },
+        },
+        "q=rust&order=oldest&near=52.5,13.25&page=3&per_page=50",
+    );
+
+    let v = Filter {
+        bounds: Bounds { min: -5, max: 10 },
+        labels: BTreeMap::from([
+            ("env".to_string(), "prod".to_string()),
+            ("app".to_string(), "api".to_string()),
+        ]),
+    };
+    let brackets = SerializerOptions::new().nesting_style(NestingStyle::Brackets);
+    // maps are nested like structs, brackets aren't encoded
+    let s = to_string_with(&v, &brackets).unwrap();
+    assert_eq!(
+        s,
+        "bounds[min]=-5&bounds[max]=10&labels[app]=api&labels[env]=prod"
+    );
+    assert_eq!(from_str::<Filter>(&s).unwrap(), v);
+    let s = "bounds%5Bmin%5D=-5&bounds%5Bmax%5D=10&labels%5Bapp%5D=api&labels%5Benv%5D=prod";
+    assert_eq!(from_str::<Filter>(s).unwrap(), v);
+    let v: Filter = from_str("bounds[min]=1&bounds[max]=2&labels[a]=b").unwrap();
+    assert_eq!(v.bounds, Bounds { min: 1, max: 2 });
+    assert!(to_string(&v)
+        .unwrap_err()
+        .to_string()
+        .contains("cannot serialize nested struct `Bounds`"));
+}
+
+#[test]
+fn test_wrappers() {
+    snapshot(
+        &Wrappers {
+            json: Array(vec![1, 2]),
+            ids: Separated(vec![4, 5, 6]),
+            tags: Separated(vec!["a b".to_string(), "c|d".to_string()]),
+        },
+        "json=%5B1%2C2%5D&ids=4,5,6&tags=a%20b|c%257Cd",
+    );
+    snapshot(
+        &Wrappers {
+            json: Array(vec![]),
+            ids: Separated(vec![]),
+            tags: Separated(vec![]),
+        },
+        "json=%5B%5D&ids=&tags=",
+    );
+}
+
+#[test]
+fn test_maps() {
+    snapshot(
+        &BTreeMap::from([
+            ("b".to_string(), "2".to_string()),
+            ("a".to_string(), "1".to_string()),
+            ("a b".to_string(), "x&y".to_string()),
+        ]),
+        "a=1&a%20b=x%26y&b=2",
+    );
+    // without a nesting style, a map field is rejected like a nested struct
+    let v = WithMap {
+        name: "n".to_string(),
+        labels: BTreeMap::from([
+            ("env".to_string(), "prod".to_string()),
+            ("app".to_string(), "api".to_string()),
+        ]),
+    };
+    assert_eq!(
+        to_string(&v).unwrap_err().to_string(),
+        "cannot serialize nested map for key `labels`, use #[serde(flatten)] instead"
+    );
+
+    let v = HashMap::from([("z", 1), ("m", 2), ("a", 3)]);
+    assert_eq!(to_string_sorted(&v).unwrap(), "a=3&m=2&z=1");
+}
+
+// Inputs written differently from what `to_string` produces, read the same way.
+#[test]
+fn test_inputs() {
+    let v: Optionals = from_str("?last=&first=%32").unwrap();
+    assert_eq!(
+        v,
+        Optionals {
+            first: Some(2),
+            middle: None,
+            last: None,
+        }
+    );
+    let v: Sequences = from_str("first[1]=9&first[0]=8&page=1&sort=a+b&unknown=1").unwrap();
+    assert_eq!((v.first, v.sort), (vec![8, 9], "a b".to_string()));
+    let v: Scalars =
+        from_str("flag=true&small=1&big=2&ratio=1e3&initial=x&name=%F0%9F%A6%80").unwrap();
+    assert_eq!((v.ratio, v.name.as_str()), (1000.0, "🦀"));
+}
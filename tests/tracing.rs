@@ -0,0 +1,91 @@
+#![cfg(feature = "tracing")]
+
+use nb_serde_query::{from_str, from_str_with, options::ParseOptions, to_string};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+use tracing::Level;
+
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn capture(level: Level, f: impl FnOnce()) -> String {
+    let out = Captured::default();
+    let writer = out.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .without_time()
+        .finish();
+    tracing::subscriber::with_default(subscriber, f);
+    let out = out.0.lock().unwrap();
+    String::from_utf8(out.clone()).unwrap()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Search {
+    q: String,
+    page: u32,
+}
+
+#[test]
+fn test_no_warnings_on_success() {
+    let out = capture(Level::WARN, || {
+        let v: Search = from_str("q=rust&page=2&debug=1").unwrap();
+        to_string(&v).unwrap();
+    });
+    assert_eq!(out, "");
+}
+
+#[test]
+fn test_warn_on_failure() {
+    let s = "q=rust&page=0123456789abcdefSECRET";
+    let out = capture(Level::WARN, || {
+        assert!(from_str::<Search>(s).is_err());
+    });
+    assert!(
+        out.contains("failed to deserialize query string"),
+        "{}",
+        out
+    );
+    assert!(out.contains("kind=InvalidNumber"), "{}", out);
+    assert!(out.contains("key=\"page\""), "{}", out);
+    assert!(out.contains("value=\"0123456789abcdef\""), "{}", out);
+    assert!(!out.contains("SECRET"), "{}", out);
+
+    let opts = ParseOptions::new().trace_full_values(true);
+    let out = capture(Level::WARN, || {
+        assert!(from_str_with::<Search>(s, &opts).is_err());
+    });
+    assert!(out.contains("value=\"0123456789abcdefSECRET\""), "{}", out);
+
+    let opts = ParseOptions::new().max_pairs(1);
+    let out = capture(Level::WARN, || {
+        assert!(from_str_with::<Search>(s, &opts).is_err());
+    });
+    assert!(out.contains("failed to parse query string"), "{}", out);
+    assert!(out.contains("kind=LimitExceeded"), "{}", out);
+}
+
+#[test]
+fn test_debug_spans() {
+    let out = capture(Level::DEBUG, || {
+        let v: Search = from_str("q=rust&page=2&debug=1&debug=2").unwrap();
+        to_string(&v).unwrap();
+    });
+    assert!(out.contains("from_str{pairs=4}"), "{}", out);
+    assert!(out.contains("unused keys keys=[\"debug\"]"), "{}", out);
+}
@@ -0,0 +1,55 @@
+//! Transcoding between query strings and JSON with `serde_transcode`, in both directions.
+//!
+//! Only flat queries are in scope: nested keys such as `a[b]=1` are kept as they are rather than
+//! turned into JSON objects, as asserted at the bottom of this file.
+
+use nb_serde_query::{Deserializer, Serializer};
+
+fn query_to_json(q: &str) -> String {
+    let mut out = Vec::new();
+    let mut de = Deserializer::try_from_str(q).unwrap();
+    serde_transcode::transcode(&mut de, &mut serde_json::Serializer::new(&mut out)).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn json_to_query(json: &str) -> String {
+    let mut ser = Serializer::new();
+    let mut de = serde_json::Deserializer::from_str(json);
+    serde_transcode::transcode(&mut de, &mut ser).unwrap();
+    ser.into_inner()
+}
+
+#[test]
+fn test_round_trip() {
+    for (q, json) in [
+        ("", "{}"),
+        ("a=1&b=x&c=true", r#"{"a":1,"b":"x","c":true}"#),
+        ("n=-3&f=1.5&e=", r#"{"n":-3,"f":1.5,"e":""}"#),
+        ("a=1&a=2&b=x%20y", r#"{"a":[1,2],"b":"x y"}"#),
+        ("s=%F0%9F%8E%89&t=a%26b%3Dc", r#"{"s":"🎉","t":"a&b=c"}"#),
+        ("zip=007&sign=%2B1", r#"{"zip":"007","sign":"+1"}"#),
+    ] {
+        assert_eq!(query_to_json(q), json, "{}", q);
+        assert_eq!(json_to_query(json), q, "{}", json);
+    }
+}
+
+#[test]
+fn test_json_to_query() {
+    assert_eq!(
+        json_to_query(r#"{"a":null,"b":[],"c":"","d":[true,false]}"#),
+        "c=&d=true&d=false"
+    );
+    assert_eq!(json_to_query(r#"{"1":2}"#), "1=2");
+
+    let mut de = serde_json::Deserializer::from_str("[1,2]");
+    assert!(serde_transcode::transcode(&mut de, &mut Serializer::new()).is_err());
+}
+
+#[test]
+fn test_nested_keys_stay_flat() {
+    assert_eq!(query_to_json("a[b]=1&a[c]=x"), r#"{"a[b]":1,"a[c]":"x"}"#);
+    assert_eq!(query_to_json("a[0]=x&a[1]=y"), r#"{"a[0]":"x","a[1]":"y"}"#);
+    assert_eq!(json_to_query(r#"{"a[b]":1}"#), "a%5Bb%5D=1");
+    assert_eq!(query_to_json("a%5Bb%5D=1"), r#"{"a[b]":1}"#);
+}
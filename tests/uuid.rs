@@ -0,0 +1,54 @@
+#![cfg(feature = "uuid")]
+
+use nb_serde_query::{error::ErrorKind, from_str, to_string};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const ID: Uuid = Uuid::from_u128(0x67e5_5044_10b1_426f_9247_bb68_0e5f_e0c8);
+const OTHER: Uuid = Uuid::from_u128(0x936d_a01f_9abd_4d9d_80c7_02af_85c8_22a8);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Lookup {
+    id: Uuid,
+    parent: Option<Uuid>,
+    related: Vec<Uuid>,
+}
+
+#[test]
+fn test_round_trip() {
+    let v = Lookup {
+        id: ID,
+        parent: None,
+        related: vec![ID, OTHER],
+    };
+    let s = to_string(&v).unwrap();
+    assert_eq!(
+        s,
+        "id=67e55044-10b1-426f-9247-bb680e5fe0c8\
+         &related=67e55044-10b1-426f-9247-bb680e5fe0c8\
+         &related=936da01f-9abd-4d9d-80c7-02af85c822a8"
+    );
+    assert_eq!(from_str::<Lookup>(&s).unwrap(), v);
+}
+
+#[test]
+fn test_simple_format() {
+    let v: Lookup =
+        from_str("id=67e5504410b1426f9247bb680e5fe0c8&parent=936DA01F9ABD4D9D80C702AF85C822A8&related=936da01f9abd4d9d80c702af85c822a8")
+            .unwrap();
+    assert_eq!(v.id, ID);
+    assert_eq!(v.parent, Some(OTHER));
+    assert_eq!(v.related, vec![OTHER]);
+}
+
+#[test]
+fn test_malformed() {
+    let err = from_str::<Lookup>(&format!("id={}&related=not-a-uuid", ID)).unwrap_err();
+    match err.kind {
+        ErrorKind::InvalidValue { key, expected } => {
+            assert_eq!(key, "related");
+            assert_eq!(expected, "a UUID string");
+        }
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+}
@@ -0,0 +1,42 @@
+//! Run with `wasm-pack test --headless --firefox --features wasm`.
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use nb_serde_query::wasm::{from_url_search_params, to_url_search_params};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_test::*;
+use web_sys::UrlSearchParams;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Search {
+    q: String,
+    page: Option<u32>,
+    tags: Vec<String>,
+}
+
+#[wasm_bindgen_test]
+fn test_round_trip() {
+    let v = Search {
+        q: "a b&c=d+ü".to_string(),
+        page: Some(2),
+        tags: vec!["x".to_string(), "y".to_string()],
+    };
+    let params = to_url_search_params(&v).unwrap();
+    assert_eq!(params.get("q").as_deref(), Some("a b&c=d+ü"));
+    assert_eq!(params.get_all("tags").length(), 2);
+    assert_eq!(from_url_search_params::<Search>(&params).unwrap(), v);
+}
+
+#[wasm_bindgen_test]
+fn test_from_browser_params() {
+    let params = UrlSearchParams::new_with_str("?q=rust+serde&tags=a&tags=b").unwrap();
+    assert_eq!(
+        from_url_search_params::<Search>(&params).unwrap(),
+        Search {
+            q: "rust serde".to_string(),
+            page: None,
+            tags: vec!["a".to_string(), "b".to_string()],
+        }
+    );
+}